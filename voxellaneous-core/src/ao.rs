@@ -0,0 +1,102 @@
+//! Classic per-vertex voxel ambient occlusion.
+//!
+//! Each corner of a voxel face is darkened by the voxels around it in the layer in front of
+//! the face: the two along its edges and the one diagonal to it. The G-buffer pass interpolates
+//! the four corner levels across the face, either computing them from neighboring voxels or
+//! reading them from a table baked on the CPU by [`bake_ao`].
+
+use glam::IVec3;
+use serde::{Deserialize, Serialize};
+
+use crate::scene::VoxelObject;
+
+/// Where the G-buffer pass gets ambient occlusion from.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AoMode {
+    /// Every face is fully unoccluded.
+    #[default]
+    Off,
+    /// Corner levels are computed by the raymarch shader from neighboring voxels.
+    Shader,
+    /// Corner levels are read from the table baked by [`bake_ao`].
+    Precomputed,
+}
+
+/// Occlusion level of a face corner, from 0 (fully occluded) to 3 (open).
+pub fn corner_ao(side1: bool, side2: bool, corner: bool) -> u32 {
+    if side1 && side2 {
+        0
+    } else {
+        3 - side1 as u32 - side2 as u32 - corner as u32
+    }
+}
+
+/// Occlusion levels of the four corners of the face of `voxel` with outward normal `sign` along
+/// `axis`, given a predicate telling which voxels are solid.
+///
+/// Corner `c` lies on the negative or positive side of axis `(axis + 1) % 3` per bit 0 of `c`,
+/// and of axis `(axis + 2) % 3` per bit 1.
+pub fn face_ao(solid: impl Fn(IVec3) -> bool, voxel: IVec3, axis: usize, sign: i32) -> [u32; 4] {
+    let mut front = voxel;
+    front[axis] += sign;
+    std::array::from_fn(|c| {
+        let (mut du, mut dv) = (IVec3::ZERO, IVec3::ZERO);
+        du[(axis + 1) % 3] = if c & 1 == 0 { -1 } else { 1 };
+        dv[(axis + 2) % 3] = if c & 2 == 0 { -1 } else { 1 };
+        corner_ao(solid(front + du), solid(front + dv), solid(front + du + dv))
+    })
+}
+
+/// Bit offset of a face corner in a baked entry, counting from bit 0 of its first byte. Each
+/// face takes one byte, ordered -X, +X, -Y, +Y, -Z, +Z, with its corners' levels in two bits each.
+pub fn ao_bit(axis: usize, sign: i32, corner: usize) -> u32 {
+    let face = axis * 2 + (sign > 0) as usize;
+    ((face * 4 + corner) * 2) as u32
+}
+
+/// Bakes the corner levels of every face of every voxel in the box `[min, max)` of a `dims`
+/// grid, in the voxel layout of [`VoxelObject`] restricted to that box.
+///
+/// Each entry packs 24 two-bit levels at [`ao_bit`] offsets into six bytes. Voxels outside the
+/// grid count as empty.
+pub fn bake_ao_region(
+    dims: [u32; 3],
+    solid: impl Fn(usize) -> bool,
+    min: [u32; 3],
+    max: [u32; 3],
+) -> Vec<[u8; 6]> {
+    let dims = IVec3::from(dims.map(|d| d as i32));
+    let is_solid = |v: IVec3| {
+        v.cmpge(IVec3::ZERO).all()
+            && v.cmplt(dims).all()
+            && solid((v.x + dims.x * (v.y + dims.y * v.z)) as usize)
+    };
+
+    let mut entries = Vec::new();
+    for z in min[2]..max[2] {
+        for y in min[1]..max[1] {
+            for x in min[0]..max[0] {
+                let voxel = IVec3::new(x as i32, y as i32, z as i32);
+                let mut entry = [0u8; 6];
+                for axis in 0..3 {
+                    for sign in [-1, 1] {
+                        for (corner, level) in
+                            face_ao(is_solid, voxel, axis, sign).into_iter().enumerate()
+                        {
+                            let bit = ao_bit(axis, sign, corner);
+                            entry[(bit / 8) as usize] |= (level << (bit % 8)) as u8;
+                        }
+                    }
+                }
+                entries.push(entry);
+            }
+        }
+    }
+    entries
+}
+
+/// Bakes the corner levels of every face of every voxel of `obj`.
+pub fn bake_ao(obj: &VoxelObject) -> Vec<[u8; 6]> {
+    bake_ao_region(obj.dims, |i| obj.voxels[i] != 0, [0; 3], obj.dims)
+}
@@ -0,0 +1,120 @@
+//! Objects split into fixed-size bricks of voxels that share one atlas.
+//!
+//! Each object keeps a table with one entry per brick of its grid, naming the atlas slot holding
+//! the brick's voxels. Bricks without solid voxels take no slot, so sparse objects cost little
+//! and the number and size of objects are bounded only by the atlas.
+
+/// Voxels along each edge of a brick.
+pub const BRICK_SIZE: u32 = 8;
+
+/// Voxels in a brick, in the voxel layout of [`VoxelObject`](crate::VoxelObject).
+pub const BRICK_VOXELS: usize = (BRICK_SIZE * BRICK_SIZE * BRICK_SIZE) as usize;
+
+/// Table entry of a brick without solid voxels.
+pub const EMPTY_BRICK: u32 = u32::MAX;
+
+/// Bricks along each axis of a `dims` grid; bricks on the far sides may stick out of it.
+pub fn brick_dims(dims: [u32; 3]) -> [u32; 3] {
+    dims.map(|d| d.div_ceil(BRICK_SIZE))
+}
+
+/// Index of voxel `local` within a brick.
+pub fn brick_local_index(local: [u32; 3]) -> usize {
+    (local[0] + BRICK_SIZE * (local[1] + BRICK_SIZE * local[2])) as usize
+}
+
+/// Copies brick `brick` of a `dims` grid laid out like [`VoxelObject::voxels`], filling the part
+/// sticking out of the grid with `fill`.
+///
+/// [`VoxelObject::voxels`]: crate::VoxelObject::voxels
+pub fn extract_brick<T: Copy>(
+    values: &[T],
+    dims: [u32; 3],
+    brick: [u32; 3],
+    fill: T,
+) -> [T; BRICK_VOXELS] {
+    let mut out = [fill; BRICK_VOXELS];
+    let origin = brick.map(|b| b * BRICK_SIZE);
+    let end = [0, 1, 2].map(|axis| (origin[axis] + BRICK_SIZE).min(dims[axis]));
+    for z in origin[2]..end[2] {
+        for y in origin[1]..end[1] {
+            let row = (origin[0] + dims[0] * (y + dims[1] * z)) as usize;
+            let local = brick_local_index([0, y - origin[1], z - origin[2]]);
+            let len = (end[0] - origin[0]) as usize;
+            out[local..local + len].copy_from_slice(&values[row..row + len]);
+        }
+    }
+    out
+}
+
+/// Bricks without a slot in `table` that writing `data`, laid out x-fastest, to the
+/// `extent`-sized box at `origin` of a `dims` grid puts solid voxels in; each needs a new slot.
+pub fn bricks_to_fill(
+    table: &[u32],
+    dims: [u32; 3],
+    origin: [u32; 3],
+    extent: [u32; 3],
+    data: &[u8],
+) -> u32 {
+    let bricks = brick_dims(dims);
+    let [ex, ey, _] = extent;
+    let mut filled: Vec<usize> = data
+        .iter()
+        .enumerate()
+        .filter(|&(_, &voxel)| voxel != 0)
+        .map(|(i, _)| {
+            let i = i as u32;
+            let [x, y, z] = [
+                origin[0] + i % ex,
+                origin[1] + i / ex % ey,
+                origin[2] + i / (ex * ey),
+            ]
+            .map(|c| c / BRICK_SIZE);
+            (x + bricks[0] * (y + bricks[1] * z)) as usize
+        })
+        .filter(|&brick| table[brick] == EMPTY_BRICK)
+        .collect();
+    filled.sort_unstable();
+    filled.dedup();
+    filled.len() as u32
+}
+
+/// Hands out atlas slots, reusing freed ones before growing.
+#[derive(Clone, Debug, Default)]
+pub struct BrickAllocator {
+    free: Vec<u32>,
+    len: u32,
+}
+
+impl BrickAllocator {
+    pub fn new() -> Self {
+        BrickAllocator::default()
+    }
+
+    pub fn allocate(&mut self) -> u32 {
+        self.free.pop().unwrap_or_else(|| {
+            self.len += 1;
+            self.len - 1
+        })
+    }
+
+    /// Returns `slot` for reuse; it must not be in the free list already.
+    pub fn free(&mut self, slot: u32) {
+        debug_assert!(slot < self.len && !self.free.contains(&slot));
+        self.free.push(slot);
+    }
+
+    /// Slots handed out so far, freed ones included; the atlas needs room for this many.
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Slots currently handed out.
+    pub fn in_use(&self) -> u32 {
+        self.len - self.free.len() as u32
+    }
+}
@@ -0,0 +1,320 @@
+//! Bounding volume hierarchy over axis-aligned boxes, used to skip objects a query cannot touch.
+
+use glam::{Mat4, Vec3, Vec4, Vec4Swizzles};
+
+use crate::constants::CUBE_VERTICES;
+use crate::scene::VoxelObject;
+
+/// An axis-aligned box; empty when `min` exceeds `max` on any axis.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub const EMPTY: Aabb = Aabb {
+        min: Vec3::INFINITY,
+        max: Vec3::NEG_INFINITY,
+    };
+
+    /// World-space bounds of the unit cube placed by `model_matrix`.
+    pub fn of_unit_cube(model_matrix: Mat4) -> Self {
+        CUBE_VERTICES.iter().fold(Aabb::EMPTY, |bounds, vertex| {
+            let corner = model_matrix.transform_point3(Vec3::from(vertex.position));
+            bounds.union(&Aabb {
+                min: corner,
+                max: corner,
+            })
+        })
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.cmple(other.max).all() && other.min.cmple(self.max).all()
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Ray parameters where `origin + t * dir` enters and leaves the box, clipped to
+    /// `[0, t_max]`; `inv_dir` is the reciprocal of `dir`.
+    pub fn ray_span(&self, origin: Vec3, inv_dir: Vec3, t_max: f32) -> Option<(f32, f32)> {
+        let t0 = (self.min - origin) * inv_dir;
+        let t1 = (self.max - origin) * inv_dir;
+        // `max_element` and `min_element` skip the NaNs of axis-parallel rays on a slab's plane.
+        let near = t0.min(t1).max_element().max(0.0);
+        let far = t0.max(t1).min_element().min(t_max);
+        (near <= far).then_some((near, far))
+    }
+}
+
+/// The volume a view-projection matrix maps into wgpu's clip space, bounded by six planes.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Frustum {
+    /// Planes as `(normal, offset)` with the inside where `normal · p + offset >= 0`.
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes of `vp_matrix`, which must map depth to `[0, 1]` like the one passed
+    /// to [`Renderer::render`](crate::Renderer::render).
+    pub fn new(vp_matrix: Mat4) -> Self {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|i| vp_matrix.row(i));
+        Frustum {
+            planes: [w + x, w - x, w + y, w - y, z, w - z],
+        }
+    }
+
+    /// Whether `bounds` may reach inside. Boxes near the frustum's edges can pass without
+    /// touching it, but boxes that do are never rejected.
+    pub fn intersects(&self, bounds: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner furthest along the plane's normal.
+            let normal = plane.xyz();
+            let corner = Vec3::select(normal.cmpge(Vec3::ZERO), bounds.max, bounds.min);
+            normal.dot(corner) + plane.w >= 0.0
+        })
+    }
+}
+
+/// Largest number of boxes kept in one leaf.
+const LEAF_SIZE: usize = 2;
+
+#[derive(Copy, Clone, Debug)]
+struct Node {
+    bounds: Aabb,
+    /// For leaves, the first of `count` entries of `Bvh::items`; otherwise the index of the
+    /// second child, the first one following its parent.
+    start: u32,
+    /// 0 for interior nodes.
+    count: u32,
+    /// `u32::MAX` for the root.
+    parent: u32,
+}
+
+/// A binary hierarchy over a list of boxes, split at the median along the widest axis.
+///
+/// Queries report boxes by their index in the list the hierarchy was built from.
+#[derive(Clone, Debug, Default)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    items: Vec<u32>,
+    bounds: Vec<Aabb>,
+    /// Leaf node holding each box.
+    leaves: Vec<u32>,
+}
+
+impl Bvh {
+    /// Builds the hierarchy over the world-space bounds of `objects`.
+    pub fn from_objects(objects: &[VoxelObject]) -> Self {
+        let bounds: Vec<Aabb> = objects.iter().map(VoxelObject::world_bounds).collect();
+        Bvh::new(&bounds)
+    }
+
+    pub fn new(bounds: &[Aabb]) -> Self {
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(bounds.len() * 2),
+            items: (0..bounds.len() as u32).collect(),
+            bounds: bounds.to_vec(),
+            leaves: vec![0; bounds.len()],
+        };
+        if !bounds.is_empty() {
+            bvh.build(0, bounds.len(), u32::MAX);
+        }
+        bvh
+    }
+
+    /// Appends the subtree over `items[start..end]`, returning the index of its root.
+    fn build(&mut self, start: usize, end: usize, parent: u32) -> usize {
+        let bounds = &self.bounds;
+        let items = &mut self.items[start..end];
+        let node_bounds = items
+            .iter()
+            .fold(Aabb::EMPTY, |acc, &i| acc.union(&bounds[i as usize]));
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            bounds: node_bounds,
+            start: start as u32,
+            count: items.len() as u32,
+            parent,
+        });
+        if items.len() <= LEAF_SIZE {
+            for &item in items.iter() {
+                self.leaves[item as usize] = index as u32;
+            }
+            return index;
+        }
+
+        let centers = items.iter().fold(Aabb::EMPTY, |acc, &i| {
+            let center = bounds[i as usize].center();
+            acc.union(&Aabb {
+                min: center,
+                max: center,
+            })
+        });
+        let axis = (centers.max - centers.min).max_position();
+        let mid = items.len() / 2;
+        items.select_nth_unstable_by(mid, |&a, &b| {
+            let a = bounds[a as usize].center()[axis];
+            let b = bounds[b as usize].center()[axis];
+            a.total_cmp(&b)
+        });
+
+        self.build(start, start + mid, index as u32);
+        let second = self.build(start + mid, end, index as u32);
+        self.nodes[index].start = second as u32;
+        self.nodes[index].count = 0;
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.bounds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bounds.is_empty()
+    }
+
+    /// Nodes as `(bounds, start, count)`, root first, with `start` and `count` as in the private
+    /// `Node`: leaves cover `count` entries of [`Bvh::items`] from `start`, while interior nodes
+    /// have a `count` of 0 and their children at their own index plus one and at `start`.
+    pub(crate) fn flat_nodes(&self) -> impl Iterator<Item = (Aabb, u32, u32)> + '_ {
+        self.nodes
+            .iter()
+            .map(|node| (node.bounds, node.start, node.count))
+    }
+
+    /// Box indices grouped by leaf, see [`Bvh::flat_nodes`].
+    pub(crate) fn items(&self) -> &[u32] {
+        &self.items
+    }
+
+    /// Bounds of box `index`.
+    pub fn bounds(&self, index: usize) -> Aabb {
+        self.bounds[index]
+    }
+
+    /// Moves box `index` to `bounds`, growing or shrinking the nodes above it.
+    ///
+    /// The tree keeps its shape, so queries slow down as boxes move far from where they were
+    /// built; rebuild with [`Bvh::new`] after large changes.
+    pub fn refit(&mut self, index: usize, bounds: Aabb) {
+        self.bounds[index] = bounds;
+        let mut i = self.leaves[index];
+        while i != u32::MAX {
+            let node = self.nodes[i as usize];
+            self.nodes[i as usize].bounds = if node.count > 0 {
+                let (start, end) = (node.start as usize, (node.start + node.count) as usize);
+                self.items[start..end]
+                    .iter()
+                    .fold(Aabb::EMPTY, |acc, &item| {
+                        acc.union(&self.bounds[item as usize])
+                    })
+            } else {
+                self.nodes[i as usize + 1]
+                    .bounds
+                    .union(&self.nodes[node.start as usize].bounds)
+            };
+            i = node.parent;
+        }
+    }
+
+    /// Visits every box intersecting `frustum`, see [`Frustum::intersects`].
+    pub fn query_frustum(&self, frustum: &Frustum, visit: impl FnMut(usize)) {
+        self.query(|bounds| frustum.intersects(bounds), visit);
+    }
+
+    /// Visits every box overlapping `bounds`, touching boxes included.
+    pub fn query_aabb(&self, bounds: &Aabb, visit: impl FnMut(usize)) {
+        self.query(|node| node.intersects(bounds), visit);
+    }
+
+    /// Visits the boxes of the leaves reached through nodes passing `test`, if they pass it too.
+    fn query(&self, test: impl Fn(&Aabb) -> bool, mut visit: impl FnMut(usize)) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !test(&node.bounds) {
+                continue;
+            }
+            if node.count == 0 {
+                stack.extend([node.start as usize, index + 1]);
+                continue;
+            }
+            let (start, end) = (node.start as usize, (node.start + node.count) as usize);
+            for &item in &self.items[start..end] {
+                if test(&self.bounds[item as usize]) {
+                    visit(item as usize);
+                }
+            }
+        }
+    }
+
+    /// Visits the boxes `origin + t * dir` passes through for `t` in `[0, t_max]`, nearer
+    /// subtrees first, with the ray parameter where it enters each box.
+    ///
+    /// `visit` may return a smaller `t_max` for the rest of the traversal, so a nearest-hit query
+    /// can shorten the ray to its closest hit so far and skip everything behind it.
+    pub fn raycast(
+        &self,
+        origin: Vec3,
+        dir: Vec3,
+        mut t_max: f32,
+        mut visit: impl FnMut(usize, f32) -> Option<f32>,
+    ) {
+        let Some(root) = self.nodes.first() else {
+            return;
+        };
+        let inv_dir = dir.recip();
+        let Some((t_root, _)) = root.bounds.ray_span(origin, inv_dir, t_max) else {
+            return;
+        };
+
+        let mut stack = vec![(0, t_root)];
+        while let Some((index, t_enter)) = stack.pop() {
+            if t_enter > t_max {
+                continue;
+            }
+            let node = &self.nodes[index];
+            if node.count > 0 {
+                let (start, end) = (node.start as usize, (node.start + node.count) as usize);
+                for &item in &self.items[start..end] {
+                    let item = item as usize;
+                    let Some((near, _)) = self.bounds[item].ray_span(origin, inv_dir, t_max) else {
+                        continue;
+                    };
+                    if let Some(t) = visit(item, near) {
+                        t_max = t_max.min(t);
+                    }
+                }
+                continue;
+            }
+
+            let children = [index + 1, node.start as usize];
+            let spans = children.map(|child| {
+                self.nodes[child]
+                    .bounds
+                    .ray_span(origin, inv_dir, t_max)
+                    .map(|(near, _)| (child, near))
+            });
+            // Push the farther child first so the nearer one is visited first.
+            match spans {
+                [Some(a), Some(b)] if a.1 <= b.1 => stack.extend([b, a]),
+                [Some(a), Some(b)] => stack.extend([a, b]),
+                [Some(hit), None] | [None, Some(hit)] => stack.push(hit),
+                [None, None] => {}
+            }
+        }
+    }
+}
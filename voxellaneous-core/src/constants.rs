@@ -1,7 +1,7 @@
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
-    position: [f32; 3],
+    pub(crate) position: [f32; 3],
 }
 
 pub const CUBE_VERTICES: &[Vertex] = &[
@@ -79,12 +79,12 @@ pub const CUBE_VERTICES: &[Vertex] = &[
     },
 ];
 
+/// Two triangles per face of the unit cube, counter-clockwise seen from outside.
 pub const CUBE_INDICES: &[u16] = &[
-    // Front face
-    0, 1, 2, 0, 2, 3, // Back face
-    4, 5, 6, 4, 6, 7, // Top face
-    8, 9, 10, 8, 10, 11, // Bottom face
-    12, 13, 14, 12, 14, 15, // Right face
-    16, 17, 18, 16, 18, 19, // Left face
-    20, 21, 22, 20, 22, 23,
+    0, 1, 2, 0, 2, 3, // front (+Z)
+    4, 6, 5, 4, 7, 6, // back (-Z)
+    8, 10, 9, 8, 11, 10, // top (+Y)
+    12, 13, 14, 12, 14, 15, // bottom (-Y)
+    16, 17, 18, 16, 18, 19, // right (+X)
+    20, 22, 21, 20, 23, 22, // left (-X)
 ];
@@ -0,0 +1,218 @@
+//! glTF 2.0 binary (`.glb`) export.
+
+use glam::{Mat4, Quat, Vec3};
+use serde_json::{json, Value};
+
+use crate::meshing::{greedy_mesh, Mesh};
+use crate::primitives::RGBA;
+use crate::scene::{InvalidReason, ObjectField, Scene, SceneError, VoxelObject};
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_VERSION: u32 = 2;
+const CHUNK_JSON: &[u8; 4] = b"JSON";
+const CHUNK_BIN: &[u8; 4] = b"BIN\0";
+
+const COMPONENT_FLOAT: u32 = 5126;
+const COMPONENT_UNSIGNED_INT: u32 = 5125;
+const TARGET_ARRAY_BUFFER: u32 = 34962;
+const TARGET_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+/// Accumulates the binary chunk along with the buffer views and accessors describing it.
+#[derive(Default)]
+struct Buffers {
+    bin: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl Buffers {
+    fn push(&mut self, bytes: &[u8], target: u32) -> usize {
+        self.bin.resize(self.bin.len().next_multiple_of(4), 0);
+        self.views.push(json!({
+            "buffer": 0,
+            "byteOffset": self.bin.len(),
+            "byteLength": bytes.len(),
+            "target": target,
+        }));
+        self.bin.extend_from_slice(bytes);
+        self.views.len() - 1
+    }
+
+    fn push_vec3(&mut self, values: &[[f32; 3]], with_bounds: bool) -> usize {
+        let view = self.push(bytemuck::cast_slice(values), TARGET_ARRAY_BUFFER);
+        let mut accessor = json!({
+            "bufferView": view,
+            "componentType": COMPONENT_FLOAT,
+            "count": values.len(),
+            "type": "VEC3",
+        });
+        if with_bounds {
+            let (mut min, mut max) = ([f32::MAX; 3], [f32::MIN; 3]);
+            for v in values {
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(v[axis]);
+                    max[axis] = max[axis].max(v[axis]);
+                }
+            }
+            accessor["min"] = json!(min);
+            accessor["max"] = json!(max);
+        }
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    fn push_vec4(&mut self, values: &[[f32; 4]]) -> usize {
+        let view = self.push(bytemuck::cast_slice(values), TARGET_ARRAY_BUFFER);
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": COMPONENT_FLOAT,
+            "count": values.len(),
+            "type": "VEC4",
+        }));
+        self.accessors.len() - 1
+    }
+
+    fn push_indices(&mut self, indices: &[u32]) -> usize {
+        let view = self.push(bytemuck::cast_slice(indices), TARGET_ELEMENT_ARRAY_BUFFER);
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": COMPONENT_UNSIGNED_INT,
+            "count": indices.len(),
+            "type": "SCALAR",
+        }));
+        self.accessors.len() - 1
+    }
+}
+
+/// glTF vertex colors are linear; palette colors are sRGB.
+fn linear_color(color: &RGBA) -> [f32; 4] {
+    let linear = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    [
+        linear(color.0),
+        linear(color.1),
+        linear(color.2),
+        color.3 as f32 / 255.0,
+    ]
+}
+
+fn primitive(mesh: &Mesh, palette: &[[f32; 4]], buffers: &mut Buffers) -> Value {
+    let colors: Vec<[f32; 4]> = mesh
+        .palette_indices
+        .iter()
+        .map(|&i| palette[i as usize])
+        .collect();
+    json!({
+        "attributes": {
+            "POSITION": buffers.push_vec3(&mesh.positions, true),
+            "NORMAL": buffers.push_vec3(&mesh.normals, false),
+            "COLOR_0": buffers.push_vec4(&colors),
+        },
+        "indices": buffers.push_indices(&mesh.indices),
+        "material": 0,
+    })
+}
+
+/// Translation, rotation and scale of the object's node; glTF requires node matrices to be
+/// decomposable, so transforms with shear or projection are rejected.
+fn node_trs(obj: &VoxelObject) -> Result<(Vec3, Quat, Vec3), SceneError> {
+    let matrix = obj.transform.matrix();
+    let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+    let decomposed = Mat4::from_scale_rotation_translation(scale, rotation, translation);
+    let largest = matrix.abs().to_cols_array().into_iter().fold(1.0, f32::max);
+    if !matrix.abs_diff_eq(decomposed, 1e-4 * largest) {
+        return Err(SceneError::InvalidObject {
+            id: obj.id.clone(),
+            field: ObjectField::Transform,
+            reason: InvalidReason::NotDecomposable,
+        });
+    }
+    Ok((translation, rotation, scale))
+}
+
+/// Exports `scene` as a binary glTF.
+///
+/// Each object becomes a node named after its id, carrying its translation, rotation and scale
+/// and a greedy mesh in unit-cube space with palette colors as vertex colors. Objects without
+/// solid voxels become empty nodes. Fails on transforms that glTF nodes cannot hold.
+pub fn write_glb(scene: &Scene) -> Result<Vec<u8>, SceneError> {
+    scene.validate()?;
+
+    let palette: Vec<[f32; 4]> = scene.palette.iter().map(linear_color).collect();
+    let mut buffers = Buffers::default();
+    let mut nodes = Vec::with_capacity(scene.objects.len());
+    let mut meshes = Vec::new();
+    for obj in &scene.objects {
+        let (translation, rotation, scale) = node_trs(obj)?;
+        let mut node = json!({
+            "name": obj.id,
+            "translation": translation.to_array(),
+            "rotation": rotation.to_array(),
+            "scale": scale.to_array(),
+        });
+        let mesh = greedy_mesh(obj);
+        if !mesh.is_empty() {
+            node["mesh"] = json!(meshes.len());
+            meshes.push(json!({
+                "name": obj.id,
+                "primitives": [primitive(&mesh, &palette, &mut buffers)],
+            }));
+        }
+        nodes.push(node);
+    }
+    buffers.bin.resize(buffers.bin.len().next_multiple_of(4), 0);
+
+    // glTF forbids empty top-level arrays, so optional ones are only added when populated.
+    let mut document = json!({
+        "asset": { "version": "2.0", "generator": "voxellaneous" },
+        "scene": 0,
+        "scenes": [{}],
+        "materials": [{
+            "name": "palette",
+            "pbrMetallicRoughness": {
+                "baseColorFactor": [1.0, 1.0, 1.0, 1.0],
+                "metallicFactor": 0.0,
+                "roughnessFactor": 1.0,
+            },
+        }],
+    });
+    if !nodes.is_empty() {
+        document["scenes"][0]["nodes"] = json!((0..nodes.len()).collect::<Vec<_>>());
+        document["nodes"] = json!(nodes);
+    }
+    if !meshes.is_empty() {
+        document["meshes"] = json!(meshes);
+    }
+    if !buffers.bin.is_empty() {
+        document["buffers"] = json!([{ "byteLength": buffers.bin.len() }]);
+        document["bufferViews"] = json!(buffers.views);
+        document["accessors"] = json!(buffers.accessors);
+    }
+
+    let mut json = serde_json::to_vec(&document).expect("glTF document is serializable");
+    json.resize(json.len().next_multiple_of(4), b' ');
+
+    let mut total = 12 + 8 + json.len();
+    if !buffers.bin.is_empty() {
+        total += 8 + buffers.bin.len();
+    }
+    let mut glb = Vec::with_capacity(total);
+    glb.extend(GLB_MAGIC);
+    glb.extend(GLB_VERSION.to_le_bytes());
+    glb.extend((total as u32).to_le_bytes());
+    glb.extend((json.len() as u32).to_le_bytes());
+    glb.extend(CHUNK_JSON);
+    glb.extend(json);
+    if !buffers.bin.is_empty() {
+        glb.extend((buffers.bin.len() as u32).to_le_bytes());
+        glb.extend(CHUNK_BIN);
+        glb.extend(buffers.bin);
+    }
+    Ok(glb)
+}
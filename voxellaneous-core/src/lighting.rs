@@ -0,0 +1,374 @@
+//! Sun, ambient and local lights for the deferred lighting pass, and the screen tiles local
+//! lights are culled into.
+
+use std::fmt;
+
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use serde::{Deserialize, Serialize};
+
+/// Directional light at infinity.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct Sun {
+    /// World-space direction the light travels in; normalized by the renderer.
+    pub direction: [f32; 3],
+    /// Linear RGB color.
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+
+/// How the lighting pass occludes the sun and local lights.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ShadowMode {
+    /// Every surface facing a light receives it.
+    #[default]
+    Off,
+    /// One shadow ray per light, toward its center.
+    Hard,
+    /// [`Shadows::samples`] shadow rays per light, jittered over the light's extent.
+    Soft,
+}
+
+/// Voxel shadows, traced from each shaded pixel through every object toward each light.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct Shadows {
+    pub mode: ShadowMode,
+    /// World-space distance shadow rays start off the surface along its normal.
+    pub bias: f32,
+    /// Shadow rays per light in [`ShadowMode::Soft`].
+    pub samples: u32,
+    /// Radius of the disc point and spot lights are sampled over in [`ShadowMode::Soft`].
+    pub light_radius: f32,
+    /// Angular radius of the sun in radians in [`ShadowMode::Soft`].
+    pub sun_angle: f32,
+}
+
+impl Default for Shadows {
+    fn default() -> Self {
+        Shadows {
+            mode: ShadowMode::Off,
+            bias: 0.01,
+            samples: 8,
+            light_radius: 0.1,
+            sun_angle: 0.05,
+        }
+    }
+}
+
+/// Global lighting evaluated by the deferred lighting pass.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct Lighting {
+    pub sun: Sun,
+    /// Linear RGB light reaching every surface regardless of orientation.
+    pub ambient: [f32; 3],
+    #[serde(default)]
+    pub shadows: Shadows,
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Lighting {
+            sun: Sun {
+                direction: [-0.4, -1.0, -0.3],
+                color: [1.0; 3],
+                intensity: 1.0,
+            },
+            ambient: [0.2; 3],
+            shadows: Shadows::default(),
+        }
+    }
+}
+
+impl Sun {
+    /// Unit direction the light travels in, or zero if it has none.
+    pub fn unit_direction(&self) -> Vec3 {
+        Vec3::from(self.direction).normalize_or_zero()
+    }
+}
+
+impl Lighting {
+    /// Checks that the sun, ambient light and shadow settings can be shaded; errors name the
+    /// light `sun`.
+    pub fn validate(&self) -> Result<(), LightError> {
+        let id = || "sun".to_owned();
+        let shadows = &self.shadows;
+        let values = [
+            &self.sun.direction[..],
+            &self.sun.color,
+            &[self.sun.intensity],
+            &self.ambient,
+            &[shadows.bias, shadows.light_radius, shadows.sun_angle],
+        ]
+        .concat();
+        if !values.iter().all(|v| v.is_finite()) {
+            return Err(LightError::NonFinite { id: id() });
+        }
+        if self.sun.unit_direction() == Vec3::ZERO {
+            return Err(LightError::ZeroDirection { id: id() });
+        }
+        if self.sun.intensity < 0.0 {
+            return Err(LightError::NegativeIntensity {
+                id: id(),
+                intensity: self.sun.intensity,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Side length in pixels of the screen tiles lights are binned into.
+pub const TILE_SIZE: u32 = 16;
+
+/// Omnidirectional local light.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct PointLight {
+    pub position: [f32; 3],
+    /// Linear RGB color.
+    pub color: [f32; 3],
+    pub intensity: f32,
+    /// Distance at which the light fades out completely.
+    pub range: f32,
+}
+
+/// Local light emitting a cone.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct SpotLight {
+    pub position: [f32; 3],
+    /// Cone axis; normalized by the renderer.
+    pub direction: [f32; 3],
+    /// Linear RGB color.
+    pub color: [f32; 3],
+    pub intensity: f32,
+    /// Distance at which the light fades out completely.
+    pub range: f32,
+    /// Half-angle in radians inside which the light is at full strength.
+    pub inner_angle: f32,
+    /// Half-angle in radians outside which the light is off.
+    pub outer_angle: f32,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Light {
+    Point(PointLight),
+    Spot(SpotLight),
+}
+
+/// Errors reported by the light list API.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LightError {
+    NotFound {
+        id: String,
+    },
+    Duplicate {
+        id: String,
+    },
+    /// A position, direction, color or intensity is NaN or infinite.
+    NonFinite {
+        id: String,
+    },
+    /// The direction the light shines in has zero length.
+    ZeroDirection {
+        id: String,
+    },
+    NegativeIntensity {
+        id: String,
+        intensity: f32,
+    },
+    InvalidRange {
+        id: String,
+        range: f32,
+    },
+    InvalidCone {
+        id: String,
+        inner: f32,
+        outer: f32,
+    },
+}
+
+impl fmt::Display for LightError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LightError::NotFound { id } => write!(f, "light '{id}' does not exist"),
+            LightError::Duplicate { id } => write!(f, "light '{id}' already exists"),
+            LightError::NonFinite { id } => {
+                write!(f, "light '{id}' contains NaN or infinite values")
+            }
+            LightError::ZeroDirection { id } => write!(f, "light '{id}' has no direction"),
+            LightError::NegativeIntensity { id, intensity } => write!(
+                f,
+                "light '{id}' has intensity {intensity}, expected a non-negative value"
+            ),
+            LightError::InvalidRange { id, range } => {
+                write!(f, "light '{id}' has range {range}, expected a positive value")
+            }
+            LightError::InvalidCone { id, inner, outer } => write!(
+                f,
+                "light '{id}' has cone angles {inner} and {outer}, expected 0 <= inner <= outer < π/2"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LightError {}
+
+impl Light {
+    pub fn position(&self) -> Vec3 {
+        match self {
+            Light::Point(light) => Vec3::from(light.position),
+            Light::Spot(light) => Vec3::from(light.position),
+        }
+    }
+
+    pub fn range(&self) -> f32 {
+        match self {
+            Light::Point(light) => light.range,
+            Light::Spot(light) => light.range,
+        }
+    }
+
+    /// Checks that the light can be shaded; `id` is only used for error reporting.
+    pub fn validate(&self, id: &str) -> Result<(), LightError> {
+        let (values, intensity, range): (Vec<f32>, f32, f32) = match self {
+            Light::Point(l) => (
+                [&l.position[..], &l.color, &[l.intensity]].concat(),
+                l.intensity,
+                l.range,
+            ),
+            Light::Spot(l) => (
+                [&l.position[..], &l.direction, &l.color, &[l.intensity]].concat(),
+                l.intensity,
+                l.range,
+            ),
+        };
+        if !values.iter().all(|v| v.is_finite()) {
+            return Err(LightError::NonFinite { id: id.to_owned() });
+        }
+        if let Light::Spot(l) = self {
+            if Vec3::from(l.direction) == Vec3::ZERO {
+                return Err(LightError::ZeroDirection { id: id.to_owned() });
+            }
+        }
+        if intensity < 0.0 {
+            return Err(LightError::NegativeIntensity {
+                id: id.to_owned(),
+                intensity,
+            });
+        }
+        if !(range.is_finite() && range > 0.0) {
+            return Err(LightError::InvalidRange {
+                id: id.to_owned(),
+                range,
+            });
+        }
+        if let Light::Spot(l) = self {
+            let valid = 0.0 <= l.inner_angle
+                && l.inner_angle <= l.outer_angle
+                && l.outer_angle < std::f32::consts::FRAC_PI_2;
+            if !valid {
+                return Err(LightError::InvalidCone {
+                    id: id.to_owned(),
+                    inner: l.inner_angle,
+                    outer: l.outer_angle,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Per-tile light lists for a `tiles_x`×`tiles_y` grid of [`TILE_SIZE`] pixel tiles.
+///
+/// Tile `x + y * tiles_x` (rows from the top) uses `indices[offset..offset + count]` with
+/// `[offset, count] = ranges[tile]`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TileBins {
+    pub tiles_x: u32,
+    pub tiles_y: u32,
+    pub ranges: Vec<[u32; 2]>,
+    pub indices: Vec<u32>,
+}
+
+/// Conservative pixel rectangle `[min_x, min_y, max_x, max_y]` covered by a sphere, or `None`
+/// if it lies entirely off screen.
+fn screen_bounds(vp: Mat4, center: Vec3, radius: f32, width: u32, height: u32) -> Option<[f32; 4]> {
+    let corners = (0..8).map(|corner| {
+        let offset = Vec3::new(
+            if corner & 1 == 0 { -radius } else { radius },
+            if corner & 2 == 0 { -radius } else { radius },
+            if corner & 4 == 0 { -radius } else { radius },
+        );
+        vp * (center + offset).extend(1.0)
+    });
+    let corners: Vec<Vec4> = corners.collect();
+    let behind = corners.iter().filter(|clip| clip.w <= 0.0).count();
+    if behind == corners.len() {
+        return None;
+    }
+    if behind > 0 {
+        // The bounding box straddles the camera plane; its projection is unbounded.
+        return Some([0.0, 0.0, width as f32, height as f32]);
+    }
+
+    let (mut min, mut max) = (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN));
+    for clip in corners {
+        let ndc = clip.truncate().truncate() / clip.w;
+        min = min.min(ndc);
+        max = max.max(ndc);
+    }
+    if max.x < -1.0 || min.x > 1.0 || max.y < -1.0 || min.y > 1.0 {
+        return None;
+    }
+    let to_pixels = |ndc: Vec2| {
+        Vec2::new(
+            (ndc.x * 0.5 + 0.5) * width as f32,
+            (0.5 - ndc.y * 0.5) * height as f32,
+        )
+    };
+    let (top_left, bottom_right) = (
+        to_pixels(Vec2::new(min.x, max.y)),
+        to_pixels(Vec2::new(max.x, min.y)),
+    );
+    Some([top_left.x, top_left.y, bottom_right.x, bottom_right.y])
+}
+
+/// Bins lights into screen tiles by the projected bounds of their range sphere.
+///
+/// Spot lights are bounded by the same sphere as point lights, so binning is conservative:
+/// a tile lists every light that can reach one of its pixels, and possibly a few more.
+pub fn bin_lights(lights: &[Light], vp_matrix: [f32; 16], width: u32, height: u32) -> TileBins {
+    if width == 0 || height == 0 {
+        return TileBins::default();
+    }
+    let vp = Mat4::from_cols_array(&vp_matrix);
+    let tiles_x = width.div_ceil(TILE_SIZE);
+    let tiles_y = height.div_ceil(TILE_SIZE);
+    let mut lists = vec![Vec::new(); (tiles_x * tiles_y) as usize];
+
+    for (index, light) in lights.iter().enumerate() {
+        let Some([x0, y0, x1, y1]) =
+            screen_bounds(vp, light.position(), light.range(), width, height)
+        else {
+            continue;
+        };
+        let tile = |pixel: f32, tiles: u32| ((pixel.max(0.0) as u32) / TILE_SIZE).min(tiles - 1);
+        for ty in tile(y0, tiles_y)..=tile(y1, tiles_y) {
+            for tx in tile(x0, tiles_x)..=tile(x1, tiles_x) {
+                lists[(tx + ty * tiles_x) as usize].push(index as u32);
+            }
+        }
+    }
+
+    let mut bins = TileBins {
+        tiles_x,
+        tiles_y,
+        ranges: Vec::with_capacity(lists.len()),
+        indices: Vec::new(),
+    };
+    for list in lists {
+        bins.ranges
+            .push([bins.indices.len() as u32, list.len() as u32]);
+        bins.indices.extend(list);
+    }
+    bins
+}
@@ -0,0 +1,139 @@
+use glam::Vec3;
+
+use crate::scene::VoxelObject;
+
+/// Indexed triangle mesh of a voxel object.
+///
+/// Positions are in the object's unit-cube space (`[-0.5, 0.5]^3`, like `CUBE_VERTICES`), so
+/// the object's model matrix places the mesh in the world. Every quad owns its four vertices,
+/// which carry the face normal and palette index; triangles wind counter-clockwise when seen
+/// from outside.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Mesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub palette_indices: Vec<u8>,
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    fn push_quad(&mut self, corners: [Vec3; 4], normal: Vec3, palette_index: u8, flip: bool) {
+        let base = self.positions.len() as u32;
+        for corner in corners {
+            self.positions.push(corner.to_array());
+            self.normals.push(normal.to_array());
+            self.palette_indices.push(palette_index);
+        }
+        let order: [u32; 6] = if flip {
+            [0, 2, 1, 0, 3, 2]
+        } else {
+            [0, 1, 2, 0, 2, 3]
+        };
+        self.indices.extend(order.map(|i| base + i));
+    }
+}
+
+/// Meshes `obj` with greedy face merging.
+///
+/// Faces shared by two solid voxels are culled, and coplanar faces with the same palette
+/// index are merged into maximal rectangles. Output order is fixed: axes X, Y, Z; negative
+/// before positive faces; slices, then rows, then columns in ascending order. `obj` must be
+/// valid (see [`VoxelObject::validate`]).
+pub fn greedy_mesh(obj: &VoxelObject) -> Mesh {
+    let dims = obj.dims.map(|d| d as i32);
+    let voxel = |p: [i32; 3]| -> u8 {
+        if (0..3).any(|a| p[a] < 0 || p[a] >= dims[a]) {
+            return 0;
+        }
+        obj.voxels[(p[0] + dims[0] * (p[1] + dims[1] * p[2])) as usize]
+    };
+    let inv_dims = Vec3::from(obj.dims.map(|d| d as f32)).recip();
+    let to_object = |p: [i32; 3]| Vec3::from(p.map(|c| c as f32)) * inv_dims - 0.5;
+    let add = |a: [i32; 3], b: [i32; 3]| [a[0] + b[0], a[1] + b[1], a[2] + b[2]];
+
+    let mut mesh = Mesh::default();
+    for axis in 0..3 {
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        let (nu, nv) = (dims[u] as usize, dims[v] as usize);
+        let mut mask = vec![0u8; nu * nv];
+
+        for sign in [-1, 1] {
+            let mut normal = Vec3::ZERO;
+            normal[axis] = sign as f32;
+
+            for slice in 0..dims[axis] {
+                // Palette index of each face on this slice that is not hidden by a neighbor.
+                for j in 0..nv {
+                    for i in 0..nu {
+                        let mut p = [0; 3];
+                        p[axis] = slice;
+                        p[u] = i as i32;
+                        p[v] = j as i32;
+                        let index = voxel(p);
+                        p[axis] += sign;
+                        mask[i + j * nu] = if index != 0 && voxel(p) == 0 {
+                            index
+                        } else {
+                            0
+                        };
+                    }
+                }
+
+                for j in 0..nv {
+                    let mut i = 0;
+                    while i < nu {
+                        let index = mask[i + j * nu];
+                        if index == 0 {
+                            i += 1;
+                            continue;
+                        }
+
+                        let mut w = 1;
+                        while i + w < nu && mask[i + w + j * nu] == index {
+                            w += 1;
+                        }
+                        let mut h = 1;
+                        while j + h < nv
+                            && mask[i + (j + h) * nu..i + w + (j + h) * nu]
+                                .iter()
+                                .all(|&m| m == index)
+                        {
+                            h += 1;
+                        }
+                        for row in j..j + h {
+                            mask[i + row * nu..i + w + row * nu].fill(0);
+                        }
+
+                        let mut origin = [0; 3];
+                        origin[axis] = if sign > 0 { slice + 1 } else { slice };
+                        origin[u] = i as i32;
+                        origin[v] = j as i32;
+                        let mut du = [0; 3];
+                        du[u] = w as i32;
+                        let mut dv = [0; 3];
+                        dv[v] = h as i32;
+
+                        // u × v points along +axis, so negative faces flip the winding.
+                        mesh.push_quad(
+                            [
+                                to_object(origin),
+                                to_object(add(origin, du)),
+                                to_object(add(add(origin, du), dv)),
+                                to_object(add(origin, dv)),
+                            ],
+                            normal,
+                            index,
+                            sign < 0,
+                        );
+                        i += w;
+                    }
+                }
+            }
+        }
+    }
+    mesh
+}
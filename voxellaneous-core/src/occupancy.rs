@@ -0,0 +1,118 @@
+//! Occupancy of an object's voxels at full resolution and in coarser cells, so traversals can
+//! skip empty space a cell at a time.
+
+/// Levels of an [`OccupancyPyramid`]: voxels, then cells of 2, 4 and 8 voxels along each axis.
+pub const OCCUPANCY_LEVELS: u32 = 4;
+
+/// Cells along each axis of `level` over a `dims` grid; cells on the far sides may stick out of
+/// it.
+pub fn level_dims(dims: [u32; 3], level: u32) -> [u32; 3] {
+    dims.map(|d| d.div_ceil(1 << level))
+}
+
+/// Word where `level` starts in the words of a pyramid over a `dims` grid, same as
+/// `occupancy_bit` in `shaders/dda.wgsl`.
+pub fn level_offset(dims: [u32; 3], level: u32) -> usize {
+    (0..level)
+        .map(|l| cell_count(level_dims(dims, l)).div_ceil(32))
+        .sum()
+}
+
+fn cell_count(dims: [u32; 3]) -> usize {
+    dims.iter().map(|&d| d as usize).product()
+}
+
+/// One bit per cell of every level, set where any voxel in the cell is non-empty.
+///
+/// Levels follow one another in [`OccupancyPyramid::words`], each starting on a new word with its
+/// cells in the voxel layout of [`VoxelObject`](crate::VoxelObject); level 0 holds the voxels
+/// themselves.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OccupancyPyramid {
+    dims: [u32; 3],
+    words: Vec<u32>,
+    /// First word and cells along each axis of every level.
+    levels: [(usize, [u32; 3]); OCCUPANCY_LEVELS as usize],
+}
+
+impl OccupancyPyramid {
+    pub fn new(voxels: &[u8], dims: [u32; 3]) -> Self {
+        let mut pyramid = OccupancyPyramid {
+            dims,
+            words: vec![0; level_offset(dims, OCCUPANCY_LEVELS)],
+            levels: std::array::from_fn(|level| {
+                let level = level as u32;
+                (level_offset(dims, level), level_dims(dims, level))
+            }),
+        };
+        for (i, _) in voxels.iter().enumerate().filter(|(_, &v)| v != 0) {
+            pyramid.words[i / 32] |= 1 << (i % 32);
+        }
+        for level in 1..OCCUPANCY_LEVELS {
+            let [nx, ny, nz] = pyramid.levels[level as usize - 1].1;
+            for z in 0..nz {
+                for y in 0..ny {
+                    for x in 0..nx {
+                        if pyramid.is_occupied(level - 1, [x, y, z]) {
+                            pyramid.set(level, [x / 2, y / 2, z / 2], true);
+                        }
+                    }
+                }
+            }
+        }
+        pyramid
+    }
+
+    pub fn dims(&self) -> [u32; 3] {
+        self.dims
+    }
+
+    /// Bits of every level, as uploaded for the shaders.
+    pub fn words(&self) -> &[u32] {
+        &self.words
+    }
+
+    /// Whether any voxel of cell `cell` of `level` is non-empty.
+    pub fn is_occupied(&self, level: u32, cell: [u32; 3]) -> bool {
+        let bit = self.bit(level, cell);
+        self.words[bit / 32] & (1 << (bit % 32)) != 0
+    }
+
+    /// Marks `voxel` as non-empty or empty, updating the cells above it.
+    pub fn set_voxel(&mut self, voxel: [u32; 3], occupied: bool) {
+        if !self.set(0, voxel, occupied) {
+            return;
+        }
+        let mut cell = voxel;
+        for level in 1..OCCUPANCY_LEVELS {
+            let children = cell.map(|c| c & !1);
+            cell = cell.map(|c| c / 2);
+            let dims = self.levels[level as usize - 1].1;
+            let occupied = (0..8).any(|corner| {
+                let child = [0, 1, 2].map(|axis| children[axis] + (corner >> axis & 1));
+                (0..3).all(|axis| child[axis] < dims[axis]) && self.is_occupied(level - 1, child)
+            });
+            if !self.set(level, cell, occupied) {
+                return;
+            }
+        }
+    }
+
+    /// Sets the bit of `cell` of `level`, returning whether it changed.
+    fn set(&mut self, level: u32, cell: [u32; 3], occupied: bool) -> bool {
+        let bit = self.bit(level, cell);
+        let (word, mask) = (&mut self.words[bit / 32], 1 << (bit % 32));
+        let was = *word & mask != 0;
+        if occupied {
+            *word |= mask;
+        } else {
+            *word &= !mask;
+        }
+        was != occupied
+    }
+
+    fn bit(&self, level: u32, [x, y, z]: [u32; 3]) -> usize {
+        let (offset, [nx, ny, _]) = self.levels[level as usize];
+        offset * 32 + (x + nx * (y + ny * z)) as usize
+    }
+}
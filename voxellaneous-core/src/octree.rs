@@ -0,0 +1,393 @@
+//! Sparse voxel octrees, storing large and mostly empty objects in proportion to their detail
+//! rather than their size.
+//!
+//! Nodes whose voxels all hold the same palette index, empty ones included, are stored as a
+//! single word. The words form one flat buffer laid out for upload to the GPU as-is, see
+//! [`SparseVoxelOctree::nodes`].
+
+use std::ops::Range;
+
+use glam::{UVec3, Vec3};
+
+use crate::reference::RayHit;
+use crate::scene::VoxelObject;
+use crate::transform::Transform;
+
+/// Node word of a node without solid voxels.
+pub const EMPTY_NODE: u32 = 0;
+
+/// Flag of node words whose voxels all hold the palette index in their low byte.
+pub const LEAF_NODE: u32 = 1 << 31;
+
+/// A voxel grid as an octree over the smallest power-of-two cube holding it.
+///
+/// Every node is one `u32` word in [`SparseVoxelOctree::nodes`]: [`EMPTY_NODE`], a
+/// [`LEAF_NODE`] holding a palette index, or otherwise the index of the first of its eight
+/// children. Children follow one another, child `i` covering the octant at `x`, `y` and `z`
+/// offsets given by bits 0, 1 and 2 of `i`. Word 0 is the root. Voxels past the grid's `dims`
+/// are empty.
+///
+/// [`SparseVoxelOctree::update_region`] edits the nodes in place, so blocks of children it
+/// merges stay in the buffer, unreachable, until it reuses them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SparseVoxelOctree {
+    dims: [u32; 3],
+    /// Levels below the root; the root covers `1 << depth` voxels along each axis.
+    depth: u32,
+    nodes: Vec<u32>,
+    /// First words of the unreachable blocks of eight children.
+    free: Vec<u32>,
+}
+
+impl SparseVoxelOctree {
+    /// Builds the octree of a `dims` grid laid out like [`VoxelObject::voxels`].
+    pub fn new(voxels: &[u8], dims: [u32; 3]) -> Self {
+        let size = dims.iter().max().unwrap_or(&1).next_power_of_two();
+        let mut octree = SparseVoxelOctree {
+            dims,
+            depth: size.trailing_zeros(),
+            nodes: vec![EMPTY_NODE],
+            free: Vec::new(),
+        };
+        octree.nodes[0] = octree.build(voxels, [0; 3], size);
+        octree
+    }
+
+    pub fn from_object(obj: &VoxelObject) -> Self {
+        SparseVoxelOctree::new(&obj.voxels, obj.dims)
+    }
+
+    /// Returns the node word of the `size`-voxel cube at `lo`, appending its children if any.
+    fn build(&mut self, voxels: &[u8], lo: [u32; 3], size: u32) -> u32 {
+        let [nx, ny, nz] = self.dims;
+        if lo[0] >= nx || lo[1] >= ny || lo[2] >= nz {
+            return EMPTY_NODE;
+        }
+        if size == 1 {
+            let index = voxels[(lo[0] + nx * (lo[1] + ny * lo[2])) as usize];
+            return leaf(index);
+        }
+        let half = size / 2;
+        let children: [u32; 8] =
+            std::array::from_fn(|child| self.build(voxels, octant(lo, half, child), half));
+        // Children with subtrees have distinct words, so only uniform ones are all equal.
+        if children.iter().all(|&child| child == children[0]) {
+            return children[0];
+        }
+        let first = self.nodes.len() as u32;
+        self.nodes.extend(children);
+        first
+    }
+
+    /// Brings the nodes over the `extent`-sized box at `origin` up to date with `voxels`, the
+    /// whole grid laid out like [`VoxelObject::voxels`] after an edit of that box. Nodes
+    /// elsewhere keep their words.
+    ///
+    /// Returns the ranges of node words that changed, in ascending order, for uploads of just
+    /// those words.
+    pub fn update_region(
+        &mut self,
+        voxels: &[u8],
+        origin: [u32; 3],
+        extent: [u32; 3],
+    ) -> Vec<Range<usize>> {
+        let end = [0, 1, 2].map(|axis| {
+            origin[axis]
+                .saturating_add(extent[axis])
+                .min(self.dims[axis])
+        });
+        let mut changed = Vec::new();
+        let root = self.nodes[0];
+        let word = self.update(
+            voxels,
+            root,
+            [0; 3],
+            1 << self.depth,
+            [origin, end],
+            &mut changed,
+        );
+        if word != root {
+            self.nodes[0] = word;
+            changed.push(0..1);
+        }
+
+        changed.sort_unstable_by_key(|range| range.start);
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(changed.len());
+        for range in changed {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        merged
+    }
+
+    /// Returns the new word of the `size`-voxel node at `lo`, now `node`, after the voxels in
+    /// `[min, end)` changed. Children are rewritten in place, and the words written are added to
+    /// `changed`.
+    fn update(
+        &mut self,
+        voxels: &[u8],
+        node: u32,
+        lo: [u32; 3],
+        size: u32,
+        [min, end]: [[u32; 3]; 2],
+        changed: &mut Vec<Range<usize>>,
+    ) -> u32 {
+        if (0..3).any(|axis| lo[axis] + size <= min[axis] || lo[axis] >= end[axis]) {
+            return node;
+        }
+        if size == 1 {
+            let [nx, ny, _] = self.dims;
+            return leaf(voxels[(lo[0] + nx * (lo[1] + ny * lo[2])) as usize]);
+        }
+        let half = size / 2;
+        let subtree = node != EMPTY_NODE && node & LEAF_NODE == 0;
+        let mut children: [u32; 8] = if subtree {
+            std::array::from_fn(|child| self.nodes[node as usize + child])
+        } else {
+            [node; 8]
+        };
+        for (child, word) in children.iter_mut().enumerate() {
+            let lo = octant(lo, half, child);
+            let updated = self.update(voxels, *word, lo, half, [min, end], changed);
+            if subtree && updated != *word {
+                self.nodes[node as usize + child] = updated;
+                changed.push(node as usize + child..node as usize + child + 1);
+            }
+            *word = updated;
+        }
+
+        // As in `build`, only uniform children are all equal.
+        let uniform = children.iter().all(|&child| child == children[0]);
+        match (subtree, uniform) {
+            (true, true) => {
+                self.free.push(node);
+                children[0]
+            }
+            (true, false) => node,
+            (false, true) => children[0],
+            (false, false) => {
+                let first = match self.free.pop() {
+                    Some(first) => {
+                        self.nodes[first as usize..first as usize + 8].copy_from_slice(&children);
+                        first
+                    }
+                    None => {
+                        self.nodes.extend(children);
+                        self.nodes.len() as u32 - 8
+                    }
+                };
+                changed.push(first as usize..first as usize + 8);
+                first
+            }
+        }
+    }
+
+    pub fn dims(&self) -> [u32; 3] {
+        self.dims
+    }
+
+    /// Levels below the root, which covers `1 << depth` voxels along each axis.
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Node words, as uploaded for the shaders, including unreachable ones left by
+    /// [`SparseVoxelOctree::update_region`].
+    pub fn nodes(&self) -> &[u32] {
+        &self.nodes
+    }
+
+    /// Palette index of `voxel`, 0 where it is empty or outside the grid.
+    pub fn get(&self, voxel: [u32; 3]) -> u8 {
+        if (0..3).any(|axis| voxel[axis] >= self.dims[axis]) {
+            return 0;
+        }
+        let mut node = self.nodes[0];
+        let mut level = self.depth;
+        while node != EMPTY_NODE && node & LEAF_NODE == 0 {
+            level -= 1;
+            let child = (0..3)
+                .map(|axis| (voxel[axis] >> level & 1) << axis)
+                .sum::<u32>();
+            node = self.nodes[(node + child) as usize];
+        }
+        node as u8
+    }
+
+    /// Visits every solid voxel of the `extent`-sized box at `origin` with its palette index,
+    /// skipping empty nodes whole. The box is clipped to the grid and voxels come in no
+    /// particular order.
+    pub fn visit_region(
+        &self,
+        origin: [u32; 3],
+        extent: [u32; 3],
+        mut visit: impl FnMut([u32; 3], u8),
+    ) {
+        let end = [0, 1, 2].map(|axis| {
+            origin[axis]
+                .saturating_add(extent[axis])
+                .min(self.dims[axis])
+        });
+        let mut stack = vec![(self.nodes[0], [0; 3], 1 << self.depth)];
+        while let Some((node, lo, size)) = stack.pop() {
+            let hi = lo.map(|l| l + size);
+            if node == EMPTY_NODE
+                || (0..3).any(|axis| hi[axis] <= origin[axis] || lo[axis] >= end[axis])
+            {
+                continue;
+            }
+            if node & LEAF_NODE == 0 {
+                let half = size / 2;
+                stack.extend((0..8).map(|child| {
+                    (
+                        self.nodes[(node + child) as usize],
+                        octant(lo, half, child as usize),
+                        half,
+                    )
+                }));
+                continue;
+            }
+            let from = [0, 1, 2].map(|axis| lo[axis].max(origin[axis]));
+            let to = [0, 1, 2].map(|axis| hi[axis].min(end[axis]));
+            for z in from[2]..to[2] {
+                for y in from[1]..to[1] {
+                    for x in from[0]..to[0] {
+                        visit([x, y, z], node as u8);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Expands the octree back to a dense grid laid out like [`VoxelObject::voxels`].
+    pub fn to_voxels(&self) -> Vec<u8> {
+        let [nx, ny, nz] = self.dims.map(|d| d as usize);
+        let mut voxels = vec![0; nx * ny * nz];
+        self.visit_region([0; 3], self.dims, |[x, y, z], index| {
+            voxels[x as usize + nx * (y as usize + ny * z as usize)] = index;
+        });
+        voxels
+    }
+
+    pub fn to_object(&self, id: &str, transform: Transform) -> VoxelObject {
+        VoxelObject {
+            id: id.to_owned(),
+            transform,
+            dims: self.dims,
+            voxels: self.to_voxels(),
+        }
+    }
+
+    /// First solid voxel along `origin + t * dir` for `t` in `[0, t_limit]`, with the same
+    /// conventions as [`trace_object`](crate::reference::trace_object): the grid fills the unit
+    /// cube `[-0.5, 0.5]^3` and `t` is in units of `dir`, which need not be normalized.
+    ///
+    /// Nodes are visited nearest first, so the first leaf reached is the hit; `steps` counts the
+    /// nodes visited.
+    pub fn raycast(&self, origin: Vec3, dir: Vec3, t_limit: f32) -> Option<RayHit> {
+        // Same setup as `march`, so hits agree with the dense traversal.
+        let safe_dir = Vec3::select(dir.abs().cmplt(Vec3::splat(1e-8)), Vec3::splat(1e-8), dir);
+        let inv_dir = 1.0 / safe_dir;
+        let t0 = (Vec3::splat(-0.5) - origin) * inv_dir;
+        let t1 = (Vec3::splat(0.5) - origin) * inv_dir;
+        let t_near = t0.min(t1);
+        let t_exit = t0.max(t1).min_element().min(t_limit);
+        let t_start = t_near.max_element().max(0.0);
+        if t_start > t_exit {
+            return None;
+        }
+        let entry_axis = argmax(t_near);
+
+        let dims = UVec3::from(self.dims);
+        let dims_f = dims.as_vec3();
+        let start = (origin + t_start * safe_dir + 0.5) * dims_f;
+        let inv_dir_voxel = inv_dir / dims_f;
+        let positive = safe_dir.cmpgt(Vec3::ZERO);
+        // Where the ray enters a node and along which axis, if it reaches it before `t_exit`.
+        let span = |lo: UVec3, size: u32| {
+            let a = t_start + (lo.as_vec3() - start) * inv_dir_voxel;
+            let b = t_start + ((lo + size).as_vec3() - start) * inv_dir_voxel;
+            let near = a.min(b);
+            let enter = near.max_element();
+            (enter.max(t_start) <= a.max(b).min_element().min(t_exit)).then(|| {
+                if enter > t_start {
+                    (enter, argmax(near))
+                } else {
+                    (t_start, entry_axis)
+                }
+            })
+        };
+
+        if self.nodes[0] == EMPTY_NODE {
+            return None;
+        }
+        let mut steps = 0;
+        let root = (self.nodes[0], UVec3::ZERO, 1 << self.depth);
+        let mut stack = vec![(root, span(root.1, root.2)?)];
+        while let Some(((node, lo, size), (t, axis))) = stack.pop() {
+            steps += 1;
+            if node & LEAF_NODE != 0 {
+                let p = start + (t - t_start) * safe_dir * dims_f;
+                let mut voxel = p.floor().as_uvec3().clamp(lo, (lo + size).min(dims) - 1);
+                if t > t_start {
+                    voxel[axis] = if positive.test(axis) {
+                        lo[axis]
+                    } else {
+                        lo[axis] + size - 1
+                    };
+                }
+                let mut normal = [0.0; 3];
+                normal[axis] = if positive.test(axis) { -1.0 } else { 1.0 };
+                return Some(RayHit {
+                    palette_index: node as u8,
+                    voxel: voxel.to_array(),
+                    t,
+                    normal,
+                    steps,
+                });
+            }
+
+            let half = size / 2;
+            let mut children: Vec<_> = (0..8)
+                .filter_map(|child| {
+                    let word = self.nodes[(node + child) as usize];
+                    let lo = UVec3::from(octant(lo.to_array(), half, child as usize));
+                    if word == EMPTY_NODE {
+                        return None;
+                    }
+                    Some(((word, lo, half), span(lo, half)?))
+                })
+                .collect();
+            // Farthest first, so the nearest child is popped next.
+            children.sort_by(|a, b| b.1 .0.total_cmp(&a.1 .0));
+            stack.extend(children);
+        }
+        None
+    }
+}
+
+fn leaf(index: u8) -> u32 {
+    if index == 0 {
+        EMPTY_NODE
+    } else {
+        LEAF_NODE | index as u32
+    }
+}
+
+/// Corner of child `child` of the `2 * half`-voxel node at `lo`.
+fn octant(lo: [u32; 3], half: u32, child: usize) -> [u32; 3] {
+    [0, 1, 2].map(|axis| lo[axis] + (child as u32 >> axis & 1) * half)
+}
+
+/// Axis of the largest component, preferring earlier axes on ties like `march`.
+fn argmax(v: Vec3) -> usize {
+    if v.x >= v.y && v.x >= v.z {
+        0
+    } else if v.y >= v.z {
+        1
+    } else {
+        2
+    }
+}
@@ -0,0 +1,150 @@
+//! What lies under a pixel: the voxel the G-buffer pass wrote there.
+//!
+//! The G-buffer pass writes each hit's object, voxel, face and palette index to an `Rg32Uint`
+//! target that [`Renderer::pick`] reads back a texel of. [`Raycaster::pick`] and [`Scene::pick`]
+//! answer the same query on the CPU by tracing the pixel's ray through every object.
+//!
+//! [`Renderer::pick`]: crate::Renderer::pick
+
+use glam::{Mat4, Vec3, Vec4, Vec4Swizzles};
+use serde::Serialize;
+
+use crate::raycast::Raycaster;
+use crate::scene::Scene;
+
+/// The voxel under a pixel.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct PickHit {
+    pub object_id: String,
+    pub voxel: [u32; 3],
+    /// Object-space normal of the voxel face the pixel's ray enters through.
+    pub normal: [i32; 3],
+    pub palette_index: u8,
+}
+
+/// Pick ids fit in the low bits of the pick target's second channel, next to the face and the
+/// palette index; the color attachments leave no room for a wider target.
+pub(crate) const PICK_ID_BITS: u32 = 20;
+
+/// A texel of the pick target, as written by `pick_texel` in `shaders/shader.wgsl`.
+///
+/// `pick_id` is 0 where nothing was hit; faces are ordered -X, +X, -Y, +Y, -Z, +Z.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PickTexel {
+    pub voxel_index: u32,
+    pub pick_id: u32,
+    pub face: u32,
+    pub palette_index: u8,
+}
+
+impl PickTexel {
+    pub fn decode(texel: [u32; 2]) -> Self {
+        PickTexel {
+            voxel_index: texel[0],
+            pick_id: texel[1] & ((1 << PICK_ID_BITS) - 1),
+            face: (texel[1] >> PICK_ID_BITS) & 0xf,
+            palette_index: (texel[1] >> 24) as u8,
+        }
+    }
+
+    /// Resolves the texel against the object it was written for, which has `dims` voxels.
+    pub fn into_hit(self, object_id: String, dims: [u32; 3]) -> PickHit {
+        let [nx, ny, _] = dims;
+        let voxel = [
+            self.voxel_index % nx,
+            self.voxel_index / nx % ny,
+            self.voxel_index / (nx * ny),
+        ];
+        let mut normal = [0; 3];
+        normal[(self.face / 2) as usize] = if self.face.is_multiple_of(2) { -1 } else { 1 };
+        PickHit {
+            object_id,
+            voxel,
+            normal,
+            palette_index: self.palette_index,
+        }
+    }
+}
+
+impl Raycaster<'_> {
+    /// CPU counterpart of [`Renderer::pick`] for a frame rendered with the same `vp_matrix` and
+    /// `view_position` into a `width` × `height` target.
+    ///
+    /// Traces the ray through the center of pixel `(x, y)`, counted from the top left, through
+    /// every object and keeps the hit with the smallest depth, the first object winning ties as
+    /// in the depth test. Unlike the GPU, hits are not clipped against the near and far planes,
+    /// and rays pass through ignored palette indices.
+    ///
+    /// [`Renderer::pick`]: crate::Renderer::pick
+    pub fn pick(
+        &self,
+        vp_matrix: [f32; 16],
+        view_position: [f32; 3],
+        width: u32,
+        height: u32,
+        x: u32,
+        y: u32,
+    ) -> Option<PickHit> {
+        if x >= width || y >= height {
+            return None;
+        }
+        let vp = Mat4::from_cols_array(&vp_matrix);
+        let cam_ws = Vec3::from(view_position);
+        let point = vp.inverse()
+            * Vec4::new(
+                (x as f32 + 0.5) / width as f32 * 2.0 - 1.0,
+                1.0 - (y as f32 + 0.5) / height as f32 * 2.0,
+                0.5,
+                1.0,
+            );
+        let point_ws = point.xyz() / point.w;
+
+        let mut nearest: Option<(f32, PickHit)> = None;
+        for (index, obj) in self.scene().objects.iter().enumerate() {
+            let inv_model = self.inv_model(index);
+            let cam_os = inv_model.transform_point3(cam_ws);
+            let dir_os = (inv_model.transform_point3(point_ws) - cam_os).normalize();
+            let Some(hit) = self.march(index, cam_os, dir_os, f32::MAX) else {
+                continue;
+            };
+
+            let model = obj.transform.matrix();
+            let hit_clip = vp * model.transform_point3(cam_os + hit.t * dir_os).extend(1.0);
+            let depth = (hit_clip.z / hit_clip.w).clamp(0.0, 1.0);
+            if nearest
+                .as_ref()
+                .is_some_and(|(nearest, _)| depth >= *nearest)
+            {
+                continue;
+            }
+            nearest = Some((
+                depth,
+                PickHit {
+                    object_id: obj.id.clone(),
+                    voxel: hit.voxel,
+                    normal: hit.normal.map(|n| n as i32),
+                    palette_index: hit.palette_index,
+                },
+            ));
+        }
+        nearest.map(|(_, hit)| hit)
+    }
+}
+
+impl Scene {
+    /// The voxel under a pixel, see [`Raycaster::pick`].
+    ///
+    /// Builds the occupancy pyramids of the scene's objects on every call; keep a [`Raycaster`]
+    /// to pick many pixels of the same scene.
+    pub fn pick(
+        &self,
+        vp_matrix: [f32; 16],
+        view_position: [f32; 3],
+        width: u32,
+        height: u32,
+        x: u32,
+        y: u32,
+    ) -> Option<PickHit> {
+        Raycaster::new(self).pick(vp_matrix, view_position, width, height, x, y)
+    }
+}
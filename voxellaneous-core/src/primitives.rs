@@ -1,4 +1,4 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
-pub struct RGBA(pub u8, pub u8, pub u8, pub u8);
\ No newline at end of file
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RGBA(pub u8, pub u8, pub u8, pub u8);
@@ -0,0 +1,184 @@
+//! Ray queries against a [`Scene`] on the CPU.
+//!
+//! Objects are found through a [`Bvh`] over their world-space bounds, then marched voxel by voxel
+//! with the same traversal as the shaders, in each object's space.
+
+use glam::{Mat3, Mat4, Vec3};
+
+use crate::bvh::Bvh;
+use crate::occupancy::OccupancyPyramid;
+use crate::reference::{march, RayHit};
+use crate::scene::Scene;
+
+/// The first voxel of an object a ray hits.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RaycastHit {
+    pub object_id: String,
+    pub voxel: [u32; 3],
+    /// World-space unit normal of the voxel face the ray enters through.
+    pub normal: [f32; 3],
+    pub palette_index: u8,
+    /// World-space distance from the ray origin to the hit.
+    pub distance: f32,
+}
+
+/// Answers ray queries against a scene, keeping the hierarchy over its objects and their
+/// occupancy pyramids between queries.
+///
+/// The scene cannot change while borrowed, so the hierarchy never goes stale.
+pub struct Raycaster<'a> {
+    scene: &'a Scene,
+    bvh: Bvh,
+    inv_models: Vec<Mat4>,
+    occupancy: Vec<OccupancyPyramid>,
+    ignored: [bool; 256],
+}
+
+impl<'a> Raycaster<'a> {
+    pub fn new(scene: &'a Scene) -> Self {
+        Raycaster {
+            scene,
+            bvh: Bvh::from_objects(&scene.objects),
+            inv_models: scene
+                .objects
+                .iter()
+                .map(|obj| obj.transform.inverse_matrix())
+                .collect(),
+            occupancy: scene
+                .objects
+                .iter()
+                .map(|obj| OccupancyPyramid::new(&obj.voxels, obj.dims))
+                .collect(),
+            ignored: [false; 256],
+        }
+    }
+
+    /// Lets rays pass through voxels with any of these palette indices, as if they were empty.
+    pub fn ignore_palette_indices(mut self, indices: &[u8]) -> Self {
+        for &index in indices {
+            self.ignored[index as usize] = true;
+        }
+        self
+    }
+
+    /// Nearest hit along `origin + distance * dir` for `distance` in `[0, max_dist]`.
+    ///
+    /// `dir` need not be normalized; `None` if it is zero.
+    pub fn raycast(&self, origin: [f32; 3], dir: [f32; 3], max_dist: f32) -> Option<RaycastHit> {
+        let mut nearest: Option<RaycastHit> = None;
+        self.cast(origin, dir, max_dist, |hit| {
+            if nearest.as_ref().is_some_and(|n| n.distance <= hit.distance) {
+                return None;
+            }
+            let distance = hit.distance;
+            nearest = Some(hit);
+            Some(distance)
+        });
+        nearest
+    }
+
+    /// The first hit in every object along the ray, nearest first.
+    pub fn raycast_all(&self, origin: [f32; 3], dir: [f32; 3], max_dist: f32) -> Vec<RaycastHit> {
+        let mut hits = Vec::new();
+        self.cast(origin, dir, max_dist, |hit| {
+            hits.push(hit);
+            None
+        });
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        hits
+    }
+
+    pub(crate) fn scene(&self) -> &Scene {
+        self.scene
+    }
+
+    pub(crate) fn inv_model(&self, index: usize) -> Mat4 {
+        self.inv_models[index]
+    }
+
+    /// First hit along an object-space ray through object `index`, skipping ignored indices.
+    pub(crate) fn march(
+        &self,
+        index: usize,
+        origin: Vec3,
+        dir: Vec3,
+        t_limit: f32,
+    ) -> Option<RayHit> {
+        let solid = |palette_index: u8| palette_index != 0 && !self.ignored[palette_index as usize];
+        march(
+            &self.scene.objects[index],
+            &self.occupancy[index],
+            origin,
+            dir,
+            t_limit,
+            solid,
+        )
+    }
+
+    /// Reports the first hit in each object the hierarchy yields; `on_hit` returns a new
+    /// `max_dist` for the rest of the query, if any.
+    fn cast(
+        &self,
+        origin: [f32; 3],
+        dir: [f32; 3],
+        max_dist: f32,
+        mut on_hit: impl FnMut(RaycastHit) -> Option<f32>,
+    ) {
+        let origin = Vec3::from(origin);
+        let Some(dir) = Vec3::from(dir).try_normalize() else {
+            return;
+        };
+
+        // In object space the ray keeps its parameter, which stays the world-space distance.
+        self.bvh.raycast(origin, dir, max_dist, |index, _| {
+            let obj = &self.scene.objects[index];
+            let inv_model = self.inv_models[index];
+            let origin_os = inv_model.transform_point3(origin);
+            let dir_os = inv_model.transform_vector3(dir);
+            let hit = self.march(index, origin_os, dir_os, max_dist)?;
+
+            // Normals transform by the inverse transpose of the model matrix.
+            let normal = Mat3::from_mat4(inv_model).transpose() * Vec3::from(hit.normal);
+            on_hit(RaycastHit {
+                object_id: obj.id.clone(),
+                voxel: hit.voxel,
+                normal: normal.normalize().to_array(),
+                palette_index: hit.palette_index,
+                distance: hit.t,
+            })
+        });
+    }
+}
+
+impl Scene {
+    /// Nearest voxel hit along a ray, passing through voxels with any of the `ignored` palette
+    /// indices; see [`Raycaster::raycast`] and [`Raycaster::ignore_palette_indices`].
+    ///
+    /// Builds the hierarchy and the occupancy pyramids of the scene's objects on every call; keep
+    /// a [`Raycaster`] to run many queries against the same scene.
+    pub fn raycast(
+        &self,
+        origin: [f32; 3],
+        dir: [f32; 3],
+        max_dist: f32,
+        ignored: &[u8],
+    ) -> Option<RaycastHit> {
+        Raycaster::new(self)
+            .ignore_palette_indices(ignored)
+            .raycast(origin, dir, max_dist)
+    }
+
+    /// The first hit in every object along a ray, passing through voxels with any of the
+    /// `ignored` palette indices; see [`Raycaster::raycast_all`].
+    pub fn raycast_all(
+        &self,
+        origin: [f32; 3],
+        dir: [f32; 3],
+        max_dist: f32,
+        ignored: &[u8],
+    ) -> Vec<RaycastHit> {
+        Raycaster::new(self)
+            .ignore_palette_indices(ignored)
+            .raycast_all(origin, dir, max_dist)
+    }
+}
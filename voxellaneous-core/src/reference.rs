@@ -0,0 +1,572 @@
+//! CPU reference of the G-buffer and lighting passes.
+//!
+//! Mirrors the proxy-cube rasterization of `shaders/shader.wgsl`, the voxel traversal of
+//! `shaders/dda.wgsl` step by step in `f32`, and the shading of `shaders/lighting.wgsl`, so that
+//! GPU output can be checked on machines without a GPU. Any change to those shaders must be
+//! reflected here.
+
+use std::f32::consts::PI;
+
+use glam::{IVec3, Mat4, UVec3, Vec2, Vec3, Vec4, Vec4Swizzles};
+
+use crate::ao::{ao_bit, bake_ao, face_ao, AoMode};
+use crate::lighting::{Light, Lighting, ShadowMode, Shadows};
+use crate::occupancy::{OccupancyPyramid, OCCUPANCY_LEVELS};
+use crate::scene::{Scene, VoxelObject};
+use crate::utils::pack_rgba;
+
+/// Linear depth that maps to the largest `R16Uint` value.
+const LINEAR_Z_RANGE: f32 = 100.0;
+
+/// Distance of the disc shadow rays toward the sun aim at.
+const SUN_DISTANCE: f32 = 10000.0;
+
+/// A voxel hit produced by the `march` traversal or an octree's raycast, in the object's space.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RayHit {
+    pub palette_index: u8,
+    pub voxel: [u32; 3],
+    /// Ray parameter where the ray enters the voxel.
+    pub t: f32,
+    pub normal: [f32; 3],
+    /// Voxels, empty cells or octree nodes the traversal visited, this one included.
+    pub steps: u32,
+}
+
+/// Contents of the G-buffer targets after the geometry pass, row by row from the top.
+///
+/// Albedo and normal hold `Rgba8Unorm` texels, linear Z the `R16Uint` texels, ambient occlusion
+/// the `R8Unorm` texels, steps the `R16Uint` traversal steps and depth the depth of the voxel hit.
+/// Uncovered pixels keep the clear values: opaque black, no steps and a depth of 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GBufferImage {
+    pub width: u32,
+    pub height: u32,
+    pub albedo: Vec<[u8; 4]>,
+    pub normal: Vec<[u8; 4]>,
+    pub linear_z: Vec<u16>,
+    pub ao: Vec<u8>,
+    pub steps: Vec<u16>,
+    pub depth: Vec<f32>,
+}
+
+impl GBufferImage {
+    fn cleared(width: u32, height: u32) -> Self {
+        let len = (width * height) as usize;
+        GBufferImage {
+            width,
+            height,
+            albedo: vec![[0, 0, 0, 255]; len],
+            normal: vec![[0, 0, 0, 255]; len],
+            linear_z: vec![0; len],
+            ao: vec![0; len],
+            steps: vec![0; len],
+            depth: vec![1.0; len],
+        }
+    }
+}
+
+/// Float to unorm8 conversion done by the color attachment.
+fn to_unorm8(v: Vec4) -> [u8; 4] {
+    (v.clamp(Vec4::ZERO, Vec4::ONE) * 255.0)
+        .round()
+        .to_array()
+        .map(|c| c as u8)
+}
+
+/// The `march` traversal of `shaders/dda.wgsl` over `obj`, for `t` in `[0, t_limit]`, stopping at
+/// the first voxel whose palette index is `solid`.
+///
+/// `occupancy` must be the pyramid of `obj`'s voxels; `solid` may only accept non-zero indices,
+/// since empty cells are skipped without looking at their voxels.
+pub(crate) fn march(
+    obj: &VoxelObject,
+    occupancy: &OccupancyPyramid,
+    origin: Vec3,
+    dir: Vec3,
+    t_limit: f32,
+    solid: impl Fn(u8) -> bool,
+) -> Option<RayHit> {
+    let safe_dir = Vec3::select(dir.abs().cmplt(Vec3::splat(1e-8)), Vec3::splat(1e-8), dir);
+    let inv_dir = 1.0 / safe_dir;
+    let t0 = (Vec3::splat(-0.5) - origin) * inv_dir;
+    let t1 = (Vec3::splat(0.5) - origin) * inv_dir;
+    let t_near = t0.min(t1);
+    let t_entry = t_near.max_element();
+    let t_exit = t0.max(t1).min_element().min(t_limit);
+    let mut t = t_entry.max(0.0);
+    if t > t_exit {
+        return None;
+    }
+
+    let dims = IVec3::from(obj.dims.map(|d| d as i32));
+    let dims_f = dims.as_vec3();
+    let start = (origin + t * safe_dir + 0.5) * dims_f;
+    let mut voxel = start.floor().as_ivec3().clamp(IVec3::ZERO, dims - 1);
+    let positive = safe_dir.cmpgt(Vec3::ZERO);
+    let step = IVec3::select(positive, IVec3::ONE, IVec3::NEG_ONE);
+    let far_side = IVec3::select(positive, IVec3::ONE, IVec3::ZERO);
+    let inv_dir_voxel = inv_dir / dims_f;
+    let t_start = t;
+    let mut t_max = t_start + ((voxel + far_side).as_vec3() - start) * inv_dir_voxel;
+    let t_delta = inv_dir_voxel.abs();
+
+    let nearest = |t: Vec3| {
+        if t.x < t.y && t.x < t.z {
+            0
+        } else if t.y < t.z {
+            1
+        } else {
+            2
+        }
+    };
+    let mut axis = if t_near.x >= t_near.y && t_near.x >= t_near.z {
+        0
+    } else if t_near.y >= t_near.z {
+        1
+    } else {
+        2
+    };
+    // Same bound as `max_steps` in `march`.
+    let max_steps = obj.dims.iter().sum();
+    for steps in 1..=max_steps {
+        let coord = voxel.as_uvec3();
+        // Same as `empty_level`.
+        let level = (1..OCCUPANCY_LEVELS)
+            .rev()
+            .find(|&level| !occupancy.is_occupied(level, (coord >> level).to_array()))
+            .unwrap_or(0);
+        if level == 0 {
+            let index =
+                obj.voxels[(coord.x + obj.dims[0] * (coord.y + obj.dims[1] * coord.z)) as usize];
+            if solid(index) {
+                let mut normal = [0.0; 3];
+                normal[axis] = -step[axis] as f32;
+                return Some(RayHit {
+                    palette_index: index,
+                    voxel: coord.to_array(),
+                    t,
+                    normal,
+                    steps,
+                });
+            }
+
+            axis = nearest(t_max);
+            t = t_max[axis];
+            voxel[axis] += step[axis];
+            t_max[axis] += t_delta[axis];
+        } else {
+            let size = 1 << level;
+            let cell_min = (voxel >> level) << level;
+            let t_cell = t_start + ((cell_min + far_side * size).as_vec3() - start) * inv_dir_voxel;
+            axis = nearest(t_cell);
+            t = t_cell[axis];
+            let p = start + (t - t_start) * safe_dir * dims_f;
+            voxel = p
+                .floor()
+                .as_ivec3()
+                .clamp(cell_min, (cell_min + size - 1).min(dims - 1));
+            voxel[axis] = if step[axis] > 0 {
+                cell_min[axis] + size
+            } else {
+                cell_min[axis] - 1
+            };
+            t_max = t_start + ((voxel + far_side).as_vec3() - start) * inv_dir_voxel;
+        }
+        if t > t_exit || voxel[axis] < 0 || voxel[axis] >= dims[axis] {
+            break;
+        }
+    }
+    None
+}
+
+/// Marches a camera ray through `obj` exactly like `fs_main`.
+///
+/// `cam_os` and `dir_os` are in the object's unit-cube space and `dir_os` must be normalized.
+/// Returns `None` where the shader discards.
+pub fn trace_object(
+    obj: &VoxelObject,
+    occupancy: &OccupancyPyramid,
+    cam_os: Vec3,
+    dir_os: Vec3,
+) -> Option<RayHit> {
+    march(obj, occupancy, cam_os, dir_os, f32::MAX, |index| index != 0)
+}
+
+/// Line parameters where `origin + s * dir` enters and leaves the unit cube, if it does.
+fn cube_span(origin: Vec3, dir: Vec3) -> Option<(f32, f32)> {
+    let inv = dir.recip();
+    let a = (Vec3::splat(-0.5) - origin) * inv;
+    let b = (Vec3::splat(0.5) - origin) * inv;
+    let near = a.min(b).max_element();
+    let far = a.max(b).min_element();
+    (near <= far).then_some((near, far))
+}
+
+/// Same as `ambient_occlusion` in `fs_main`; `baked` holds [`bake_ao`] of `obj` in
+/// [`AoMode::Precomputed`].
+fn ambient_occlusion(
+    obj: &VoxelObject,
+    hit: &RayHit,
+    hit_os: Vec3,
+    ao_mode: AoMode,
+    baked: &[[u8; 6]],
+) -> f32 {
+    let axis = if hit.normal[0] != 0.0 {
+        0
+    } else if hit.normal[1] != 0.0 {
+        1
+    } else {
+        2
+    };
+    let sign = hit.normal[axis] as i32;
+    let voxel = UVec3::from(hit.voxel);
+    let dims = IVec3::from(obj.dims.map(|d| d as i32));
+    let levels = match ao_mode {
+        AoMode::Off => return 1.0,
+        AoMode::Shader => {
+            let solid = |v: IVec3| {
+                v.cmpge(IVec3::ZERO).all()
+                    && v.cmplt(dims).all()
+                    && obj.voxels[(v.x + dims.x * (v.y + dims.y * v.z)) as usize] != 0
+            };
+            face_ao(solid, voxel.as_ivec3(), axis, sign)
+        }
+        AoMode::Precomputed => {
+            let entry = baked[(voxel.x + obj.dims[0] * (voxel.y + obj.dims[1] * voxel.z)) as usize];
+            std::array::from_fn(|corner| {
+                let bit = ao_bit(axis, sign, corner);
+                (entry[(bit / 8) as usize] as u32 >> (bit % 8)) & 3
+            })
+        }
+    }
+    .map(|level| level as f32);
+
+    let p = (hit_os + 0.5) * dims.as_vec3();
+    let f = (p - voxel.as_vec3()).clamp(Vec3::ZERO, Vec3::ONE);
+    let (fu, fv) = (f[(axis + 1) % 3], f[(axis + 2) % 3]);
+    let mix = |a: f32, b: f32, t: f32| a * (1.0 - t) + b * t;
+    mix(
+        mix(levels[0], levels[1], fu),
+        mix(levels[2], levels[3], fu),
+        fv,
+    ) / 3.0
+}
+
+/// Renders `scene` into G-buffer images the way [`Renderer::render`] fills its targets.
+///
+/// Each object's proxy cube is rasterized with both faces at pixel centers; covered pixels run
+/// the [`trace_object`] traversal, discard on a miss, and depth-test the hit with `Less` in draw
+/// order. Depth modes give the same image. `vp_matrix` must map depth to wgpu's `[0, 1]` clip
+/// range, and `ao_mode` is the one passed to [`Renderer::set_ao_mode`].
+///
+/// [`Renderer::render`]: crate::Renderer::render
+/// [`Renderer::set_ao_mode`]: crate::Renderer::set_ao_mode
+pub fn render_reference(
+    scene: &Scene,
+    vp_matrix: [f32; 16],
+    view_position: [f32; 3],
+    width: u32,
+    height: u32,
+    ao_mode: AoMode,
+) -> GBufferImage {
+    let vp = Mat4::from_cols_array(&vp_matrix);
+    let inv_vp = vp.inverse();
+    let cam_ws = Vec3::from(view_position);
+    let mut palette = [0u32; 256];
+    for (packed, color) in palette.iter_mut().zip(&scene.palette) {
+        *packed = pack_rgba(color);
+    }
+
+    let mut image = GBufferImage::cleared(width, height);
+    for obj in &scene.objects {
+        let model = Mat4::from_cols_array(&obj.model_matrix());
+        let inv_model = Mat4::from_cols_array(&obj.inv_model_matrix());
+        let cam_os = inv_model.transform_point3(cam_ws);
+        let mvp = vp * model;
+        let occupancy = OccupancyPyramid::new(&obj.voxels, obj.dims);
+        let baked = match ao_mode {
+            AoMode::Precomputed => bake_ao(obj),
+            _ => Vec::new(),
+        };
+
+        for y in 0..height {
+            for x in 0..width {
+                let ndc = Vec4::new(
+                    (x as f32 + 0.5) / width as f32 * 2.0 - 1.0,
+                    1.0 - (y as f32 + 0.5) / height as f32 * 2.0,
+                    0.5,
+                    1.0,
+                );
+                let pixel_ws = inv_vp * ndc;
+                let pixel_os = inv_model.transform_point3(pixel_ws.xyz() / pixel_ws.w);
+                let line = pixel_os - cam_os;
+                let Some((entry, exit)) = cube_span(cam_os, line) else {
+                    continue;
+                };
+
+                // The fragment shader runs if either cube face survives clipping.
+                let covered = [entry, exit].into_iter().any(|s| {
+                    let clip = mvp * (cam_os + s * line).extend(1.0);
+                    let z = clip.z / clip.w;
+                    clip.w > 0.0 && (0.0..=1.0).contains(&z)
+                });
+                if !covered {
+                    continue;
+                }
+
+                let dir_os = line.normalize();
+                let Some(hit) = trace_object(obj, &occupancy, cam_os, dir_os) else {
+                    continue;
+                };
+
+                let hit_os = cam_os + hit.t * dir_os;
+                let hit_ws = model.transform_point3(hit_os);
+                let hit_clip = vp * hit_ws.extend(1.0);
+                // Written depth is clamped to the viewport's range.
+                let depth = (hit_clip.z / hit_clip.w).clamp(0.0, 1.0);
+                let pixel = (x + y * width) as usize;
+                if depth >= image.depth[pixel] {
+                    continue;
+                }
+
+                let linear_z = (hit_ws - cam_ws).length();
+                let packed = palette[hit.palette_index as usize];
+                let normal = Vec3::from(hit.normal) * 0.5 + 0.5;
+
+                image.albedo[pixel] = packed.to_le_bytes();
+                image.normal[pixel] = to_unorm8(normal.extend(1.0));
+                image.linear_z[pixel] =
+                    ((linear_z / LINEAR_Z_RANGE).clamp(0.0, 1.0) * 65535.0) as u16;
+                image.ao[pixel] = to_unorm8(Vec4::splat(ambient_occlusion(
+                    obj, &hit, hit_os, ao_mode, &baked,
+                )))[0];
+                image.steps[pixel] = hit.steps as u16;
+                image.depth[pixel] = depth;
+            }
+        }
+    }
+    image
+}
+
+fn srgb_to_linear(c: Vec3) -> Vec3 {
+    let curve = ((c + 0.055) / 1.055).powf(2.4);
+    Vec3::select(c.cmple(Vec3::splat(0.04045)), c / 12.92, curve)
+}
+
+fn linear_to_srgb(c: Vec3) -> Vec3 {
+    let curve = 1.055 * c.powf(1.0 / 2.4) - 0.055;
+    Vec3::select(c.cmple(Vec3::splat(0.0031308)), c * 12.92, curve)
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Same falloff as `attenuation` in the lighting pass.
+fn attenuation(distance: f32, range: f32) -> f32 {
+    let window = (1.0 - (distance / range).powf(4.0)).clamp(0.0, 1.0);
+    window * window / (distance * distance + 1.0)
+}
+
+/// Light reaching `position` with `normal` from a point or spot light.
+fn local_light(light: &Light, position: Vec3, normal: Vec3) -> Vec3 {
+    let (color, intensity, range) = match light {
+        Light::Point(l) => (l.color, l.intensity, l.range),
+        Light::Spot(l) => (l.color, l.intensity, l.range),
+    };
+    let to_light = light.position() - position;
+    let distance = to_light.length();
+    if distance >= range {
+        return Vec3::ZERO;
+    }
+    let dir = to_light / distance.max(1e-6);
+    let cone = match light {
+        Light::Point(_) => 1.0,
+        Light::Spot(l) => {
+            let cos_outer = l.outer_angle.cos();
+            let cos_inner = l.inner_angle.cos().max(cos_outer + 1e-4);
+            let axis = Vec3::from(l.direction).normalize_or_zero();
+            smoothstep(cos_outer, cos_inner, (-dir).dot(axis))
+        }
+    };
+    Vec3::from(color) * intensity * normal.dot(dir).max(0.0) * attenuation(distance, range) * cone
+}
+
+/// Same hash as `hash` in the lighting pass.
+fn hash(x: u32) -> u32 {
+    let mut h = x;
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846ca68b);
+    h ^= h >> 16;
+    h
+}
+
+fn random2(seed: u32, s: u32) -> Vec2 {
+    let a = hash(seed.wrapping_add(s.wrapping_mul(0x9e3779b9)));
+    let b = hash(a);
+    Vec2::new((a >> 8) as f32, (b >> 8) as f32) / 16777216.0
+}
+
+/// Objects shadow rays are traced against, with the lighting pass's shadow settings.
+struct Occluders<'a> {
+    scene: &'a Scene,
+    inv_models: Vec<Mat4>,
+    occupancy: Vec<OccupancyPyramid>,
+    shadows: Shadows,
+}
+
+impl Occluders<'_> {
+    /// Whether any object blocks `origin + t * dir` for `t` in `[0, distance]`.
+    fn occluded(&self, origin: Vec3, dir: Vec3, distance: f32) -> bool {
+        self.scene
+            .objects
+            .iter()
+            .zip(self.inv_models.iter().zip(&self.occupancy))
+            .any(|(obj, (inv_model, occupancy))| {
+                let origin_os = inv_model.transform_point3(origin);
+                let dir_os = inv_model.transform_vector3(dir);
+                march(obj, occupancy, origin_os, dir_os, distance, |index| {
+                    index != 0
+                })
+                .is_some()
+            })
+    }
+
+    /// Same as `visibility` in the lighting pass.
+    fn visibility(
+        &self,
+        origin: Vec3,
+        to_light: Vec3,
+        distance: f32,
+        radius: f32,
+        seed: u32,
+    ) -> f32 {
+        let samples = match self.shadows.mode {
+            ShadowMode::Off => return 1.0,
+            ShadowMode::Hard => 1,
+            ShadowMode::Soft => self.shadows.samples.max(1),
+        };
+
+        let n = to_light;
+        let sz = if n.z >= 0.0 { 1.0 } else { -1.0 };
+        let a = -1.0 / (sz + n.z);
+        let b = n.x * n.y * a;
+        let tangent = Vec3::new(1.0 + sz * n.x * n.x * a, sz * b, -sz * n.x);
+        let bitangent = Vec3::new(b, sz + n.y * n.y * a, -n.y);
+
+        let unoccluded = (0..samples)
+            .filter(|&s| {
+                let mut aim = to_light * distance;
+                if self.shadows.mode == ShadowMode::Soft {
+                    let u = random2(seed, s);
+                    let r = radius * u.x.sqrt();
+                    let phi = 2.0 * PI * u.y;
+                    aim += r * (phi.cos() * tangent + phi.sin() * bitangent);
+                }
+                let aim_length = aim.length();
+                !self.occluded(origin, aim / aim_length, aim_length)
+            })
+            .count();
+        unoccluded as f32 / samples as f32
+    }
+}
+
+/// Shades G-buffer images of `scene` like the lighting pass, returning the lit target's texels.
+///
+/// Positions are unprojected from `image.depth` as on the GPU; every light is
+/// evaluated, which matches the tiled lookup since tiles list all lights that can reach them.
+/// `lights` must be in the order they were added to the renderer, which seeds soft shadows.
+pub fn shade_reference(
+    image: &GBufferImage,
+    scene: &Scene,
+    lighting: &Lighting,
+    lights: &[Light],
+    vp_matrix: [f32; 16],
+) -> Vec<[u8; 4]> {
+    let sun_direction = lighting.sun.unit_direction();
+    let sun = Vec3::from(lighting.sun.color) * lighting.sun.intensity;
+    let ambient = Vec3::from(lighting.ambient);
+    let shadows = &lighting.shadows;
+    let occluders = Occluders {
+        scene,
+        inv_models: scene
+            .objects
+            .iter()
+            .map(|obj| Mat4::from_cols_array(&obj.inv_model_matrix()))
+            .collect(),
+        occupancy: scene
+            .objects
+            .iter()
+            .map(|obj| OccupancyPyramid::new(&obj.voxels, obj.dims))
+            .collect(),
+        shadows: *shadows,
+    };
+    let inv_vp = Mat4::from_cols_array(&vp_matrix).inverse();
+    let (width, height) = (image.width as f32, image.height as f32);
+
+    let mut lit = Vec::with_capacity(image.albedo.len());
+    for (pixel, (&albedo, &normal)) in image.albedo.iter().zip(&image.normal).enumerate() {
+        let albedo = Vec4::from_array(albedo.map(|c| c as f32 / 255.0));
+        let encoded_normal = Vec3::from_array([0, 1, 2].map(|i| normal[i] as f32 / 255.0));
+        if encoded_normal == Vec3::ZERO {
+            lit.push(to_unorm8(albedo));
+            continue;
+        }
+
+        let normal = encoded_normal * 2.0 - 1.0;
+        let (px, py) = (pixel as u32 % image.width, pixel as u32 / image.width);
+        // Unprojected from depth like the lighting pass, not from the coarser linear Z.
+        let point = inv_vp
+            * Vec4::new(
+                (px as f32 + 0.5) / width * 2.0 - 1.0,
+                1.0 - (py as f32 + 0.5) / height * 2.0,
+                image.depth[pixel],
+                1.0,
+            );
+        let position = point.xyz() / point.w;
+        let shadow_origin = position + normal * shadows.bias;
+        let pixel_seed = hash(px + (py << 16));
+
+        let mut light = ambient * image.ao[pixel] as f32 / 255.0;
+        let n_dot_l = normal.dot(-sun_direction).max(0.0);
+        if n_dot_l > 0.0 {
+            let sun_radius = SUN_DISTANCE * shadows.sun_angle.tan();
+            let visible = occluders.visibility(
+                shadow_origin,
+                -sun_direction,
+                SUN_DISTANCE,
+                sun_radius,
+                pixel_seed,
+            );
+            light += sun * n_dot_l * visible;
+        }
+
+        for (index, local) in lights.iter().enumerate() {
+            let contribution = local_light(local, position, normal);
+            if contribution == Vec3::ZERO {
+                continue;
+            }
+            let to_light = local.position() - shadow_origin;
+            let distance = to_light.length();
+            let seed = hash(pixel_seed.wrapping_add(index as u32 + 1));
+            light += contribution
+                * occluders.visibility(
+                    shadow_origin,
+                    to_light / distance,
+                    distance,
+                    shadows.light_radius,
+                    seed,
+                );
+        }
+
+        let color = srgb_to_linear(albedo.xyz()) * light;
+        lit.push(to_unorm8(
+            linear_to_srgb(color.clamp(Vec3::ZERO, Vec3::ONE)).extend(albedo.w),
+        ));
+    }
+    lit
+}
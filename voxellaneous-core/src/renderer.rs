@@ -0,0 +1,2588 @@
+use std::fmt;
+
+use crate::ao::{bake_ao_region, AoMode};
+use crate::bricks::{
+    brick_dims, bricks_to_fill, extract_brick, BrickAllocator, BRICK_SIZE, BRICK_VOXELS,
+    EMPTY_BRICK,
+};
+use crate::bvh::{Aabb, Bvh, Frustum};
+use crate::constants::{Vertex, CUBE_INDICES, CUBE_VERTICES};
+use crate::lighting::{bin_lights, Light, LightError, Lighting, ShadowMode};
+use crate::occupancy::OccupancyPyramid;
+use crate::octree::SparseVoxelOctree;
+use crate::picking::{PickHit, PickTexel, PICK_ID_BITS};
+use crate::primitives::RGBA;
+use crate::scene::{
+    validate_palette, validate_palette_indices, validate_region, validate_transform, InvalidReason,
+    ObjectField, Scene, SceneError, VoxelObject,
+};
+use crate::transform::Transform;
+use crate::utils;
+use glam::Mat4;
+use wgpu::util::DeviceExt;
+
+/// Errors produced while creating or driving a [`Renderer`].
+#[derive(Debug)]
+pub enum RendererError {
+    RequestAdapter(wgpu::RequestAdapterError),
+    RequestDevice(wgpu::RequestDeviceError),
+    Surface(wgpu::SurfaceError),
+    /// The target's texture format cannot be copied to a buffer.
+    UnreadableTarget(PresentTarget),
+    Poll(wgpu::PollError),
+    BufferMap(wgpu::BufferAsyncError),
+    /// The device lacks features the requested setting needs.
+    MissingFeatures(wgpu::Features),
+}
+
+impl fmt::Display for RendererError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RendererError::RequestAdapter(e) => write!(f, "failed to request adapter: {e}"),
+            RendererError::RequestDevice(e) => write!(f, "failed to request device: {e}"),
+            RendererError::Surface(e) => write!(f, "failed to acquire surface texture: {e}"),
+            RendererError::UnreadableTarget(target) => {
+                write!(f, "{target:?} target cannot be read back")
+            }
+            RendererError::Poll(e) => write!(f, "failed to wait for the device: {e}"),
+            RendererError::BufferMap(e) => write!(f, "failed to map readback buffer: {e}"),
+            RendererError::MissingFeatures(features) => {
+                write!(f, "device does not support {features:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RendererError {}
+
+/// How the G-buffer pass writes depth for raymarched hits.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DepthMode {
+    /// Depth of the voxel surface hit, so objects sort by surface rather than by bounding box.
+    /// Writing depth from the shader disables early depth testing.
+    #[default]
+    Exact,
+    /// The same depth, declared to never be nearer than the proxy cube face so the hardware can
+    /// keep testing depth early. Needs [`wgpu::Features::SHADER_EARLY_DEPTH_TEST`]. Only the
+    /// front faces are drawn, which hits always lie behind; objects that reach the near plane or
+    /// are mirrored are drawn as in [`DepthMode::Exact`].
+    Conservative,
+}
+
+/// Where the G-buffer raymarch finds each object's voxels.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VoxelStorage {
+    /// Walks the voxels in the brick atlas, crossing empty cells of up to 8^3 voxels at once.
+    #[default]
+    BrickAtlas,
+    /// Descends each object's [`SparseVoxelOctree`] and crosses empty nodes whole, which takes
+    /// far fewer steps through large, mostly empty objects. The atlas still backs ambient
+    /// occlusion.
+    Octree,
+}
+
+/// Object counts of the last [`Renderer::render`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Serialize)]
+pub struct RenderStats {
+    /// Objects whose bounds reach into the view frustum, which the G-buffer pass raymarched.
+    pub drawn: u32,
+    /// Objects skipped for lying entirely outside the view frustum.
+    pub culled: u32,
+}
+
+/// G-buffer target blitted to the output by the present pass.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PresentTarget {
+    #[default]
+    Albedo,
+    Normal,
+    LinearZ,
+    Depth,
+    /// Output of the deferred lighting pass.
+    Lit,
+    AmbientOcclusion,
+    /// Traversal steps of each pixel's ray. While shown, rays that miss keep their steps too.
+    Steps,
+}
+
+impl From<usize> for PresentTarget {
+    fn from(index: usize) -> Self {
+        match index {
+            1 => PresentTarget::Normal,
+            2 => PresentTarget::LinearZ,
+            3 => PresentTarget::Depth,
+            4 => PresentTarget::Lit,
+            5 => PresentTarget::AmbientOcclusion,
+            6 => PresentTarget::Steps,
+            _ => PresentTarget::Albedo,
+        }
+    }
+}
+
+#[repr(C, align(16))]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct PerFrameUniforms {
+    vp_matrix: [f32; 16],
+    camera_position: [f32; 3],
+    ao_mode: u32,
+    keep_misses: u32,
+    voxel_storage: u32,
+    _padding: [u32; 2],
+}
+
+#[repr(C, align(16))]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct StaticUniforms {
+    color_palette: [u32; 256],
+}
+
+/// An object as the G-buffer pass reads it from the object buffer.
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct GpuObject {
+    model_matrix: [f32; 16],
+    inverse_model_matrix: [f32; 16],
+    dims: [u32; 3],
+    pick_id: u32,
+    brick_offset: u32,
+    occupancy_offset: u32,
+    octree_offset: u32,
+    octree_depth: u32,
+}
+
+impl GpuObject {
+    fn new(draw_call: &DrawCallData) -> Self {
+        GpuObject {
+            model_matrix: draw_call.model_matrix,
+            inverse_model_matrix: draw_call.inverse_model_matrix,
+            dims: draw_call.dims,
+            pick_id: draw_call.pick_id,
+            brick_offset: draw_call.brick_offset,
+            occupancy_offset: draw_call.occupancy_offset,
+            octree_offset: draw_call.octree_offset,
+            octree_depth: draw_call
+                .octree
+                .as_ref()
+                .map_or(0, SparseVoxelOctree::depth),
+        }
+    }
+}
+
+#[repr(C, align(16))]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightingUniforms {
+    sun_direction: [f32; 3],
+    sun_intensity: f32,
+    sun_color: [f32; 3],
+    shadow_mode: u32,
+    ambient: [f32; 3],
+    shadow_bias: f32,
+    shadow_samples: u32,
+    light_radius: f32,
+    sun_angle: f32,
+    _padding: f32,
+}
+
+impl LightingUniforms {
+    fn new(lighting: &Lighting) -> Self {
+        let shadows = &lighting.shadows;
+        LightingUniforms {
+            sun_direction: lighting.sun.unit_direction().to_array(),
+            sun_intensity: lighting.sun.intensity,
+            sun_color: lighting.sun.color,
+            shadow_mode: match shadows.mode {
+                ShadowMode::Off => 0,
+                ShadowMode::Hard => 1,
+                ShadowMode::Soft => 2,
+            },
+            ambient: lighting.ambient,
+            shadow_bias: shadows.bias,
+            shadow_samples: shadows.samples,
+            light_radius: shadows.light_radius,
+            sun_angle: shadows.sun_angle,
+            _padding: 0.0,
+        }
+    }
+}
+
+#[repr(C, align(16))]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightingFrameUniforms {
+    inv_vp_matrix: [f32; 16],
+    tiles_x: u32,
+    object_count: u32,
+    _padding: [u32; 2],
+}
+
+/// A node of the object hierarchy shadow rays traverse, see `Bvh::flat_nodes`.
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct GpuBvhNode {
+    min: [f32; 3],
+    start: u32,
+    max: [f32; 3],
+    count: u32,
+}
+
+/// An object as seen by shadow rays: its transform and where its occupancy bits start.
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct GpuShadowObject {
+    inverse_model_matrix: [f32; 16],
+    dims: [u32; 3],
+    offset: u32,
+}
+
+/// A point or spot light as laid out in the lighting pass's storage buffer.
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct GpuLight {
+    position: [f32; 3],
+    range: f32,
+    color: [f32; 3],
+    kind: u32,
+    direction: [f32; 3],
+    cos_outer: f32,
+    cos_inner: f32,
+    _padding: [f32; 3],
+}
+
+impl GpuLight {
+    fn new(light: &Light) -> Self {
+        let premultiplied = |color: [f32; 3], intensity: f32| color.map(|c| c * intensity);
+        match light {
+            Light::Point(l) => GpuLight {
+                position: l.position,
+                range: l.range,
+                color: premultiplied(l.color, l.intensity),
+                kind: 0,
+                direction: [0.0; 3],
+                cos_outer: -1.0,
+                cos_inner: 1.0,
+                _padding: [0.0; 3],
+            },
+            Light::Spot(l) => {
+                let cos_outer = l.outer_angle.cos();
+                GpuLight {
+                    position: l.position,
+                    range: l.range,
+                    color: premultiplied(l.color, l.intensity),
+                    kind: 1,
+                    direction: glam::Vec3::from(l.direction).normalize_or_zero().to_array(),
+                    cos_outer,
+                    // smoothstep is undefined for equal edges.
+                    cos_inner: l.inner_angle.cos().max(cos_outer + 1e-4),
+                    _padding: [0.0; 3],
+                }
+            }
+        }
+    }
+}
+
+/// Minimum storage buffer size; bindings must hold at least one element of their largest type.
+const MIN_STORAGE_BUFFER_SIZE: wgpu::BufferAddress = std::mem::size_of::<GpuObject>() as _;
+
+/// Bricks along the atlas's x and y axes; it grows along z.
+const ATLAS_BRICKS_XY: u32 = 16;
+
+/// The brick atlas: palette indices in an `R8Uint` 3D texture, stacked in layers of
+/// `ATLAS_BRICKS_XY` × `ATLAS_BRICKS_XY` bricks.
+fn create_atlas(device: &wgpu::Device, layers: u32) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Brick Atlas"),
+        size: wgpu::Extent3d {
+            width: ATLAS_BRICKS_XY * BRICK_SIZE,
+            height: ATLAS_BRICKS_XY * BRICK_SIZE,
+            depth_or_array_layers: layers * BRICK_SIZE,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D3,
+        format: wgpu::TextureFormat::R8Uint,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    (texture, view)
+}
+
+/// Bytes of baked occlusion per brick, see [`crate::ao::bake_ao`].
+const BRICK_AO_BYTES: usize = BRICK_VOXELS * std::mem::size_of::<[u8; 6]>();
+
+/// Baked occlusion for the bricks of an atlas of `layers` layers, as many as the device can bind,
+/// in [`AoMode::Precomputed`]. Other modes get a placeholder. Bricks past the end of the buffer
+/// fall back to computing occlusion in the shader.
+fn create_atlas_ao_buffer(device: &wgpu::Device, layers: u32, ao_mode: AoMode) -> wgpu::Buffer {
+    let bricks = match ao_mode {
+        AoMode::Precomputed => {
+            let limits = device.limits();
+            let max_bytes =
+                u64::from(limits.max_storage_buffer_binding_size).min(limits.max_buffer_size);
+            let max_bricks = max_bytes / BRICK_AO_BYTES as u64;
+            u64::from(layers * ATLAS_BRICKS_XY * ATLAS_BRICKS_XY).min(max_bricks)
+        }
+        AoMode::Off | AoMode::Shader => 0,
+    };
+    create_storage_buffer(
+        device,
+        bricks * BRICK_AO_BYTES as wgpu::BufferAddress,
+        "Atlas AO Buffer",
+    )
+}
+
+/// Texel of the atlas where brick `slot` starts.
+fn atlas_origin(slot: u32) -> [u32; 3] {
+    [
+        slot % ATLAS_BRICKS_XY,
+        slot / ATLAS_BRICKS_XY % ATLAS_BRICKS_XY,
+        slot / (ATLAS_BRICKS_XY * ATLAS_BRICKS_XY),
+    ]
+    .map(|b| b * BRICK_SIZE)
+}
+
+fn create_storage_buffer(
+    device: &wgpu::Device,
+    size: wgpu::BufferAddress,
+    label: &str,
+) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: size.max(MIN_STORAGE_BUFFER_SIZE),
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// Writes `contents` to `buffer`, reallocating it with twice the size when it does not fit.
+fn upload_storage_buffer(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    buffer: &mut wgpu::Buffer,
+    contents: &[u8],
+    label: &str,
+) {
+    let len = contents.len() as wgpu::BufferAddress;
+    if len > buffer.size() {
+        let size = (len * 2).min(device.limits().max_buffer_size).max(len);
+        *buffer = create_storage_buffer(device, size, label);
+    }
+    queue.write_buffer(buffer, 0, contents);
+}
+
+/// Vertex buffer of per-instance object indices, holding at least one.
+fn create_instance_buffer(device: &wgpu::Device, size: wgpu::BufferAddress) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Instance Buffer"),
+        size: size.max(std::mem::size_of::<u32>() as _),
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// Depth buffer format; the lighting pass reads it back to place shaded pixels in the world.
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Format of the texture headless renderers present into.
+const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// A 2D texture rendered to and then sampled or copied from.
+struct RenderTarget {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+}
+
+fn create_render_target(
+    device: &wgpu::Device,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+    label: &str,
+) -> RenderTarget {
+    let size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    RenderTarget { texture, view }
+}
+
+fn create_depth_texture(device: &wgpu::Device, width: u32, height: u32) -> wgpu::TextureView {
+    let size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+
+    let depth_texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Depth Texture"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: DEPTH_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+
+    depth_texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// CPU copy of an object's data, packed into the renderer's shared buffers.
+pub struct DrawCallData {
+    pub id: String,
+    pub dims: [u32; 3],
+    pub model_matrix: [f32; 16],
+    pub inverse_model_matrix: [f32; 16],
+    pub voxels: Vec<u8>,
+    pub occupancy: OccupancyPyramid,
+    /// Where the object's occupancy pyramid starts in the occupancy buffer.
+    pub occupancy_offset: u32,
+    /// Baked corner occlusion levels, see [`crate::ao::bake_ao`]; empty unless the renderer is in
+    /// [`AoMode::Precomputed`].
+    pub ao: Vec<[u8; 6]>,
+    /// Atlas slot of each brick of the object, or [`EMPTY_BRICK`].
+    pub bricks: Vec<u32>,
+    /// Where the object's brick table starts in the brick table buffer.
+    pub brick_offset: u32,
+    /// The object's octree in [`VoxelStorage::Octree`].
+    pub octree: Option<SparseVoxelOctree>,
+    /// Where the object's octree nodes start in the octree buffer.
+    pub octree_offset: u32,
+    /// Words set aside for the object's octree in the octree buffer, so edits can add nodes
+    /// without moving the octrees after it.
+    pub octree_words: u32,
+    /// World-space bounds of the object's proxy cube.
+    pub bounds: Aabb,
+    /// Written to the pick target for this object's voxels.
+    pub pick_id: u32,
+}
+
+/// Where the present pass draws: a window surface or, for headless renderers, a texture.
+enum Output {
+    Surface {
+        surface: wgpu::Surface<'static>,
+        config: wgpu::SurfaceConfiguration,
+    },
+    Offscreen(RenderTarget),
+}
+
+pub struct Renderer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    adapter_info: wgpu::AdapterInfo,
+    output: Output,
+    width: u32,
+    height: u32,
+    render_pipeline: wgpu::RenderPipeline,
+    conservative_pipeline: Option<wgpu::RenderPipeline>,
+    depth_mode: DepthMode,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    static_uniform_buffer: wgpu::Buffer,
+    per_frame_uniform_buffer: wgpu::Buffer,
+    per_frame_bind_group_layout: wgpu::BindGroupLayout,
+    object_bind_group_layout: wgpu::BindGroupLayout,
+    object_buffer: wgpu::Buffer,
+    brick_table_buffer: wgpu::Buffer,
+    atlas: wgpu::Texture,
+    atlas_view: wgpu::TextureView,
+    /// Baked occlusion of the atlas, brick by brick, see `create_atlas_ao_buffer`.
+    atlas_ao_buffer: wgpu::Buffer,
+    bricks: BrickAllocator,
+    /// Indices of the objects drawn this frame, one per instance.
+    instance_buffer: wgpu::Buffer,
+    /// Set when any object's entry in `object_buffer` is out of date.
+    objects_dirty: bool,
+    /// Set when objects were added or removed, which moves tables in `brick_table_buffer`.
+    tables_dirty: bool,
+    /// Set when the atlas was replaced and every brick needs writing again.
+    atlas_dirty: bool,
+    /// Node words of every object's octree in [`VoxelStorage::Octree`], one after another.
+    octree_buffer: wgpu::Buffer,
+    /// Set when octrees were built, dropped or outgrew their room, which moves them in
+    /// `octree_buffer`.
+    octree_dirty: bool,
+    quad_layout_uint: wgpu::BindGroupLayout,
+    quad_layout_float: wgpu::BindGroupLayout,
+    quad_pipeline_uint: wgpu::RenderPipeline,
+    quad_layout_steps: wgpu::BindGroupLayout,
+    quad_pipeline_steps: wgpu::RenderPipeline,
+    quad_pipeline_float: wgpu::RenderPipeline,
+    lighting_layout: wgpu::BindGroupLayout,
+    lighting_pipeline: wgpu::RenderPipeline,
+    lighting_uniform_buffer: wgpu::Buffer,
+    lighting_frame_buffer: wgpu::Buffer,
+    light_buffer: wgpu::Buffer,
+    tile_range_buffer: wgpu::Buffer,
+    tile_index_buffer: wgpu::Buffer,
+    lights: Vec<(String, Light)>,
+    shadow_object_buffer: wgpu::Buffer,
+    occupancy_buffer: wgpu::Buffer,
+    /// `bvh` for shadow rays, its nodes and its items.
+    bvh_node_buffer: wgpu::Buffer,
+    bvh_item_buffer: wgpu::Buffer,
+    /// Set when objects changed since the shadow buffers were last uploaded.
+    shadow_objects_dirty: bool,
+    static_bind_group: wgpu::BindGroup,
+    gbuffer_albedo: RenderTarget,
+    gbuffer_normal: RenderTarget,
+    gbuffer_linear_z: RenderTarget,
+    gbuffer_ao: RenderTarget,
+    gbuffer_pick: RenderTarget,
+    gbuffer_steps: RenderTarget,
+    ao_mode: AoMode,
+    voxel_storage: VoxelStorage,
+    lit_target: RenderTarget,
+    sampler: wgpu::Sampler,
+    depth_texture_view: wgpu::TextureView,
+    draw_call_array: Vec<DrawCallData>,
+    /// Hierarchy over the bounds of `draw_call_array`, in the same order.
+    bvh: Bvh,
+    /// Set when objects were added or removed since `bvh` was built.
+    bvh_dirty: bool,
+    stats: RenderStats,
+    /// Pick id of the next object uploaded. Ids are only reused after wrapping around
+    /// [`PICK_ID_BITS`], so stale picks of removed objects find nothing.
+    next_pick_id: u32,
+    /// Entries of the palette last uploaded; 0 until the first [`Renderer::upload_scene`] or
+    /// [`Renderer::update_palette`].
+    palette_len: usize,
+}
+
+impl Renderer {
+    /// Creates a renderer presenting to `surface`, which must have been created from `instance`.
+    pub async fn new(
+        instance: &wgpu::Instance,
+        surface: wgpu::Surface<'static>,
+        canvas_width: u32,
+        canvas_height: u32,
+    ) -> Result<Renderer, RendererError> {
+        let adapter = Self::request_adapter(instance, Some(&surface)).await?;
+        let (device, queue) = Self::request_device(&adapter).await?;
+
+        let supported_formats = surface.get_capabilities(&adapter).formats;
+        let surface_format = *supported_formats.first().unwrap();
+
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: surface_format,
+            width: canvas_width,
+            height: canvas_height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            desired_maximum_frame_latency: 2,
+            view_formats: vec![],
+        };
+        surface.configure(&device, &surface_config);
+
+        let output = Output::Surface {
+            surface,
+            config: surface_config,
+        };
+        Ok(Self::with_device(
+            adapter.get_info(),
+            device,
+            queue,
+            output,
+            surface_format,
+            canvas_width,
+            canvas_height,
+        ))
+    }
+
+    /// Creates a renderer without a window that presents into a `width`×`height` texture.
+    ///
+    /// G-buffer contents can be fetched with [`Renderer::read_pixels`] after each render.
+    pub async fn new_headless(
+        instance: &wgpu::Instance,
+        width: u32,
+        height: u32,
+    ) -> Result<Renderer, RendererError> {
+        let adapter = Self::request_adapter(instance, None).await?;
+        let (device, queue) = Self::request_device(&adapter).await?;
+
+        let target = create_render_target(&device, width, height, OFFSCREEN_FORMAT, "Offscreen");
+        Ok(Self::with_device(
+            adapter.get_info(),
+            device,
+            queue,
+            Output::Offscreen(target),
+            OFFSCREEN_FORMAT,
+            width,
+            height,
+        ))
+    }
+
+    async fn request_adapter(
+        instance: &wgpu::Instance,
+        compatible_surface: Option<&wgpu::Surface<'static>>,
+    ) -> Result<wgpu::Adapter, RendererError> {
+        instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                compatible_surface,
+                force_fallback_adapter: false,
+            })
+            .await
+            .map_err(RendererError::RequestAdapter)
+    }
+
+    async fn request_device(
+        adapter: &wgpu::Adapter,
+    ) -> Result<(wgpu::Device, wgpu::Queue), RendererError> {
+        // Conservative depth is optional; WebGPU does not expose it.
+        let optional_features = wgpu::Features::SHADER_EARLY_DEPTH_TEST;
+        adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                required_features: adapter.features() & optional_features,
+                ..Default::default()
+            })
+            .await
+            .map_err(RendererError::RequestDevice)
+    }
+
+    /// Builds pipelines and render targets shared by the windowed and headless renderers.
+    fn with_device(
+        adapter_info: wgpu::AdapterInfo,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        output: Output,
+        output_format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Renderer {
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+
+        let depth_texture_view = create_depth_texture(&device, width, height);
+
+        let mut shader_source = concat!(
+            include_str!("shaders/shader.wgsl"),
+            include_str!("shaders/dda.wgsl")
+        )
+        .to_owned();
+        if device
+            .features()
+            .contains(wgpu::Features::SHADER_EARLY_DEPTH_TEST)
+        {
+            shader_source.push_str(include_str!("shaders/conservative_depth.wgsl"));
+        }
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shader"),
+            source: wgpu::ShaderSource::Wgsl(shader_source.into()),
+        });
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Vertex Buffer"),
+            contents: bytemuck::cast_slice(CUBE_VERTICES),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Index Buffer"),
+            contents: bytemuck::cast_slice(CUBE_INDICES),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let static_uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Static Uniform Buffer"),
+            contents: &[0; std::mem::size_of::<StaticUniforms>()],
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let per_frame_uniform_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Per Frame Uniform Buffer"),
+                contents: &[0; std::mem::size_of::<PerFrameUniforms>()],
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
+        let static_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Static Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        let per_frame_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Per Frame Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        let storage_entry = |binding, visibility| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let object_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Object Bind Group Layout"),
+                entries: &[
+                    storage_entry(0, wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT),
+                    storage_entry(1, wgpu::ShaderStages::FRAGMENT),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Uint,
+                            view_dimension: wgpu::TextureViewDimension::D3,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    storage_entry(3, wgpu::ShaderStages::FRAGMENT),
+                    storage_entry(4, wgpu::ShaderStages::FRAGMENT),
+                    storage_entry(5, wgpu::ShaderStages::FRAGMENT),
+                ],
+            });
+
+        let static_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Static Bind Group"),
+            layout: &static_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: static_uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Pipeline Layout"),
+            bind_group_layouts: &[
+                &static_bind_group_layout,
+                &per_frame_bind_group_layout,
+                &object_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let render_pipeline = Renderer::create_gbuffer_pipeline(
+            &device,
+            &pipeline_layout,
+            &shader,
+            "fs_main",
+            None,
+            "G-Buffer Render Pipeline",
+        );
+        // Only built where the device can compile `@early_depth_test`.
+        let conservative_pipeline = device
+            .features()
+            .contains(wgpu::Features::SHADER_EARLY_DEPTH_TEST)
+            .then(|| {
+                Renderer::create_gbuffer_pipeline(
+                    &device,
+                    &pipeline_layout,
+                    &shader,
+                    "fs_main_conservative",
+                    // Back faces lie behind the hits, which breaks the promise to the early test.
+                    Some(wgpu::Face::Back),
+                    "G-Buffer Conservative Depth Pipeline",
+                )
+            });
+
+        let gbuffer_albedo = create_render_target(
+            &device,
+            width,
+            height,
+            wgpu::TextureFormat::Rgba8Unorm,
+            "GBuffer Albedo",
+        );
+        let gbuffer_normal = create_render_target(
+            &device,
+            width,
+            height,
+            wgpu::TextureFormat::Rgba8Unorm,
+            "GBuffer Normal",
+        );
+        let gbuffer_linear_z = create_render_target(
+            &device,
+            width,
+            height,
+            wgpu::TextureFormat::R16Uint,
+            "GBuffer LinearZ",
+        );
+        let gbuffer_ao = create_render_target(
+            &device,
+            width,
+            height,
+            wgpu::TextureFormat::R8Unorm,
+            "GBuffer AO",
+        );
+        let gbuffer_pick = create_render_target(
+            &device,
+            width,
+            height,
+            wgpu::TextureFormat::Rg32Uint,
+            "GBuffer Pick",
+        );
+        let gbuffer_steps = create_render_target(
+            &device,
+            width,
+            height,
+            wgpu::TextureFormat::R16Uint,
+            "GBuffer Steps",
+        );
+
+        let (quad_layout_uint, quad_pipeline_uint, _) = Renderer::create_fullscreen_quad_pipeline(
+            &device,
+            output_format,
+            include_str!("shaders/quad_uint.wgsl"),
+            wgpu::TextureSampleType::Uint,
+            wgpu::SamplerBindingType::NonFiltering,
+            "Quad Layout Uint",
+            "Quad Uint Shader",
+            "Quad Pipeline Uint",
+        );
+        let (quad_layout_steps, quad_pipeline_steps, _) = Renderer::create_fullscreen_quad_pipeline(
+            &device,
+            output_format,
+            include_str!("shaders/quad_steps.wgsl"),
+            wgpu::TextureSampleType::Uint,
+            wgpu::SamplerBindingType::NonFiltering,
+            "Quad Layout Steps",
+            "Quad Steps Shader",
+            "Quad Pipeline Steps",
+        );
+        let (quad_layout_float, quad_pipeline_float, _) = Renderer::create_fullscreen_quad_pipeline(
+            &device,
+            output_format,
+            include_str!("shaders/quad_float.wgsl"),
+            wgpu::TextureSampleType::Float { filterable: false },
+            wgpu::SamplerBindingType::Filtering,
+            "Quad Layout Float",
+            "Quad Float Shader",
+            "Quad Pipeline Float",
+        );
+
+        let lit_target = create_render_target(
+            &device,
+            width,
+            height,
+            wgpu::TextureFormat::Rgba8Unorm,
+            "Lit Output",
+        );
+        let lighting_uniform_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Lighting Uniform Buffer"),
+                contents: bytemuck::cast_slice(&[LightingUniforms::new(&Lighting::default())]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        let lighting_frame_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Lighting Frame Uniform Buffer"),
+            size: std::mem::size_of::<LightingFrameUniforms>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let light_buffer = create_storage_buffer(&device, 0, "Light Buffer");
+        let tile_range_buffer = create_storage_buffer(&device, 0, "Tile Range Buffer");
+        let tile_index_buffer = create_storage_buffer(&device, 0, "Tile Index Buffer");
+        let shadow_object_buffer = create_storage_buffer(&device, 0, "Shadow Object Buffer");
+        let bvh_node_buffer = create_storage_buffer(&device, 0, "BVH Node Buffer");
+        let bvh_item_buffer = create_storage_buffer(&device, 0, "BVH Item Buffer");
+        let object_buffer = create_storage_buffer(&device, 0, "Object Buffer");
+        let brick_table_buffer = create_storage_buffer(&device, 0, "Brick Table Buffer");
+        let (atlas, atlas_view) = create_atlas(&device, 1);
+        let atlas_ao_buffer = create_atlas_ao_buffer(&device, 1, AoMode::default());
+        let instance_buffer = create_instance_buffer(&device, 0);
+        let occupancy_buffer = create_storage_buffer(&device, 0, "Occupancy Buffer");
+        let octree_buffer = create_storage_buffer(&device, 0, "Octree Buffer");
+        let (lighting_layout, lighting_pipeline) = Renderer::create_lighting_pipeline(&device);
+
+        Renderer {
+            device,
+            queue,
+            adapter_info,
+            output,
+            width,
+            height,
+            render_pipeline,
+            conservative_pipeline,
+            depth_mode: DepthMode::default(),
+            vertex_buffer,
+            index_buffer,
+            static_uniform_buffer,
+            per_frame_uniform_buffer,
+            per_frame_bind_group_layout,
+            object_bind_group_layout,
+            object_buffer,
+            brick_table_buffer,
+            atlas,
+            atlas_view,
+            atlas_ao_buffer,
+            bricks: BrickAllocator::new(),
+            instance_buffer,
+            objects_dirty: false,
+            tables_dirty: false,
+            atlas_dirty: false,
+            octree_buffer,
+            octree_dirty: false,
+            static_bind_group,
+            depth_texture_view,
+            gbuffer_albedo,
+            gbuffer_normal,
+            gbuffer_linear_z,
+            gbuffer_ao,
+            gbuffer_pick,
+            gbuffer_steps,
+            quad_layout_uint,
+            quad_layout_float,
+            quad_pipeline_uint,
+            quad_layout_steps,
+            quad_pipeline_steps,
+            quad_pipeline_float,
+            lighting_layout,
+            lighting_pipeline,
+            lighting_uniform_buffer,
+            lighting_frame_buffer,
+            light_buffer,
+            tile_range_buffer,
+            tile_index_buffer,
+            lights: Vec::new(),
+            shadow_object_buffer,
+            bvh_node_buffer,
+            bvh_item_buffer,
+            occupancy_buffer,
+            shadow_objects_dirty: false,
+            lit_target,
+            sampler,
+            draw_call_array: Vec::new(),
+            bvh: Bvh::default(),
+            bvh_dirty: false,
+            stats: RenderStats::default(),
+            next_pick_id: 1,
+            ao_mode: AoMode::default(),
+            voxel_storage: VoxelStorage::default(),
+            palette_len: 0,
+        }
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        match &mut self.output {
+            Output::Surface { surface, config } => {
+                config.width = width;
+                config.height = height;
+                surface.configure(&self.device, config);
+            }
+            Output::Offscreen(target) => {
+                *target = create_render_target(
+                    &self.device,
+                    width,
+                    height,
+                    OFFSCREEN_FORMAT,
+                    "Offscreen",
+                );
+            }
+        }
+
+        // Recreate depth
+        self.depth_texture_view = create_depth_texture(&self.device, width, height);
+
+        // Recreate G‑buffer targets
+        self.gbuffer_albedo = create_render_target(
+            &self.device,
+            width,
+            height,
+            wgpu::TextureFormat::Rgba8Unorm,
+            "GBuffer Albedo",
+        );
+        self.gbuffer_normal = create_render_target(
+            &self.device,
+            width,
+            height,
+            wgpu::TextureFormat::Rgba8Unorm,
+            "GBuffer Normal",
+        );
+        self.gbuffer_linear_z = create_render_target(
+            &self.device,
+            width,
+            height,
+            wgpu::TextureFormat::R16Uint,
+            "GBuffer LinearZ",
+        );
+        self.gbuffer_ao = create_render_target(
+            &self.device,
+            width,
+            height,
+            wgpu::TextureFormat::R8Unorm,
+            "GBuffer AO",
+        );
+        self.gbuffer_pick = create_render_target(
+            &self.device,
+            width,
+            height,
+            wgpu::TextureFormat::Rg32Uint,
+            "GBuffer Pick",
+        );
+        self.gbuffer_steps = create_render_target(
+            &self.device,
+            width,
+            height,
+            wgpu::TextureFormat::R16Uint,
+            "GBuffer Steps",
+        );
+        self.lit_target = create_render_target(
+            &self.device,
+            width,
+            height,
+            wgpu::TextureFormat::Rgba8Unorm,
+            "Lit Output",
+        );
+    }
+
+    /// Builds the geometry pass writing the G-buffer targets, shading with `fragment_entry_point`
+    /// and dropping faces of the proxy cube per `cull_mode`.
+    fn create_gbuffer_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        fragment_entry_point: &str,
+        cull_mode: Option<wgpu::Face>,
+        label: &str,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                buffers: &[
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![0 => Float32x3],
+                    },
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<u32>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &wgpu::vertex_attr_array![1 => Uint32],
+                    },
+                ],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some(fragment_entry_point),
+                targets: &[
+                    Some(wgpu::ColorTargetState {
+                        format: wgpu::TextureFormat::Rgba8Unorm,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                    Some(wgpu::ColorTargetState {
+                        format: wgpu::TextureFormat::Rgba8Unorm,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                    Some(wgpu::ColorTargetState {
+                        format: wgpu::TextureFormat::R16Uint,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                    Some(wgpu::ColorTargetState {
+                        format: wgpu::TextureFormat::R8Unorm,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                    Some(wgpu::ColorTargetState {
+                        format: wgpu::TextureFormat::Rg32Uint,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                    Some(wgpu::ColorTargetState {
+                        format: wgpu::TextureFormat::R16Uint,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                ],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    /// Builds the deferred lighting pass, which shades the G-buffer into the lit target.
+    fn create_lighting_pipeline(
+        device: &wgpu::Device,
+    ) -> (wgpu::BindGroupLayout, wgpu::RenderPipeline) {
+        let texture_entry = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let buffer_entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let float = wgpu::TextureSampleType::Float { filterable: false };
+        let storage = wgpu::BufferBindingType::Storage { read_only: true };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Lighting Layout"),
+            entries: &[
+                texture_entry(0, float),
+                texture_entry(1, float),
+                buffer_entry(2, wgpu::BufferBindingType::Uniform),
+                texture_entry(3, float),
+                buffer_entry(4, wgpu::BufferBindingType::Uniform),
+                buffer_entry(5, storage),
+                buffer_entry(6, storage),
+                buffer_entry(7, storage),
+                buffer_entry(8, storage),
+                buffer_entry(9, storage),
+                texture_entry(10, float),
+                buffer_entry(11, storage),
+                buffer_entry(12, storage),
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Lighting Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                concat!(
+                    include_str!("shaders/lighting.wgsl"),
+                    include_str!("shaders/dda.wgsl")
+                )
+                .into(),
+            ),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Lighting Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Lighting Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba8Unorm,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: Default::default(),
+            depth_stencil: None,
+            multisample: Default::default(),
+            multiview: None,
+            cache: None,
+        });
+        (layout, pipeline)
+    }
+
+    /// Helper to build a full‑screen quad pipeline + bind‑group layout
+    #[allow(clippy::too_many_arguments)]
+    fn create_fullscreen_quad_pipeline(
+        device: &wgpu::Device,
+        output_format: wgpu::TextureFormat,
+        shader_src: &'static str,
+        sample_type: wgpu::TextureSampleType,
+        sampler_type: wgpu::SamplerBindingType,
+        layout_label: &str,
+        shader_label: &str,
+        pipeline_label: &str,
+    ) -> (
+        wgpu::BindGroupLayout,
+        wgpu::RenderPipeline,
+        wgpu::ShaderModule,
+    ) {
+        // 1) bind‑group layout
+        let quad_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(layout_label),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(sampler_type),
+                    count: None,
+                },
+            ],
+        });
+
+        // 2) shader module
+        let quad_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(shader_label),
+            source: wgpu::ShaderSource::Wgsl(shader_src.into()),
+        });
+
+        // 3) pipeline
+        let quad_pipeline = {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(&format!("{} Layout", pipeline_label)),
+                bind_group_layouts: &[&quad_layout],
+                push_constant_ranges: &[],
+            });
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(pipeline_label),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &quad_shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &quad_shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: output_format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: Default::default(),
+                depth_stencil: None,
+                multisample: Default::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        (quad_layout, quad_pipeline, quad_shader)
+    }
+
+    pub fn render(
+        &mut self,
+        vp_matrix: [f32; 16],
+        view_position: [f32; 3],
+        present_target: PresentTarget,
+    ) -> Result<(), RendererError> {
+        let per_frame_uniforms = PerFrameUniforms {
+            vp_matrix,
+            camera_position: view_position,
+            ao_mode: match self.ao_mode {
+                AoMode::Off => 0,
+                AoMode::Shader => 1,
+                AoMode::Precomputed => 2,
+            },
+            keep_misses: (present_target == PresentTarget::Steps) as u32,
+            voxel_storage: match self.voxel_storage {
+                VoxelStorage::BrickAtlas => 0,
+                VoxelStorage::Octree => 1,
+            },
+            _padding: [0; 2],
+        };
+
+        self.queue.write_buffer(
+            &self.per_frame_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[per_frame_uniforms]),
+        );
+
+        let per_frame_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Per Frame Bind Group"),
+            layout: &self.per_frame_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: self.per_frame_uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let mut visible = self.cull(vp_matrix);
+        // Objects whose front faces do not cover them are drawn last, without conservative depth.
+        let conservative =
+            self.depth_mode == DepthMode::Conservative && self.conservative_pipeline.is_some();
+        let front_facing = if conservative {
+            let vp = Mat4::from_cols_array(&vp_matrix);
+            let (mut covered, rest): (Vec<u32>, Vec<u32>) = visible
+                .iter()
+                .partition(|&&i| self.draw_call_array[i as usize].front_faces_cover(vp));
+            let count = covered.len() as u32;
+            covered.extend(rest);
+            visible = covered;
+            count
+        } else {
+            0
+        };
+        self.upload_objects();
+        self.upload_shadow_objects();
+        let instances = bytemuck::cast_slice(&visible);
+        if instances.len() as wgpu::BufferAddress > self.instance_buffer.size() {
+            self.instance_buffer =
+                create_instance_buffer(&self.device, instances.len() as wgpu::BufferAddress * 2);
+        }
+        self.queue.write_buffer(&self.instance_buffer, 0, instances);
+        let object_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Object Bind Group"),
+            layout: &self.object_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.object_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.brick_table_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&self.atlas_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: self.atlas_ao_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: self.occupancy_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: self.octree_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("GBuffer Pass"),
+                color_attachments: &[
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: &self.gbuffer_albedo.view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    }),
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: &self.gbuffer_normal.view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    }),
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: &self.gbuffer_linear_z.view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    }),
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: &self.gbuffer_ao.view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    }),
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: &self.gbuffer_pick.view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    }),
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: &self.gbuffer_steps.view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    }),
+                ],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        // Read by the lighting pass.
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                ..Default::default()
+            });
+            pass.set_bind_group(0, &self.static_bind_group, &[]);
+            pass.set_bind_group(1, &per_frame_bind_group, &[]);
+            pass.set_bind_group(2, &object_bind_group, &[]);
+            pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+            pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            // Every visible object is one instance of the cube.
+            if let Some(pipeline) = self.conservative_pipeline.as_ref().filter(|_| conservative) {
+                pass.set_pipeline(pipeline);
+                pass.draw_indexed(0..CUBE_INDICES.len() as u32, 0, 0..front_facing);
+            }
+            pass.set_pipeline(&self.render_pipeline);
+            pass.draw_indexed(
+                0..CUBE_INDICES.len() as u32,
+                0,
+                front_facing..visible.len() as u32,
+            );
+        }
+
+        // 2) Lighting pass: shade the G‑buffer into the lit target
+        self.upload_lights(vp_matrix);
+        {
+            let lighting_bind = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &self.lighting_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&self.gbuffer_albedo.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&self.gbuffer_normal.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: self.lighting_uniform_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::TextureView(&self.depth_texture_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: self.lighting_frame_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 5,
+                        resource: self.light_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 6,
+                        resource: self.tile_range_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 7,
+                        resource: self.tile_index_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 8,
+                        resource: self.shadow_object_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 9,
+                        resource: self.occupancy_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 10,
+                        resource: wgpu::BindingResource::TextureView(&self.gbuffer_ao.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 11,
+                        resource: self.bvh_node_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 12,
+                        resource: self.bvh_item_buffer.as_entire_binding(),
+                    },
+                ],
+                label: Some("Lighting BG"),
+            });
+
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Lighting Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.lit_target.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                ..Default::default()
+            });
+            pass.set_pipeline(&self.lighting_pipeline);
+            pass.set_bind_group(0, &lighting_bind, &[]);
+            pass.draw(0..3, 0..1);
+        }
+
+        // 3) Present pass: full‑screen quad sampling chosen G‑buffer
+        let (frame, frame_view) = match &self.output {
+            Output::Surface { surface, .. } => {
+                let frame = surface
+                    .get_current_texture()
+                    .map_err(RendererError::Surface)?;
+                let view = frame.texture.create_view(&Default::default());
+                (Some(frame), view)
+            }
+            Output::Offscreen(target) => (None, target.view.clone()),
+        };
+        {
+            // choose which pipeline & layout
+            let (pipeline, layout, view) = match present_target {
+                PresentTarget::Albedo => (
+                    &self.quad_pipeline_float,
+                    &self.quad_layout_float,
+                    &self.gbuffer_albedo.view,
+                ),
+                PresentTarget::Normal => (
+                    &self.quad_pipeline_float,
+                    &self.quad_layout_float,
+                    &self.gbuffer_normal.view,
+                ),
+                PresentTarget::LinearZ => (
+                    &self.quad_pipeline_uint,
+                    &self.quad_layout_uint,
+                    &self.gbuffer_linear_z.view,
+                ),
+                PresentTarget::Depth => (
+                    &self.quad_pipeline_float,
+                    &self.quad_layout_float,
+                    &self.depth_texture_view,
+                ),
+                PresentTarget::Lit => (
+                    &self.quad_pipeline_float,
+                    &self.quad_layout_float,
+                    &self.lit_target.view,
+                ),
+                PresentTarget::AmbientOcclusion => (
+                    &self.quad_pipeline_float,
+                    &self.quad_layout_float,
+                    &self.gbuffer_ao.view,
+                ),
+                PresentTarget::Steps => (
+                    &self.quad_pipeline_steps,
+                    &self.quad_layout_steps,
+                    &self.gbuffer_steps.view,
+                ),
+            };
+
+            // create bind group
+            let quad_bind = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+                label: Some("Quad Present BG"),
+            });
+
+            // draw full‑screen
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Present Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &frame_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                ..Default::default()
+            });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &quad_bind, &[]);
+            pass.draw(0..3, 0..1);
+        }
+
+        self.queue.submit(Some(encoder.finish()));
+        if let Some(frame) = frame {
+            frame.present();
+        }
+        Ok(())
+    }
+
+    /// Indices of the draw calls whose bounds reach into the frustum of `vp_matrix`, in draw
+    /// order, updating the stats of the frame.
+    fn cull(&mut self, vp_matrix: [f32; 16]) -> Vec<u32> {
+        if self.bvh_dirty {
+            let bounds: Vec<Aabb> = self.draw_call_array.iter().map(|dc| dc.bounds).collect();
+            self.bvh = Bvh::new(&bounds);
+            self.bvh_dirty = false;
+        }
+        let frustum = Frustum::new(Mat4::from_cols_array(&vp_matrix));
+        let mut visible = Vec::new();
+        self.bvh
+            .query_frustum(&frustum, |index| visible.push(index as u32));
+        // Keep draw order, which decides depth ties.
+        visible.sort_unstable();
+
+        self.stats = RenderStats {
+            drawn: visible.len() as u32,
+            culled: (self.draw_call_array.len() - visible.len()) as u32,
+        };
+        visible
+    }
+
+    /// Object counts of the last [`Renderer::render`].
+    pub fn stats(&self) -> RenderStats {
+        self.stats
+    }
+
+    /// Copies a G-buffer target as left by the last [`Renderer::render`] back to the CPU.
+    ///
+    /// Rows are tightly packed from the top: four bytes per pixel for albedo, normal and the
+    /// lit image, one little-endian `u16` for linear Z and steps, and one byte for ambient
+    /// occlusion. The depth buffer cannot be read back.
+    pub async fn read_pixels(&self, target: PresentTarget) -> Result<Vec<u8>, RendererError> {
+        let (texture, bytes_per_pixel) = match target {
+            PresentTarget::Albedo => (&self.gbuffer_albedo.texture, 4),
+            PresentTarget::Normal => (&self.gbuffer_normal.texture, 4),
+            PresentTarget::LinearZ => (&self.gbuffer_linear_z.texture, 2),
+            PresentTarget::Lit => (&self.lit_target.texture, 4),
+            PresentTarget::AmbientOcclusion => (&self.gbuffer_ao.texture, 1),
+            PresentTarget::Steps => (&self.gbuffer_steps.texture, 2),
+            PresentTarget::Depth => return Err(RendererError::UnreadableTarget(target)),
+        };
+        let row_bytes = self.width * bytes_per_pixel;
+        let padded_row_bytes = row_bytes.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback Buffer"),
+            size: (padded_row_bytes * self.height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: Some(self.height),
+                },
+            },
+            texture.size(),
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        self.map_readback(slice).await?;
+        let pixels = slice
+            .get_mapped_range()
+            .chunks(padded_row_bytes as usize)
+            .flat_map(|row| &row[..row_bytes as usize])
+            .copied()
+            .collect();
+        buffer.unmap();
+        Ok(pixels)
+    }
+
+    /// Returns the voxel the last [`Renderer::render`] drew at pixel `(x, y)`, counted from the
+    /// top left, or `None` for background, pixels outside the target and removed objects.
+    ///
+    /// Only the one texel of the pick target is copied back. [`Scene::pick`] gives the same result
+    /// on the CPU.
+    pub async fn pick(&self, x: u32, y: u32) -> Result<Option<PickHit>, RendererError> {
+        if x >= self.width || y >= self.height {
+            return Ok(None);
+        }
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pick Readback Buffer"),
+            size: std::mem::size_of::<[u32; 2]>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.gbuffer_pick.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: None,
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        self.map_readback(slice).await?;
+        let texel: [u32; 2] = bytemuck::pod_read_unaligned(&slice.get_mapped_range());
+        buffer.unmap();
+
+        let texel = PickTexel::decode(texel);
+        if texel.pick_id == 0 {
+            return Ok(None);
+        }
+        Ok(self
+            .draw_call_array
+            .iter()
+            .find(|draw_call| draw_call.pick_id == texel.pick_id)
+            .map(|draw_call| texel.into_hit(draw_call.id.clone(), draw_call.dims)))
+    }
+
+    /// Maps a readback buffer once the copies submitted so far have finished.
+    async fn map_readback(&self, slice: wgpu::BufferSlice<'_>) -> Result<(), RendererError> {
+        let (sender, receiver) = futures_channel::oneshot::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        // Blocks on native backends; on WebGPU the callback runs from the browser's event loop.
+        self.device
+            .poll(wgpu::PollType::Wait)
+            .map_err(RendererError::Poll)?;
+        receiver
+            .await
+            .expect("buffer mapping callback is always invoked")
+            .map_err(RendererError::BufferMap)
+    }
+
+    /// Sets the sun and ambient light used by the lighting pass.
+    pub fn set_lighting(&mut self, lighting: &Lighting) -> Result<(), LightError> {
+        lighting.validate()?;
+        self.queue.write_buffer(
+            &self.lighting_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[LightingUniforms::new(lighting)]),
+        );
+        Ok(())
+    }
+
+    /// Adds a point or spot light shaded by the lighting pass.
+    pub fn add_light(&mut self, id: &str, light: &Light) -> Result<(), LightError> {
+        if self.find_light(id).is_some() {
+            return Err(LightError::Duplicate { id: id.to_owned() });
+        }
+        light.validate(id)?;
+        self.lights.push((id.to_owned(), *light));
+        Ok(())
+    }
+
+    pub fn update_light(&mut self, id: &str, light: &Light) -> Result<(), LightError> {
+        light.validate(id)?;
+        let index = self.light_index(id)?;
+        self.lights[index].1 = *light;
+        Ok(())
+    }
+
+    pub fn remove_light(&mut self, id: &str) -> Result<(), LightError> {
+        let index = self.light_index(id)?;
+        self.lights.remove(index);
+        Ok(())
+    }
+
+    fn find_light(&self, id: &str) -> Option<usize> {
+        self.lights.iter().position(|(light_id, _)| light_id == id)
+    }
+
+    fn light_index(&self, id: &str) -> Result<usize, LightError> {
+        self.find_light(id)
+            .ok_or_else(|| LightError::NotFound { id: id.to_owned() })
+    }
+
+    /// Uploads the light list along with this frame's per-tile light lists.
+    fn upload_lights(&mut self, vp_matrix: [f32; 16]) {
+        let lights: Vec<Light> = self.lights.iter().map(|(_, light)| *light).collect();
+        let bins = bin_lights(&lights, vp_matrix, self.width, self.height);
+        let gpu_lights: Vec<GpuLight> = lights.iter().map(GpuLight::new).collect();
+
+        let frame = LightingFrameUniforms {
+            inv_vp_matrix: glam::Mat4::from_cols_array(&vp_matrix)
+                .inverse()
+                .to_cols_array(),
+            tiles_x: bins.tiles_x,
+            object_count: self.draw_call_array.len() as u32,
+            _padding: [0; 2],
+        };
+        self.queue
+            .write_buffer(&self.lighting_frame_buffer, 0, bytemuck::bytes_of(&frame));
+        upload_storage_buffer(
+            &self.device,
+            &self.queue,
+            &mut self.light_buffer,
+            bytemuck::cast_slice(&gpu_lights),
+            "Light Buffer",
+        );
+        upload_storage_buffer(
+            &self.device,
+            &self.queue,
+            &mut self.tile_range_buffer,
+            bytemuck::cast_slice(&bins.ranges),
+            "Tile Range Buffer",
+        );
+        upload_storage_buffer(
+            &self.device,
+            &self.queue,
+            &mut self.tile_index_buffer,
+            bytemuck::cast_slice(&bins.indices),
+            "Tile Index Buffer",
+        );
+    }
+
+    /// Uploads every object's transform and occupancy bits for shadow rays, and the hierarchy
+    /// over their bounds, if they changed. Expects `bvh` to be current, see `cull`.
+    fn upload_shadow_objects(&mut self) {
+        if !self.shadow_objects_dirty {
+            return;
+        }
+        let mut objects = Vec::with_capacity(self.draw_call_array.len());
+        let mut occupancy = Vec::new();
+        for dc in &self.draw_call_array {
+            objects.push(GpuShadowObject {
+                inverse_model_matrix: dc.inverse_model_matrix,
+                dims: dc.dims,
+                offset: dc.occupancy_offset,
+            });
+            occupancy.extend_from_slice(dc.occupancy.words());
+        }
+        upload_storage_buffer(
+            &self.device,
+            &self.queue,
+            &mut self.shadow_object_buffer,
+            bytemuck::cast_slice(&objects),
+            "Shadow Object Buffer",
+        );
+        upload_storage_buffer(
+            &self.device,
+            &self.queue,
+            &mut self.occupancy_buffer,
+            bytemuck::cast_slice(&occupancy),
+            "Occupancy Buffer",
+        );
+        let nodes: Vec<GpuBvhNode> = self
+            .bvh
+            .flat_nodes()
+            .map(|(bounds, start, count)| GpuBvhNode {
+                min: bounds.min.to_array(),
+                start,
+                max: bounds.max.to_array(),
+                count,
+            })
+            .collect();
+        upload_storage_buffer(
+            &self.device,
+            &self.queue,
+            &mut self.bvh_node_buffer,
+            bytemuck::cast_slice(&nodes),
+            "BVH Node Buffer",
+        );
+        upload_storage_buffer(
+            &self.device,
+            &self.queue,
+            &mut self.bvh_item_buffer,
+            bytemuck::cast_slice(self.bvh.items()),
+            "BVH Item Buffer",
+        );
+        self.shadow_objects_dirty = false;
+    }
+
+    /// Writes the brick tables, the atlas and the object table, as far as they changed.
+    fn upload_objects(&mut self) {
+        if self.tables_dirty {
+            let mut tables = Vec::new();
+            let mut occupancy_len = 0;
+            for dc in &mut self.draw_call_array {
+                dc.brick_offset = tables.len() as u32;
+                tables.extend_from_slice(&dc.bricks);
+                dc.occupancy_offset = occupancy_len;
+                occupancy_len += dc.occupancy.words().len() as u32;
+            }
+            upload_storage_buffer(
+                &self.device,
+                &self.queue,
+                &mut self.brick_table_buffer,
+                bytemuck::cast_slice(&tables),
+                "Brick Table Buffer",
+            );
+            self.tables_dirty = false;
+            self.objects_dirty = true;
+        }
+        if self.atlas_dirty {
+            self.write_atlas();
+            self.atlas_dirty = false;
+        }
+        if self.octree_dirty {
+            let mut nodes = Vec::new();
+            for dc in &mut self.draw_call_array {
+                dc.octree_offset = nodes.len() as u32;
+                dc.octree_words = 0;
+                if let Some(octree) = &dc.octree {
+                    // Room for edits to add half as many nodes again before octrees move.
+                    let len = octree.nodes().len();
+                    dc.octree_words = (len + len / 2 + 8) as u32;
+                    nodes.extend_from_slice(octree.nodes());
+                    nodes.resize(nodes.len() - len + dc.octree_words as usize, 0);
+                }
+            }
+            upload_storage_buffer(
+                &self.device,
+                &self.queue,
+                &mut self.octree_buffer,
+                bytemuck::cast_slice(&nodes),
+                "Octree Buffer",
+            );
+            self.octree_dirty = false;
+            self.objects_dirty = true;
+        }
+        if self.objects_dirty {
+            let objects: Vec<GpuObject> = self.draw_call_array.iter().map(GpuObject::new).collect();
+            upload_storage_buffer(
+                &self.device,
+                &self.queue,
+                &mut self.object_buffer,
+                bytemuck::cast_slice(&objects),
+                "Object Buffer",
+            );
+            self.objects_dirty = false;
+        }
+    }
+
+    /// Bricks the atlas has room for.
+    fn atlas_capacity(&self) -> u32 {
+        self.atlas.depth_or_array_layers() / BRICK_SIZE * ATLAS_BRICKS_XY * ATLAS_BRICKS_XY
+    }
+
+    /// Most bricks the atlas can grow to hold, bounded by the deepest 3D texture.
+    fn max_bricks(&self) -> u32 {
+        let max_layers = self.device.limits().max_texture_dimension_3d / BRICK_SIZE;
+        max_layers * ATLAS_BRICKS_XY * ATLAS_BRICKS_XY
+    }
+
+    /// Replaces the atlas with a deeper one when the allocator handed out more slots than it
+    /// holds. The bricks reach the new atlas with the next frame's upload.
+    fn grow_atlas(&mut self) {
+        if self.bricks.len() <= self.atlas_capacity() {
+            return;
+        }
+        let per_layer = ATLAS_BRICKS_XY * ATLAS_BRICKS_XY;
+        let max_layers = self.max_bricks().div_ceil(per_layer);
+        let layers = self
+            .bricks
+            .len()
+            .div_ceil(per_layer)
+            .max(self.atlas.depth_or_array_layers() / BRICK_SIZE * 2)
+            .min(max_layers);
+        (self.atlas, self.atlas_view) = create_atlas(&self.device, layers);
+        self.atlas_ao_buffer = create_atlas_ao_buffer(&self.device, layers, self.ao_mode);
+        self.atlas_dirty = true;
+    }
+
+    /// Writes every brick of every object to the atlas at once.
+    fn write_atlas(&self) {
+        let size = self.atlas.size();
+        let (width, height) = (size.width as usize, size.height as usize);
+        let mut texels = vec![0u8; width * height * size.depth_or_array_layers as usize];
+        let mut ao = vec![0u8; self.atlas_ao_buffer.size() as usize];
+        let ao_bricks = if self.ao_mode == AoMode::Precomputed {
+            ao.len() / BRICK_AO_BYTES
+        } else {
+            0
+        };
+        for dc in &self.draw_call_array {
+            for (brick, &slot) in dc.bricks.iter().enumerate() {
+                if slot == EMPTY_BRICK {
+                    continue;
+                }
+                let voxels = dc.brick_voxels(brick);
+                let [ox, oy, oz] = atlas_origin(slot).map(|o| o as usize);
+                let size = BRICK_SIZE as usize;
+                for (row, voxels) in voxels.chunks(size).enumerate() {
+                    let (y, z) = (oy + row % size, oz + row / size);
+                    let start = ox + width * (y + height * z);
+                    texels[start..start + size].copy_from_slice(voxels);
+                }
+                if (slot as usize) < ao_bricks {
+                    let start = slot as usize * BRICK_AO_BYTES;
+                    ao[start..start + BRICK_AO_BYTES]
+                        .copy_from_slice(dc.brick_ao(brick).as_flattened());
+                }
+            }
+        }
+        self.queue.write_texture(
+            self.atlas.as_image_copy(),
+            &texels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(size.width),
+                rows_per_image: Some(size.height),
+            },
+            size,
+        );
+        if ao_bricks > 0 {
+            self.queue.write_buffer(&self.atlas_ao_buffer, 0, &ao);
+        }
+    }
+
+    /// Writes brick `brick` of draw call `index` to its atlas slot.
+    fn write_brick(&self, index: usize, brick: usize) {
+        let dc = &self.draw_call_array[index];
+        let slot = dc.bricks[brick];
+        let [x, y, z] = atlas_origin(slot);
+        self.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.atlas,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &dc.brick_voxels(brick),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(BRICK_SIZE),
+                rows_per_image: Some(BRICK_SIZE),
+            },
+            wgpu::Extent3d {
+                width: BRICK_SIZE,
+                height: BRICK_SIZE,
+                depth_or_array_layers: BRICK_SIZE,
+            },
+        );
+        let ao_offset = (slot as usize * BRICK_AO_BYTES) as wgpu::BufferAddress;
+        if self.ao_mode == AoMode::Precomputed && ao_offset < self.atlas_ao_buffer.size() {
+            self.queue.write_buffer(
+                &self.atlas_ao_buffer,
+                ao_offset,
+                dc.brick_ao(brick).as_flattened(),
+            );
+        }
+    }
+
+    pub fn adapter_info(&self) -> &wgpu::AdapterInfo {
+        &self.adapter_info
+    }
+
+    /// Replaces all GPU scene resources; the scene is validated first and rejected as a whole.
+    pub fn upload_scene(&mut self, scene: &Scene) -> Result<(), SceneError> {
+        scene.validate()?;
+        let mut bricks = BrickAllocator::new();
+        let mut draw_calls = Vec::with_capacity(scene.objects.len());
+        for obj in &scene.objects {
+            let pick_id = self.allocate_pick_id();
+            draw_calls.push(create_draw_call(
+                obj,
+                pick_id,
+                &mut bricks,
+                self.ao_mode,
+                self.voxel_storage,
+            ));
+            self.check_capacity(&obj.id, bricks.in_use())?;
+        }
+
+        self.write_palette(&scene.palette);
+        self.draw_call_array = draw_calls;
+        self.bricks = bricks;
+        self.grow_atlas();
+        self.shadow_objects_dirty = true;
+        self.tables_dirty = true;
+        self.atlas_dirty = true;
+        self.octree_dirty = true;
+        self.bvh_dirty = true;
+
+        Ok(())
+    }
+
+    /// Uploads a single object without touching the rest of the scene.
+    ///
+    /// Its voxels are checked against the palette of the last [`Renderer::upload_scene`] or
+    /// [`Renderer::update_palette`]; before either there is no palette, so only objects without
+    /// solid voxels are accepted.
+    pub fn add_object(&mut self, obj: &VoxelObject) -> Result<(), SceneError> {
+        if self.find_draw_call(&obj.id).is_some() {
+            return Err(SceneError::InvalidObject {
+                id: obj.id.clone(),
+                field: ObjectField::Id,
+                reason: InvalidReason::Duplicate,
+            });
+        }
+        obj.validate(self.palette_len)?;
+
+        let pick_id = self.allocate_pick_id();
+        let draw_call = create_draw_call(
+            obj,
+            pick_id,
+            &mut self.bricks,
+            self.ao_mode,
+            self.voxel_storage,
+        );
+        if let Err(e) = self.check_capacity(&obj.id, self.bricks.in_use()) {
+            free_bricks(&mut self.bricks, &draw_call);
+            return Err(e);
+        }
+        self.draw_call_array.push(draw_call);
+        self.grow_atlas();
+        if !self.atlas_dirty {
+            let index = self.draw_call_array.len() - 1;
+            for (brick, &slot) in self.draw_call_array[index].bricks.iter().enumerate() {
+                if slot != EMPTY_BRICK {
+                    self.write_brick(index, brick);
+                }
+            }
+        }
+        self.shadow_objects_dirty = true;
+        self.tables_dirty = true;
+        self.octree_dirty = true;
+        self.bvh_dirty = true;
+        Ok(())
+    }
+
+    pub fn remove_object(&mut self, id: &str) -> Result<(), SceneError> {
+        let index = self.draw_call_index(id)?;
+        let draw_call = self.draw_call_array.remove(index);
+        free_bricks(&mut self.bricks, &draw_call);
+        self.shadow_objects_dirty = true;
+        self.tables_dirty = true;
+        self.octree_dirty = true;
+        self.bvh_dirty = true;
+        Ok(())
+    }
+
+    /// Rewrites the object's entry in the object buffer in place.
+    pub fn set_object_transform(
+        &mut self,
+        id: &str,
+        transform: &Transform,
+    ) -> Result<(), SceneError> {
+        validate_transform(id, transform)?;
+        let index = self.draw_call_index(id)?;
+        let bounds = Aabb::of_unit_cube(transform.matrix());
+        let draw_call = &mut self.draw_call_array[index];
+        draw_call.model_matrix = transform.matrix().to_cols_array();
+        draw_call.inverse_model_matrix = transform.inverse_matrix().to_cols_array();
+        draw_call.bounds = bounds;
+        if !self.objects_dirty && !self.tables_dirty {
+            self.queue.write_buffer(
+                &self.object_buffer,
+                (index * std::mem::size_of::<GpuObject>()) as wgpu::BufferAddress,
+                bytemuck::bytes_of(&GpuObject::new(draw_call)),
+            );
+        }
+        if !self.bvh_dirty {
+            self.bvh.refit(index, bounds);
+        }
+        self.shadow_objects_dirty = true;
+        Ok(())
+    }
+
+    /// Overwrites an `extent`-sized box of the object's voxels starting at `origin`.
+    pub fn write_voxels(
+        &mut self,
+        id: &str,
+        origin: [u32; 3],
+        extent: [u32; 3],
+        data: &[u8],
+    ) -> Result<(), SceneError> {
+        let index = self.draw_call_index(id)?;
+        let dims = self.draw_call_array[index].dims;
+        validate_region(id, dims, origin, extent, data)?;
+        validate_palette_indices(id, data, self.palette_len)?;
+        if extent.contains(&0) {
+            return Ok(());
+        }
+
+        // Empty bricks that the region puts solid voxels in need slots; check before changing
+        // anything.
+        let table = &self.draw_call_array[index].bricks;
+        let new_bricks = bricks_to_fill(table, dims, origin, extent, data);
+        self.check_capacity(id, self.bricks.in_use() + new_bricks)?;
+
+        let bricks = brick_dims(dims);
+        let brick_index = |[x, y, z]: [u32; 3]| (x + bricks[0] * (y + bricks[1] * z)) as usize;
+        let brick_box = |min: [u32; 3], max: [u32; 3]| {
+            let (min, max) = (
+                min.map(|m| m / BRICK_SIZE),
+                max.map(|m| m.div_ceil(BRICK_SIZE)),
+            );
+            (min[2]..max[2]).flat_map(move |z| {
+                (min[1]..max[1]).flat_map(move |y| (min[0]..max[0]).map(move |x| [x, y, z]))
+            })
+        };
+        let [ex, ey, _] = extent;
+        let linear = |[x, y, z]: [u32; 3]| (x + dims[0] * (y + dims[1] * z)) as usize;
+        let draw_call = &mut self.draw_call_array[index];
+        for (i, &voxel) in data.iter().enumerate() {
+            let i = i as u32;
+            let coord = [
+                origin[0] + i % ex,
+                origin[1] + i / ex % ey,
+                origin[2] + i / (ex * ey),
+            ];
+            draw_call.voxels[linear(coord)] = voxel;
+            draw_call.occupancy.set_voxel(coord, voxel != 0);
+        }
+        self.shadow_objects_dirty = true;
+
+        // Occlusion also changes for faces of the voxels bordering the region.
+        let min = origin.map(|o| o.saturating_sub(1));
+        let max = [0, 1, 2].map(|axis| (origin[axis] + extent[axis] + 1).min(dims[axis]));
+        if self.ao_mode == AoMode::Precomputed {
+            rebake_ao(draw_call, min, max);
+        }
+        if let Some(octree) = &mut draw_call.octree {
+            let changed = octree.update_region(&draw_call.voxels, origin, extent);
+            if octree.nodes().len() > draw_call.octree_words as usize {
+                // Out of room; the next frame lays the octrees out again.
+                self.octree_dirty = true;
+            } else if !self.octree_dirty {
+                for range in changed {
+                    self.queue.write_buffer(
+                        &self.octree_buffer,
+                        ((draw_call.octree_offset as usize + range.start)
+                            * std::mem::size_of::<u32>())
+                            as wgpu::BufferAddress,
+                        bytemuck::cast_slice(&octree.nodes()[range]),
+                    );
+                }
+            }
+        }
+
+        // Bricks emptied by the edit give up their slots; bricks filled by it take one.
+        let mut changed = Vec::new();
+        let mut table_changed = false;
+        for brick in brick_box(min, max).map(brick_index) {
+            let draw_call = &mut self.draw_call_array[index];
+            let solid = draw_call.brick_is_solid(brick);
+            let slot = &mut draw_call.bricks[brick];
+            match (*slot == EMPTY_BRICK, solid) {
+                (true, true) => {
+                    *slot = self.bricks.allocate();
+                    table_changed = true;
+                }
+                (false, false) => {
+                    self.bricks.free(*slot);
+                    *slot = EMPTY_BRICK;
+                    table_changed = true;
+                    continue;
+                }
+                (true, false) => continue,
+                (false, true) => {}
+            }
+            changed.push(brick);
+        }
+        self.grow_atlas();
+        if !self.atlas_dirty {
+            for &brick in &changed {
+                self.write_brick(index, brick);
+            }
+        }
+        // The rest waits for the next frame's upload, which moves tables around anyway.
+        if table_changed && !self.tables_dirty {
+            let draw_call = &self.draw_call_array[index];
+            self.queue.write_buffer(
+                &self.brick_table_buffer,
+                (draw_call.brick_offset as usize * std::mem::size_of::<u32>())
+                    as wgpu::BufferAddress,
+                bytemuck::cast_slice(&draw_call.bricks),
+            );
+        }
+        Ok(())
+    }
+
+    /// Selects where the G-buffer raymarch reads voxels from; entering [`VoxelStorage::Octree`]
+    /// builds every object's octree.
+    pub fn set_voxel_storage(&mut self, storage: VoxelStorage) {
+        if storage == self.voxel_storage {
+            return;
+        }
+        for dc in &mut self.draw_call_array {
+            dc.octree = (storage == VoxelStorage::Octree)
+                .then(|| SparseVoxelOctree::new(&dc.voxels, dc.dims));
+        }
+        if storage == VoxelStorage::BrickAtlas {
+            self.octree_buffer = create_storage_buffer(&self.device, 0, "Octree Buffer");
+        }
+        self.octree_dirty = true;
+        self.voxel_storage = storage;
+    }
+
+    /// Selects how the G-buffer pass writes depth.
+    pub fn set_depth_mode(&mut self, mode: DepthMode) -> Result<(), RendererError> {
+        if mode == DepthMode::Conservative && self.conservative_pipeline.is_none() {
+            return Err(RendererError::MissingFeatures(
+                wgpu::Features::SHADER_EARLY_DEPTH_TEST,
+            ));
+        }
+        self.depth_mode = mode;
+        Ok(())
+    }
+
+    /// Selects where the G-buffer pass gets ambient occlusion from.
+    ///
+    /// Occlusion is baked and kept, on the CPU and in an atlas-sized buffer, only in
+    /// [`AoMode::Precomputed`]; entering it bakes every object.
+    pub fn set_ao_mode(&mut self, mode: AoMode) {
+        if mode == self.ao_mode {
+            return;
+        }
+        let precomputed = mode == AoMode::Precomputed;
+        if precomputed || self.ao_mode == AoMode::Precomputed {
+            for dc in &mut self.draw_call_array {
+                if precomputed {
+                    dc.ao = vec![[0; 6]; dc.voxels.len()];
+                    rebake_ao(dc, [0; 3], dc.dims);
+                } else {
+                    dc.ao = Vec::new();
+                }
+            }
+            let layers = self.atlas.depth_or_array_layers() / BRICK_SIZE;
+            self.atlas_ao_buffer = create_atlas_ao_buffer(&self.device, layers, mode);
+            // The whole atlas is rewritten to fill the new buffer.
+            self.atlas_dirty |= precomputed;
+        }
+        self.ao_mode = mode;
+    }
+
+    /// Replaces the palette, which must still cover every palette index of the objects uploaded.
+    pub fn update_palette(&mut self, palette: &[RGBA]) -> Result<(), SceneError> {
+        validate_palette(palette)?;
+        if palette.len() < self.palette_len {
+            for dc in &self.draw_call_array {
+                validate_palette_indices(&dc.id, &dc.voxels, palette.len())?;
+            }
+        }
+        self.write_palette(palette);
+        Ok(())
+    }
+
+    fn allocate_pick_id(&mut self) -> u32 {
+        let pick_id = self.next_pick_id;
+        self.next_pick_id = pick_id % ((1 << PICK_ID_BITS) - 1) + 1;
+        pick_id
+    }
+
+    fn find_draw_call(&self, id: &str) -> Option<usize> {
+        self.draw_call_array.iter().position(|dc| dc.id == id)
+    }
+
+    fn draw_call_index(&self, id: &str) -> Result<usize, SceneError> {
+        self.find_draw_call(id)
+            .ok_or_else(|| SceneError::ObjectNotFound { id: id.to_owned() })
+    }
+
+    /// Checks that `bricks` bricks, counting those of object `id` and the objects before it, fit
+    /// in the atlas.
+    fn check_capacity(&self, id: &str, bricks: u32) -> Result<(), SceneError> {
+        let max = self.max_bricks();
+        if bricks > max {
+            return Err(SceneError::InvalidObject {
+                id: id.to_owned(),
+                field: ObjectField::Voxels,
+                reason: InvalidReason::CapacityExceeded {
+                    max: u64::from(max) * BRICK_VOXELS as u64,
+                },
+            });
+        }
+        Ok(())
+    }
+
+    fn write_palette(&mut self, palette: &[RGBA]) {
+        let mut color_palette: [u32; 256] = [0; 256];
+        for (i, color) in palette.iter().enumerate() {
+            color_palette[i] = utils::pack_rgba(color);
+        }
+        let static_uniforms = StaticUniforms { color_palette };
+        self.queue.write_buffer(
+            &self.static_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[static_uniforms]),
+        );
+        self.palette_len = palette.len();
+    }
+}
+
+impl DrawCallData {
+    /// Position of brick `brick` in the object's grid of bricks.
+    fn brick_coord(&self, brick: usize) -> [u32; 3] {
+        let [bx, by, _] = brick_dims(self.dims);
+        let brick = brick as u32;
+        [brick % bx, brick / bx % by, brick / (bx * by)]
+    }
+
+    /// Palette indices of brick `brick`, in the atlas's voxel order.
+    fn brick_voxels(&self, brick: usize) -> [u8; BRICK_VOXELS] {
+        extract_brick(&self.voxels, self.dims, self.brick_coord(brick), 0)
+    }
+
+    fn brick_ao(&self, brick: usize) -> [[u8; 6]; BRICK_VOXELS] {
+        extract_brick(&self.ao, self.dims, self.brick_coord(brick), [0; 6])
+    }
+
+    fn brick_is_solid(&self, brick: usize) -> bool {
+        self.brick_voxels(brick).iter().any(|&v| v != 0)
+    }
+
+    /// Whether the front faces of the proxy cube alone cover the object under `vp`: the cube
+    /// lies wholly past the near plane, so the camera is outside it, and is not mirrored, so
+    /// its front faces keep their winding.
+    fn front_faces_cover(&self, vp: Mat4) -> bool {
+        let model = Mat4::from_cols_array(&self.model_matrix);
+        let clip = vp * model;
+        model.determinant() > 0.0
+            && (0..8).all(|corner| {
+                let local = glam::Vec3::new(
+                    (corner & 1) as f32 - 0.5,
+                    (corner >> 1 & 1) as f32 - 0.5,
+                    (corner >> 2 & 1) as f32 - 0.5,
+                );
+                let position = clip * local.extend(1.0);
+                position.w > 0.0 && position.z >= 0.0
+            })
+    }
+}
+
+/// Copies an object's data, bakes its occlusion in [`AoMode::Precomputed`], builds its octree in
+/// [`VoxelStorage::Octree`] and gives each of its bricks with solid voxels an atlas slot from
+/// `bricks`.
+fn create_draw_call(
+    obj: &VoxelObject,
+    pick_id: u32,
+    bricks: &mut BrickAllocator,
+    ao_mode: AoMode,
+    voxel_storage: VoxelStorage,
+) -> DrawCallData {
+    let mut draw_call = DrawCallData {
+        id: obj.id.clone(),
+        dims: obj.dims,
+        model_matrix: obj.model_matrix(),
+        inverse_model_matrix: obj.inv_model_matrix(),
+        voxels: obj.voxels.clone(),
+        occupancy: OccupancyPyramid::new(&obj.voxels, obj.dims),
+        occupancy_offset: 0,
+        ao: Vec::new(),
+        bricks: Vec::new(),
+        brick_offset: 0,
+        octree: (voxel_storage == VoxelStorage::Octree)
+            .then(|| SparseVoxelOctree::from_object(obj)),
+        octree_offset: 0,
+        octree_words: 0,
+        bounds: obj.world_bounds(),
+        pick_id,
+    };
+    if ao_mode == AoMode::Precomputed {
+        draw_call.ao = vec![[0; 6]; obj.voxels.len()];
+        rebake_ao(&mut draw_call, [0; 3], obj.dims);
+    }
+    let [bx, by, bz] = brick_dims(obj.dims);
+    draw_call.bricks = (0..(bx * by * bz) as usize)
+        .map(|brick| {
+            if draw_call.brick_is_solid(brick) {
+                bricks.allocate()
+            } else {
+                EMPTY_BRICK
+            }
+        })
+        .collect();
+    draw_call
+}
+
+fn free_bricks(bricks: &mut BrickAllocator, draw_call: &DrawCallData) {
+    for &slot in &draw_call.bricks {
+        if slot != EMPTY_BRICK {
+            bricks.free(slot);
+        }
+    }
+}
+
+/// Bakes ambient occlusion for the box `[min, max)` of an object.
+fn rebake_ao(draw_call: &mut DrawCallData, min: [u32; 3], max: [u32; 3]) {
+    let occupancy = draw_call.occupancy.words();
+    let solid = |i: usize| occupancy[i / 32] & (1 << (i % 32)) != 0;
+    let entries = bake_ao_region(draw_call.dims, solid, min, max);
+    let [nx, ny, _] = draw_call.dims.map(|d| d as usize);
+    let row = (max[0] - min[0]) as usize;
+    let mut rows = entries.chunks(row);
+    for z in min[2] as usize..max[2] as usize {
+        for y in min[1] as usize..max[1] as usize {
+            let start = min[0] as usize + nx * (y + ny * z);
+            let entries = rows.next().expect("one row of entries per row of the box");
+            draw_call.ao[start..start + row].copy_from_slice(entries);
+        }
+    }
+}
@@ -1,20 +1,313 @@
+use std::collections::HashSet;
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 
+use crate::bvh::Aabb;
 use crate::primitives::RGBA;
+use crate::transform::Transform;
+
+/// Maximum number of palette entries; voxels store palette indices as `u8`.
+pub const MAX_PALETTE_LEN: usize = 256;
 
-/// A voxel object: an 8×8×8 grid of palette indices.
-#[derive(Serialize, Deserialize)]
+/// A voxel object: a `dims`-sized grid of palette indices filling the unit cube placed by
+/// `transform`, with `voxels` laid out x-fastest, then y, then z, and 0 for empty voxels.
+///
+/// Unknown fields are rejected when deserializing, so payloads still carrying the old
+/// `model_matrix` and `inv_model_matrix` fail instead of loading with the wrong placement.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct VoxelObject {
     pub id: String,
-    pub model_matrix: [f32; 16],
-    pub inv_model_matrix: [f32; 16],
+    pub transform: Transform,
     pub dims: [u32; 3],
     pub voxels: Vec<u8>,
 }
 
 /// The scene containing a shared palette and multiple voxel objects.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Scene {
     pub palette: Vec<RGBA>,
     pub objects: Vec<VoxelObject>,
 }
+
+/// Field of a [`VoxelObject`] that failed validation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ObjectField {
+    Id,
+    Transform,
+    Dims,
+    Voxels,
+}
+
+impl fmt::Display for ObjectField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ObjectField::Id => "id",
+            ObjectField::Transform => "transform",
+            ObjectField::Dims => "dims",
+            ObjectField::Voxels => "voxels",
+        })
+    }
+}
+
+/// Why a [`VoxelObject`] field failed validation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InvalidReason {
+    Duplicate,
+    NonFinite,
+    Singular,
+    /// The transform has shear or projection, so it cannot be split into translation, rotation
+    /// and scale.
+    NotDecomposable,
+    ZeroDimension,
+    /// The voxel count implied by `dims` does not fit in `usize`.
+    VoxelCountOverflow,
+    LengthMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    RegionOutOfBounds {
+        origin: [u32; 3],
+        extent: [u32; 3],
+    },
+    PaletteIndexOutOfRange {
+        index: u8,
+        palette_len: usize,
+    },
+    /// The renderer's buffers cannot hold this many voxels.
+    CapacityExceeded {
+        max: u64,
+    },
+}
+
+impl fmt::Display for InvalidReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidReason::Duplicate => write!(f, "is not unique"),
+            InvalidReason::NonFinite => write!(f, "contains NaN or infinite values"),
+            InvalidReason::Singular => write!(f, "is not invertible"),
+            InvalidReason::NotDecomposable => {
+                write!(f, "is not a translation, rotation and scale")
+            }
+            InvalidReason::ZeroDimension => write!(f, "has a zero-sized axis"),
+            InvalidReason::VoxelCountOverflow => {
+                write!(f, "holds more voxels than can be addressed")
+            }
+            InvalidReason::LengthMismatch { expected, actual } => {
+                write!(f, "has {actual} entries, expected {expected}")
+            }
+            InvalidReason::RegionOutOfBounds { origin, extent } => write!(
+                f,
+                "region at {origin:?} with extent {extent:?} lies outside the grid"
+            ),
+            InvalidReason::CapacityExceeded { max } => {
+                write!(f, "do not fit in the renderer's room for {max} voxels")
+            }
+            InvalidReason::PaletteIndexOutOfRange { index, palette_len } => write!(
+                f,
+                "references palette index {index} but the palette has {palette_len} entries"
+            ),
+        }
+    }
+}
+
+/// Errors reported by [`Scene::validate`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum SceneError {
+    PaletteTooLarge {
+        len: usize,
+    },
+    ObjectNotFound {
+        id: String,
+    },
+    InvalidObject {
+        id: String,
+        field: ObjectField,
+        reason: InvalidReason,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::PaletteTooLarge { len } => write!(
+                f,
+                "palette has {len} entries, at most {MAX_PALETTE_LEN} are supported"
+            ),
+            SceneError::ObjectNotFound { id } => write!(f, "object '{id}' does not exist"),
+            SceneError::InvalidObject { id, field, reason } => {
+                write!(f, "object '{id}': {field} {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+pub(crate) fn validate_palette(palette: &[RGBA]) -> Result<(), SceneError> {
+    if palette.len() > MAX_PALETTE_LEN {
+        return Err(SceneError::PaletteTooLarge { len: palette.len() });
+    }
+    Ok(())
+}
+
+pub(crate) fn validate_transform(id: &str, transform: &Transform) -> Result<(), SceneError> {
+    let invalid = |reason| SceneError::InvalidObject {
+        id: id.to_owned(),
+        field: ObjectField::Transform,
+        reason,
+    };
+
+    let model = transform.matrix();
+    if !model.is_finite() {
+        return Err(invalid(InvalidReason::NonFinite));
+    }
+    if model.determinant() == 0.0 {
+        return Err(invalid(InvalidReason::Singular));
+    }
+    Ok(())
+}
+
+/// Checks that a `extent`-sized box of `data` at `origin` fits inside a `dims` grid.
+pub(crate) fn validate_region(
+    id: &str,
+    dims: [u32; 3],
+    origin: [u32; 3],
+    extent: [u32; 3],
+    data: &[u8],
+) -> Result<(), SceneError> {
+    let invalid = |reason| SceneError::InvalidObject {
+        id: id.to_owned(),
+        field: ObjectField::Voxels,
+        reason,
+    };
+
+    let fits = (0..3).all(|axis| {
+        origin[axis]
+            .checked_add(extent[axis])
+            .is_some_and(|end| end <= dims[axis])
+    });
+    if !fits {
+        return Err(invalid(InvalidReason::RegionOutOfBounds { origin, extent }));
+    }
+    let expected = extent.iter().map(|&e| e as usize).product();
+    if data.len() != expected {
+        return Err(invalid(InvalidReason::LengthMismatch {
+            expected,
+            actual: data.len(),
+        }));
+    }
+    Ok(())
+}
+
+pub(crate) fn validate_palette_indices(
+    id: &str,
+    voxels: &[u8],
+    palette_len: usize,
+) -> Result<(), SceneError> {
+    if let Some(&index) = voxels
+        .iter()
+        .find(|&&index| index != 0 && index as usize >= palette_len)
+    {
+        return Err(SceneError::InvalidObject {
+            id: id.to_owned(),
+            field: ObjectField::Voxels,
+            reason: InvalidReason::PaletteIndexOutOfRange { index, palette_len },
+        });
+    }
+    Ok(())
+}
+
+impl VoxelObject {
+    pub fn model_matrix(&self) -> [f32; 16] {
+        self.transform.matrix().to_cols_array()
+    }
+
+    pub fn inv_model_matrix(&self) -> [f32; 16] {
+        self.transform.inverse_matrix().to_cols_array()
+    }
+
+    /// World-space bounds of the object's unit cube.
+    pub fn world_bounds(&self) -> Aabb {
+        Aabb::of_unit_cube(self.transform.matrix())
+    }
+
+    /// Number of voxels implied by `dims`, or `None` if it overflows `usize`.
+    pub fn voxel_count(&self) -> Option<usize> {
+        self.dims
+            .iter()
+            .try_fold(1usize, |acc, &d| acc.checked_mul(d as usize))
+    }
+
+    /// Checks the object's invariants against a palette of `palette_len` entries.
+    pub fn validate(&self, palette_len: usize) -> Result<(), SceneError> {
+        let invalid = |field, reason| SceneError::InvalidObject {
+            id: self.id.clone(),
+            field,
+            reason,
+        };
+
+        validate_transform(&self.id, &self.transform)?;
+
+        if self.dims.contains(&0) {
+            return Err(invalid(ObjectField::Dims, InvalidReason::ZeroDimension));
+        }
+        let expected = self
+            .voxel_count()
+            .ok_or_else(|| invalid(ObjectField::Dims, InvalidReason::VoxelCountOverflow))?;
+        if self.voxels.len() != expected {
+            return Err(invalid(
+                ObjectField::Voxels,
+                InvalidReason::LengthMismatch {
+                    expected,
+                    actual: self.voxels.len(),
+                },
+            ));
+        }
+        validate_palette_indices(&self.id, &self.voxels, palette_len)
+    }
+
+    /// Overwrites an `extent`-sized box starting at `origin` with `data`, laid out x-fastest.
+    pub fn set_region(
+        &mut self,
+        origin: [u32; 3],
+        extent: [u32; 3],
+        data: &[u8],
+    ) -> Result<(), SceneError> {
+        validate_region(&self.id, self.dims, origin, extent, data)?;
+
+        let [nx, ny, _] = self.dims.map(|d| d as usize);
+        let [ox, oy, oz] = origin.map(|o| o as usize);
+        let [ex, ey, ez] = extent.map(|e| e as usize);
+        for z in 0..ez {
+            for y in 0..ey {
+                let src = ex * (y + ey * z);
+                let dst = ox + nx * ((oy + y) + ny * (oz + z));
+                self.voxels[dst..dst + ex].copy_from_slice(&data[src..src + ex]);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Scene {
+    /// Checks that the scene can be uploaded as-is, returning the first problem found.
+    pub fn validate(&self) -> Result<(), SceneError> {
+        validate_palette(&self.palette)?;
+
+        let mut ids = HashSet::with_capacity(self.objects.len());
+        for obj in &self.objects {
+            if !ids.insert(obj.id.as_str()) {
+                return Err(SceneError::InvalidObject {
+                    id: obj.id.clone(),
+                    field: ObjectField::Id,
+                    reason: InvalidReason::Duplicate,
+                });
+            }
+            obj.validate(self.palette.len())?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,9 @@
+
+// Appended to `shader.wgsl` when the device supports `SHADER_EARLY_DEPTH_TEST`. The pipeline culls
+// back faces and the renderer only draws objects wholly past the near plane with it, so every
+// fragment comes from a front face the hit lies behind: the written depth never decreases and
+// early-Z stays valid. Objects the camera is inside of show only back faces and use `fs_main`.
+@fragment @early_depth_test(greater_equal)
+fn fs_main_conservative(in: VertexOutput) -> GBuffer {
+    return gbuffer(in);
+}
@@ -0,0 +1,137 @@
+// Voxel traversal shared by the G-buffer and lighting passes, which are built with this file
+// appended. Each includer defines `voxel_at(obj: u32, coord: vec3<u32>) -> u32`, returning the
+// palette index (or just non-zero occupancy) of a voxel of object `obj`, and
+// `occupied(obj: u32, level: u32, cell: vec3<u32>) -> bool`, reading bit `occupancy_bit` of the
+// object's occupancy pyramid.
+
+struct Hit {
+    index:  u32,       // 0 on a miss
+    voxel:  vec3<u32>,
+    t:      f32,       // ray parameter where the ray enters the voxel
+    normal: vec3<f32>, // object-space normal of the face it enters through
+    steps:  u32,       // voxels and empty cells visited
+};
+
+// Pyramid levels above the voxels, with cells of 2, 4 and 8 voxels; see `occupancy.rs`.
+const COARSEST_LEVEL: u32 = 3u;
+
+// Bit of `cell` of `level` in the occupancy pyramid over a `dims` grid, counted from the
+// pyramid's first word; levels start on a new word each.
+fn occupancy_bit(dims: vec3<u32>, level: u32, cell: vec3<u32>) -> u32 {
+    var offset = 0u;
+    for (var l = 0u; l < level; l = l + 1u) {
+        let n = (dims + vec3<u32>((1u << l) - 1u)) >> vec3<u32>(l);
+        offset += (n.x * n.y * n.z + 31u) / 32u;
+    }
+    let n = (dims + vec3<u32>((1u << level) - 1u)) >> vec3<u32>(level);
+    return offset * 32u + cell.x + n.x * (cell.y + n.y * cell.z);
+}
+
+// Coarsest level whose cell around `coord` is empty, or 0 if even the 2^3 cell is occupied.
+fn empty_level(obj: u32, coord: vec3<u32>) -> u32 {
+    for (var level = COARSEST_LEVEL; level > 0u; level = level - 1u) {
+        if !occupied(obj, level, coord >> vec3<u32>(level)) {
+            return level;
+        }
+    }
+    return 0u;
+}
+
+// Amanatides-Woo DDA of `origin + t * dir` through the unit cube [-0.5, 0.5]^3 holding `dims`
+// voxels, for t in [0, t_limit]. `dir` need not be normalized; `t` is in its units.
+//
+// Empty cells of the occupancy pyramid are crossed in one step: the ray jumps to the first voxel
+// past the cell's far side, entered through the same face as a voxel-by-voxel walk would.
+fn march(obj: u32, dims: vec3<u32>, origin: vec3<f32>, dir: vec3<f32>, t_limit: f32) -> Hit {
+    var hit = Hit(0u, vec3<u32>(0u), 0.0, vec3<f32>(0.0), 0u);
+
+    // Nudge zero components so axis-aligned rays still traverse.
+    let safe_dir = select(dir, vec3<f32>(1e-8), abs(dir) < vec3<f32>(1e-8));
+    let inv_dir = 1.0 / safe_dir;
+    let t0 = (vec3<f32>(-0.5) - origin) * inv_dir;
+    let t1 = (vec3<f32>(0.5) - origin) * inv_dir;
+    let t_near = min(t0, t1);
+    let t_far = max(t0, t1);
+    let t_entry = max(max(t_near.x, t_near.y), t_near.z);
+    let t_exit = min(min(min(t_far.x, t_far.y), t_far.z), t_limit);
+    var t = max(t_entry, 0.0);
+    if t > t_exit {
+        return hit;
+    }
+
+    // In voxel coordinates the ray keeps its parameter: p = (origin + t * dir + 0.5) * dims.
+    let dims_i = vec3<i32>(dims);
+    let dims_f = vec3<f32>(dims);
+    let start = (origin + t * safe_dir + vec3<f32>(0.5)) * dims_f;
+    var voxel = clamp(vec3<i32>(floor(start)), vec3<i32>(0), dims_i - vec3<i32>(1));
+    let positive = safe_dir > vec3<f32>(0.0);
+    let step = select(vec3<i32>(-1), vec3<i32>(1), positive);
+    let far_side = select(vec3<i32>(0), vec3<i32>(1), positive);
+    let inv_dir_voxel = inv_dir / dims_f;
+    // Ray parameter where the ray crosses voxel-space planes, measured from `start`.
+    let t_start = t;
+    var t_max = t_start + (vec3<f32>(voxel + far_side) - start) * inv_dir_voxel;
+    let t_delta = abs(inv_dir_voxel);
+
+    var axis = 2;
+    if t_near.x >= t_near.y && t_near.x >= t_near.z {
+        axis = 0;
+    } else if t_near.y >= t_near.z {
+        axis = 1;
+    }
+
+    // Every step moves at least one voxel further along one axis and never back along the
+    // others, so no ray takes more steps than this.
+    let max_steps = dims.x + dims.y + dims.z;
+    for (var i = 0u; i < max_steps; i = i + 1u) {
+        hit.steps = i + 1u;
+        let coord = vec3<u32>(voxel);
+        let level = empty_level(obj, coord);
+        if level == 0u {
+            let index = voxel_at(obj, coord);
+            if index != 0u {
+                hit.index = index;
+                hit.voxel = coord;
+                hit.t = t;
+                hit.normal[axis] = -f32(step[axis]);
+                break;
+            }
+
+            if t_max.x < t_max.y && t_max.x < t_max.z {
+                axis = 0;
+            } else if t_max.y < t_max.z {
+                axis = 1;
+            } else {
+                axis = 2;
+            }
+            t = t_max[axis];
+            voxel[axis] += step[axis];
+            t_max[axis] += t_delta[axis];
+        } else {
+            let size = i32(1u << level);
+            let cell_min = (voxel >> vec3<u32>(level)) << vec3<u32>(level);
+            let t_cell = t_start + (vec3<f32>(cell_min + far_side * size) - start) * inv_dir_voxel;
+            if t_cell.x < t_cell.y && t_cell.x < t_cell.z {
+                axis = 0;
+            } else if t_cell.y < t_cell.z {
+                axis = 1;
+            } else {
+                axis = 2;
+            }
+            t = t_cell[axis];
+            // The ray leaves the cell through `axis`; along the others it stays within the cell.
+            let p = start + (t - t_start) * safe_dir * dims_f;
+            voxel = clamp(
+                vec3<i32>(floor(p)),
+                cell_min,
+                min(cell_min + vec3<i32>(size - 1), dims_i - vec3<i32>(1))
+            );
+            voxel[axis] = select(cell_min[axis] - 1, cell_min[axis] + size, positive[axis]);
+            t_max = t_start + (vec3<f32>(voxel + far_side) - start) * inv_dir_voxel;
+        }
+        if t > t_exit || voxel[axis] < 0 || voxel[axis] >= dims_i[axis] {
+            break;
+        }
+    }
+    return hit;
+}
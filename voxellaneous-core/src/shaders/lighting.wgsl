@@ -0,0 +1,273 @@
+struct VSOut {
+    @builtin(position) Position: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vi: u32) -> VSOut {
+    var corners = array<vec2<f32>,3>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>( 3.0, -1.0),
+        vec2<f32>(-1.0,  3.0)
+    );
+    var out: VSOut;
+    out.Position = vec4<f32>(corners[vi], 0.0, 1.0);
+    return out;
+}
+
+struct LightingUniforms {
+    sun_direction:  vec3<f32>, // unit, direction the light travels
+    sun_intensity:  f32,
+    sun_color:      vec3<f32>,
+    shadow_mode:    u32,       // 0 = off, 1 = hard, 2 = soft
+    ambient:        vec3<f32>,
+    shadow_bias:    f32,
+    shadow_samples: u32,
+    light_radius:   f32,
+    sun_angle:      f32,
+    _padding:       f32,
+};
+
+struct FrameUniforms {
+    inv_vp_matrix:   mat4x4<f32>,
+    tiles_x:         u32,
+    object_count:    u32,
+    _padding0:       u32,
+    _padding1:       u32,
+};
+
+struct Light {
+    position:  vec3<f32>,
+    range:     f32,
+    color:     vec3<f32>, // premultiplied by intensity
+    kind:      u32,       // 0 = point, 1 = spot
+    direction: vec3<f32>, // unit spot axis
+    cos_outer: f32,
+    cos_inner: f32,
+    _padding0: f32,
+    _padding1: f32,
+    _padding2: f32,
+};
+
+struct ShadowObject {
+    inv_model_matrix: mat4x4<f32>,
+    dims:             vec3<u32>,
+    offset:           u32,       // first word of the object's pyramid in `occupancy`
+};
+
+// A node of the hierarchy over the objects' world bounds, see `Bvh::flat_nodes`.
+struct BvhNode {
+    min:   vec3<f32>,
+    start: u32,       // first of `count` entries of `bvh_items`, or the second child
+    max:   vec3<f32>,
+    count: u32,       // 0 for interior nodes, whose first child follows them
+};
+
+const TILE_SIZE: u32 = 16u;
+// Enough for the depth of hierarchies over billions of objects.
+const BVH_STACK_SIZE: u32 = 32u;
+const SHADOWS_SOFT: u32 = 2u;
+// Shadow rays toward the sun aim at a disc this far away.
+const SUN_DISTANCE: f32 = 10000.0;
+const PI: f32 = 3.14159265;
+
+@group(0) @binding(0) var g_albedo: texture_2d<f32>;
+@group(0) @binding(1) var g_normal: texture_2d<f32>;
+@group(0) @binding(2) var<uniform> u_lighting: LightingUniforms;
+@group(0) @binding(3) var g_depth: texture_2d<f32>;
+@group(0) @binding(4) var<uniform> u_frame: FrameUniforms;
+@group(0) @binding(5) var<storage, read> lights: array<Light>;
+@group(0) @binding(6) var<storage, read> tile_ranges: array<vec2<u32>>; // offset, count
+@group(0) @binding(7) var<storage, read> tile_lights: array<u32>;
+@group(0) @binding(8) var<storage, read> shadow_objects: array<ShadowObject>;
+@group(0) @binding(9) var<storage, read> occupancy: array<u32>; // pyramids, see `occupancy.rs`
+@group(0) @binding(10) var g_ao: texture_2d<f32>;
+@group(0) @binding(11) var<storage, read> bvh_nodes: array<BvhNode>;
+@group(0) @binding(12) var<storage, read> bvh_items: array<u32>;
+
+// Palette colors are stored sRGB-encoded; lighting happens in linear space.
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3<f32>(2.4)), c / 12.92, c <= vec3<f32>(0.04045));
+}
+
+fn linear_to_srgb(c: vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3<f32>(0.0031308));
+}
+
+// Windowed inverse-square falloff reaching zero at the light's range.
+fn attenuation(distance: f32, range: f32) -> f32 {
+    let window = clamp(1.0 - pow(distance / range, 4.0), 0.0, 1.0);
+    return window * window / (distance * distance + 1.0);
+}
+
+// Unprojects the hardware depth of `pixel`, as precise as the depth buffer at any distance.
+fn world_position(pixel: vec2<f32>, depth: f32) -> vec3<f32> {
+    let dims = vec2<f32>(textureDimensions(g_depth, 0));
+    let ndc = vec2<f32>(pixel.x / dims.x * 2.0 - 1.0, 1.0 - pixel.y / dims.y * 2.0);
+    let point = u_frame.inv_vp_matrix * vec4<f32>(ndc, depth, 1.0);
+    return point.xyz / point.w;
+}
+
+fn voxel_at(obj: u32, coord: vec3<u32>) -> u32 {
+    let o = shadow_objects[obj];
+    let i = coord.x + o.dims.x * (coord.y + o.dims.y * coord.z);
+    return (occupancy[o.offset + i / 32u] >> (i % 32u)) & 1u;
+}
+
+fn occupied(obj: u32, level: u32, cell: vec3<u32>) -> bool {
+    let o = shadow_objects[obj];
+    let bit = occupancy_bit(o.dims, level, cell);
+    return ((occupancy[o.offset + bit / 32u] >> (bit % 32u)) & 1u) != 0u;
+}
+
+// Whether the ray crosses `node`'s box before `distance`; the slab test of `Aabb::ray_span`.
+fn ray_enters(node: BvhNode, origin: vec3<f32>, inv_dir: vec3<f32>, distance: f32) -> bool {
+    let t0 = (node.min - origin) * inv_dir;
+    let t1 = (node.max - origin) * inv_dir;
+    let near = min(t0, t1);
+    let far = max(t0, t1);
+    let t_near = max(max(near.x, near.y), max(near.z, 0.0));
+    let t_far = min(min(far.x, far.y), min(far.z, distance));
+    return t_near <= t_far;
+}
+
+// Marches only the objects whose bounds the ray crosses, found through the hierarchy.
+fn occluded(origin: vec3<f32>, dir: vec3<f32>, distance: f32) -> bool {
+    if u_frame.object_count == 0u {
+        return false;
+    }
+    let inv_dir = 1.0 / select(dir, vec3<f32>(1e-8), abs(dir) < vec3<f32>(1e-8));
+    var stack: array<u32, BVH_STACK_SIZE>;
+    stack[0] = 0u;
+    var top = 1u;
+    while top > 0u {
+        top -= 1u;
+        let index = stack[top];
+        let node = bvh_nodes[index];
+        if !ray_enters(node, origin, inv_dir, distance) {
+            continue;
+        }
+        if node.count == 0u {
+            stack[top] = index + 1u;
+            stack[top + 1u] = node.start;
+            top += 2u;
+            continue;
+        }
+        for (var k = node.start; k < node.start + node.count; k = k + 1u) {
+            let i = bvh_items[k];
+            let o = shadow_objects[i];
+            let origin_os = (o.inv_model_matrix * vec4<f32>(origin, 1.0)).xyz;
+            let dir_os = (o.inv_model_matrix * vec4<f32>(dir, 0.0)).xyz;
+            if march(i, o.dims, origin_os, dir_os, distance).index != 0u {
+                return true;
+            }
+        }
+    }
+    return false;
+}
+
+fn hash(x: u32) -> u32 {
+    var h = x;
+    h ^= h >> 16u;
+    h *= 0x7feb352du;
+    h ^= h >> 15u;
+    h *= 0x846ca68bu;
+    h ^= h >> 16u;
+    return h;
+}
+
+// Two uniform numbers in [0, 1) for shadow sample `s`.
+fn random2(seed: u32, s: u32) -> vec2<f32> {
+    let a = hash(seed + s * 0x9e3779b9u);
+    let b = hash(a);
+    return vec2<f32>(f32(a >> 8u), f32(b >> 8u)) / 16777216.0;
+}
+
+// Fraction of shadow rays from `origin` that reach a light `distance` away along the unit
+// vector `to_light`, sampled over a disc of `radius` facing `origin` in soft mode.
+fn visibility(origin: vec3<f32>, to_light: vec3<f32>, distance: f32, radius: f32, seed: u32) -> f32 {
+    if u_lighting.shadow_mode == 0u {
+        return 1.0;
+    }
+    var samples = 1u;
+    if u_lighting.shadow_mode == SHADOWS_SOFT {
+        samples = max(u_lighting.shadow_samples, 1u);
+    }
+
+    // Orthonormal basis around `to_light` (Duff et al. 2017).
+    let n = to_light;
+    let sz = select(-1.0, 1.0, n.z >= 0.0);
+    let a = -1.0 / (sz + n.z);
+    let b = n.x * n.y * a;
+    let tangent = vec3<f32>(1.0 + sz * n.x * n.x * a, sz * b, -sz * n.x);
+    let bitangent = vec3<f32>(b, sz + n.y * n.y * a, -n.y);
+
+    var unoccluded = 0u;
+    for (var s = 0u; s < samples; s = s + 1u) {
+        var aim = to_light * distance;
+        if u_lighting.shadow_mode == SHADOWS_SOFT {
+            let u = random2(seed, s);
+            let r = radius * sqrt(u.x);
+            let phi = 2.0 * PI * u.y;
+            aim += r * (cos(phi) * tangent + sin(phi) * bitangent);
+        }
+        let aim_length = length(aim);
+        if !occluded(origin, aim / aim_length, aim_length) {
+            unoccluded += 1u;
+        }
+    }
+    return f32(unoccluded) / f32(samples);
+}
+
+@fragment
+fn fs_main(in: VSOut) -> @location(0) vec4<f32> {
+    let coord = vec2<i32>(in.Position.xy);
+    let albedo = textureLoad(g_albedo, coord, 0);
+    let encoded_normal = textureLoad(g_normal, coord, 0).xyz;
+
+    // Pixels without a hit keep the cleared normal of zero.
+    if all(encoded_normal == vec3<f32>(0.0)) {
+        return albedo;
+    }
+
+    let normal = encoded_normal * 2.0 - 1.0;
+    let position = world_position(in.Position.xy, textureLoad(g_depth, coord, 0).r);
+    let shadow_origin = position + normal * u_lighting.shadow_bias;
+    let pixel_seed = hash(u32(coord.x) + (u32(coord.y) << 16u));
+
+    var light = u_lighting.ambient * textureLoad(g_ao, coord, 0).r;
+    let n_dot_l = max(dot(normal, -u_lighting.sun_direction), 0.0);
+    if n_dot_l > 0.0 {
+        let sun_radius = SUN_DISTANCE * tan(u_lighting.sun_angle);
+        let sun = visibility(shadow_origin, -u_lighting.sun_direction, SUN_DISTANCE, sun_radius, pixel_seed);
+        light += u_lighting.sun_color * u_lighting.sun_intensity * n_dot_l * sun;
+    }
+
+    let tile = vec2<u32>(coord) / TILE_SIZE;
+    let tile_range = tile_ranges[tile.x + tile.y * u_frame.tiles_x];
+    for (var i = 0u; i < tile_range.y; i = i + 1u) {
+        let index = tile_lights[tile_range.x + i];
+        let l = lights[index];
+        let to_light = l.position - position;
+        let distance = length(to_light);
+        if distance >= l.range {
+            continue;
+        }
+        let dir = to_light / max(distance, 1e-6);
+        var cone = 1.0;
+        if l.kind == 1u {
+            cone = smoothstep(l.cos_outer, l.cos_inner, dot(-dir, l.direction));
+        }
+        let n_dot_l = max(dot(normal, dir), 0.0);
+        if n_dot_l * cone == 0.0 {
+            continue;
+        }
+        // Shadow rays start off the surface, so aim them from there.
+        let to_light_biased = l.position - shadow_origin;
+        let light_distance = length(to_light_biased);
+        let seed = hash(pixel_seed + index + 1u);
+        let shadow = visibility(shadow_origin, to_light_biased / light_distance, light_distance, u_lighting.light_radius, seed);
+        light += l.color * n_dot_l * attenuation(distance, l.range) * cone * shadow;
+    }
+    let color = srgb_to_linear(albedo.rgb) * light;
+    return vec4<f32>(linear_to_srgb(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0))), albedo.a);
+}
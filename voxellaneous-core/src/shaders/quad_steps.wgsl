@@ -0,0 +1,44 @@
+struct VSOut {
+    @builtin(position) Position: vec4<f32>,
+    @location(0)         uv:       vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vi: u32) -> VSOut {
+    var corners = array<vec2<f32>,3>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>( 3.0, -1.0),
+        vec2<f32>(-1.0,  3.0)
+    );
+    var out: VSOut;
+    out.Position = vec4<f32>(corners[vi], 0.0, 1.0);
+    out.uv       = corners[vi] * 0.5 + vec2<f32>(0.5);
+    return out;
+}
+
+@group(0) @binding(0) var u_tex: texture_2d<u32>;
+@group(0) @binding(1) var u_samp: sampler;
+
+// Steps at which the heat map saturates.
+const STEPS_RANGE: f32 = 128.0;
+
+@fragment
+fn fs_main(in: VSOut) -> @location(0) vec4<f32> {
+    let dims = textureDimensions(u_tex, 0);
+    let coord = vec2<i32>(
+        i32(in.uv.x * f32(dims.x)),
+        i32((1.0 - in.uv.y) * f32(dims.y))
+    );
+    let steps = textureLoad(u_tex, coord, 0).r;
+    if steps == 0u {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    // Blue through green and yellow to red.
+    let t = clamp(f32(steps) / STEPS_RANGE, 0.0, 1.0);
+    let color = vec3<f32>(
+        smoothstep(0.4, 0.7, t),
+        smoothstep(0.0, 0.35, t) - smoothstep(0.7, 1.0, t),
+        1.0 - smoothstep(0.2, 0.45, t)
+    );
+    return vec4<f32>(color, 1.0);
+}
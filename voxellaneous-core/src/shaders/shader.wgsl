@@ -1,16 +1,20 @@
 struct VertexInput {
     @location(0) position: vec3<f32>,  // in object space [-0.5,0.5]^3
+    @location(1) object:   u32,        // per instance, index into `objects`
 };
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) obj_pos: vec3<f32>,   // object‑space position
+    @location(1) @interpolate(flat) object: u32,
 };
 
 struct PerFrameUniforms {
     vp_matrix:  mat4x4<f32>,
     cam_pos_ws: vec3<f32>,
-    _padding:   f32,
+    ao_mode:    u32, // 0 = off, 1 = computed here, 2 = read from `atlas_ao`
+    keep_misses: u32, // 1 while steps are shown, so rays that miss write theirs too
+    voxel_storage: u32, // 0 = brick atlas, 1 = `octree`
 };
 @group(1) @binding(0) var<uniform> u_frame: PerFrameUniforms;
 
@@ -19,124 +23,305 @@ struct StaticUniforms {
 };
 @group(0) @binding(0) var<uniform> u_static: StaticUniforms;
 
-struct PerDrawUniforms {
+struct Object {
     model_matrix:     mat4x4<f32>,
     inv_model_matrix: mat4x4<f32>,
+    dims:             vec3<u32>,
+    pick_id:          u32, // identifies the object in the pick target, never 0
+    brick_offset:     u32, // first entry of the object's table in `bricks`
+    occupancy_offset: u32, // first word of the object's pyramid in `occupancy`
+    octree_offset:    u32, // first word of the object's nodes in `octree`
+    octree_depth:     u32, // levels below the root, which spans 2^depth voxels
 };
-@group(2) @binding(1) var<uniform> u_draw: PerDrawUniforms;
+@group(2) @binding(0) var<storage, read> objects: array<Object>;
+// Atlas slot of each 8^3 brick of every object, or EMPTY_BRICK; see `bricks.rs`.
+@group(2) @binding(1) var<storage, read> bricks: array<u32>;
+// Palette indices of the bricks, in layers of bricks stacked along z.
+@group(2) @binding(2) var atlas: texture_3d<u32>;
+// Corner occlusion levels baked on the CPU in precomputed mode: six bytes per atlas voxel in
+// brick order, one byte per face holding 2 bits per corner; see `ao.rs`. Other modes bind a
+// placeholder.
+@group(2) @binding(3) var<storage, read> atlas_ao: array<u32>;
+// Occupancy pyramid of every object, one bit per cell; see `occupancy.rs`.
+@group(2) @binding(4) var<storage, read> occupancy: array<u32>;
+// Node words of every object's sparse voxel octree; see `octree.rs`.
+@group(2) @binding(5) var<storage, read> octree: array<u32>;
 
-@group(2) @binding(0) var voxel_texture: texture_3d<u32>;
+const BRICK_SIZE: u32 = 8u;
+const EMPTY_BRICK: u32 = 0xffffffffu;
+const EMPTY_NODE: u32 = 0u;
+const LEAF_NODE: u32 = 0x80000000u;
 
-// G‑buffer outputs: albedo, normal, linear depth
+// G‑buffer outputs: albedo, normal, linear depth, ambient occlusion, what was hit, traversal
+// steps and the hit's depth
 struct GBuffer {
     @location(0) albedo:    vec4<f32>, // Rgba8Unorm
     @location(1) normal:    vec4<f32>, // Rgba8Unorm encoded
     @location(2) linear_z:  u32,       // R16Uint
+    @location(3) ao:        f32,       // R8Unorm
+    @location(4) pick:      vec2<u32>, // Rg32Uint, see `pick_texel`
+    @location(5) steps:     u32,       // R16Uint
+    @builtin(frag_depth) depth: f32,
 };
 
 @vertex
 fn vs_main(in: VertexInput) -> VertexOutput {
     var out: VertexOutput;
-    let ws4 = u_draw.model_matrix * vec4<f32>(in.position, 1.0);
+    let ws4 = objects[in.object].model_matrix * vec4<f32>(in.position, 1.0);
     out.position = u_frame.vp_matrix * ws4;
     out.obj_pos  = in.position;
+    out.object   = in.object;
     return out;
 }
 
-@fragment
-fn fs_main(in: VertexOutput) -> GBuffer {
-    let cam_os = (u_draw.inv_model_matrix * vec4<f32>(u_frame.cam_pos_ws, 1.0)).xyz;
-    let dir_os = normalize(in.obj_pos - cam_os);
+fn voxel_index(obj: u32, coord: vec3<u32>) -> u32 {
+    let dims = objects[obj].dims;
+    return coord.x + dims.x * (coord.y + dims.y * coord.z);
+}
 
-    let dims = vec3<u32>(textureDimensions(voxel_texture, 0));
-    let dims_f = vec3<f32>(dims);
-    let inv_dir = sign(dir_os) / max(abs(dir_os), vec3<f32>(1e-4));
+// Atlas slot of the brick holding `coord`.
+fn brick_at(obj: u32, coord: vec3<u32>) -> u32 {
+    let bricks_dims = (objects[obj].dims + vec3<u32>(BRICK_SIZE - 1u)) / BRICK_SIZE;
+    let b = coord / BRICK_SIZE;
+    return bricks[objects[obj].brick_offset + b.x + bricks_dims.x * (b.y + bricks_dims.y * b.z)];
+}
+
+fn voxel_at(obj: u32, coord: vec3<u32>) -> u32 {
+    let slot = brick_at(obj, coord);
+    if slot == EMPTY_BRICK {
+        return 0u;
+    }
+    let row = textureDimensions(atlas).x / BRICK_SIZE;
+    let origin = vec3<u32>(slot % row, slot / row % row, slot / (row * row)) * BRICK_SIZE;
+    return textureLoad(atlas, origin + coord % BRICK_SIZE, 0).r;
+}
+
+fn occupied(obj: u32, level: u32, cell: vec3<u32>) -> bool {
+    let bit = occupancy_bit(objects[obj].dims, level, cell);
+    return ((occupancy[objects[obj].occupancy_offset + bit / 32u] >> (bit % 32u)) & 1u) != 0u;
+}
+
+fn solid(obj: u32, coord: vec3<i32>) -> bool {
+    let dims = vec3<i32>(objects[obj].dims);
+    if any(coord < vec3<i32>(0)) || any(coord >= dims) {
+        return false;
+    }
+    return voxel_at(obj, vec3<u32>(coord)) != 0u;
+}
+
+fn corner_ao(side1: bool, side2: bool, corner: bool) -> f32 {
+    if side1 && side2 {
+        return 0.0;
+    }
+    return 3.0 - f32(side1) - f32(side2) - f32(corner);
+}
+
+// Occlusion levels of the four corners of the face of `voxel` with outward normal `sign` along
+// `axis`, from the voxels in front of it; same corner order as `face_ao` in `ao.rs`.
+fn face_ao(obj: u32, voxel: vec3<i32>, axis: i32, sign: i32) -> vec4<f32> {
+    var front = voxel;
+    front[axis] += sign;
+    var levels = vec4<f32>(0.0);
+    for (var c = 0; c < 4; c = c + 1) {
+        var du = vec3<i32>(0);
+        var dv = vec3<i32>(0);
+        du[(axis + 1) % 3] = select(-1, 1, (c & 1) != 0);
+        dv[(axis + 2) % 3] = select(-1, 1, (c & 2) != 0);
+        levels[c] = corner_ao(
+            solid(obj, front + du),
+            solid(obj, front + dv),
+            solid(obj, front + du + dv)
+        );
+    }
+    return levels;
+}
+
+// Levels baked into `atlas_ao`, or computed like in mode 1 for bricks past its end.
+fn baked_face_ao(obj: u32, voxel: vec3<u32>, axis: i32, sign: i32) -> vec4<f32> {
+    // Hits are solid, so their brick has a slot.
+    let local = voxel % BRICK_SIZE;
+    let slot = brick_at(obj, voxel);
+    let entry = slot * BRICK_SIZE * BRICK_SIZE * BRICK_SIZE
+        + local.x + BRICK_SIZE * (local.y + BRICK_SIZE * local.z);
+    let byte = entry * 6u + u32(axis * 2 + select(0, 1, sign > 0));
+    if byte / 4u >= arrayLength(&atlas_ao) {
+        return face_ao(obj, vec3<i32>(voxel), axis, sign);
+    }
+    let face = (atlas_ao[byte / 4u] >> (byte % 4u * 8u)) & 0xffu;
+    var levels = vec4<f32>(0.0);
+    for (var c = 0u; c < 4u; c = c + 1u) {
+        levels[c] = f32((face >> (c * 2u)) & 3u);
+    }
+    return levels;
+}
+
+// Ambient occlusion at `hit_pos_os`, interpolated between the corners of the hit face.
+fn ambient_occlusion(obj: u32, hit: Hit, hit_pos_os: vec3<f32>) -> f32 {
+    if u_frame.ao_mode == 0u {
+        return 1.0;
+    }
+    var axis = 2;
+    if hit.normal.x != 0.0 {
+        axis = 0;
+    } else if hit.normal.y != 0.0 {
+        axis = 1;
+    }
+    let sign = i32(hit.normal[axis]);
 
-    let bounds_min = vec3<f32>(-0.5);
-    let bounds_max = vec3<f32>(0.5);
-    let tmin = (bounds_min - cam_os) * inv_dir;
-    let tmax = (bounds_max - cam_os) * inv_dir;
+    var levels: vec4<f32>;
+    if u_frame.ao_mode == 1u {
+        levels = face_ao(obj, vec3<i32>(hit.voxel), axis, sign);
+    } else {
+        levels = baked_face_ao(obj, hit.voxel, axis, sign);
+    }
 
-    let t_entry = max(max(min(tmin.x, tmax.x), min(tmin.y, tmax.y)), min(tmin.z, tmax.z));
-    let t_exit  = min(min(max(tmin.x, tmax.x), max(tmin.y, tmax.y)), max(tmin.z, tmax.z));
+    let p = (hit_pos_os + vec3<f32>(0.5)) * vec3<f32>(objects[obj].dims);
+    let f = clamp(p - vec3<f32>(hit.voxel), vec3<f32>(0.0), vec3<f32>(1.0));
+    let fu = f[(axis + 1) % 3];
+    let fv = f[(axis + 2) % 3];
+    return mix(mix(levels[0], levels[1], fu), mix(levels[2], levels[3], fu), fv) / 3.0;
+}
 
-    if t_exit < 0.0 || t_entry > t_exit {
-        discard;
+// Voxel index, then pick id | face << 20 | palette index << 24; see `picking.rs`.
+fn pick_texel(obj: u32, hit: Hit) -> vec2<u32> {
+    var axis = 2u;
+    if hit.normal.x != 0.0 {
+        axis = 0u;
+    } else if hit.normal.y != 0.0 {
+        axis = 1u;
     }
+    let face = axis * 2u + select(0u, 1u, hit.normal[axis] > 0.0);
+    return vec2<u32>(
+        voxel_index(obj, hit.voxel),
+        objects[obj].pick_id | (face << 20u) | (hit.index << 24u)
+    );
+}
+
+// Like `march`, but through the object's octree: each step descends from the root to the node
+// holding the current voxel and, unless it is a solid leaf, crosses that node whole.
+fn march_octree(obj: u32, dims: vec3<u32>, origin: vec3<f32>, dir: vec3<f32>, t_limit: f32) -> Hit {
+    var hit = Hit(0u, vec3<u32>(0u), 0.0, vec3<f32>(0.0), 0u);
 
+    // Same setup as `march`, so both find the same hits.
+    let safe_dir = select(dir, vec3<f32>(1e-8), abs(dir) < vec3<f32>(1e-8));
+    let inv_dir = 1.0 / safe_dir;
+    let t0 = (vec3<f32>(-0.5) - origin) * inv_dir;
+    let t1 = (vec3<f32>(0.5) - origin) * inv_dir;
+    let t_near = min(t0, t1);
+    let t_far = max(t0, t1);
+    let t_entry = max(max(t_near.x, t_near.y), t_near.z);
+    let t_exit = min(min(min(t_far.x, t_far.y), t_far.z), t_limit);
     var t = max(t_entry, 0.0);
-    let ray_start = cam_os + t * dir_os + vec3<f32>(0.5);
-    let offset = dir_os * (1.0 / dims_f);
-    let ray_voxel = ray_start * dims_f + offset;
-    var voxel = vec3<i32>(floor(ray_voxel));
-    let step = vec3<i32>(select(vec3<f32>(-1.0), vec3<f32>(1.0), dir_os > vec3<f32>(0.0)));
-    let next_boundary = select(vec3<f32>(ceil(ray_voxel)), floor(ray_voxel), dir_os > vec3<f32>(0.0));
-    var t_max = (next_boundary - ray_voxel) * inv_dir;
-    let t_delta = abs(inv_dir);
-
-    var hit_idx = 0u;
-    var hit_voxel = vec3<u32>(0u);
-    var hit_t = 0.0;
-    var hit_normal = vec3<f32>(0.0);
-    var last_axis = 0;
-
-    let MAX_STEPS = 256u;
-    for (var i = 0u; i < MAX_STEPS; i = i + 1u) {
-        if any(voxel < vec3<i32>(0)) || any(voxel >= vec3<i32>(dims)) {
-            break;
-        }
+    if t > t_exit {
+        return hit;
+    }
+
+    let dims_i = vec3<i32>(dims);
+    let dims_f = vec3<f32>(dims);
+    let start = (origin + t * safe_dir + vec3<f32>(0.5)) * dims_f;
+    var voxel = clamp(vec3<i32>(floor(start)), vec3<i32>(0), dims_i - vec3<i32>(1));
+    let positive = safe_dir > vec3<f32>(0.0);
+    let step = select(vec3<i32>(-1), vec3<i32>(1), positive);
+    let far_side = select(vec3<i32>(0), vec3<i32>(1), positive);
+    let inv_dir_voxel = inv_dir / dims_f;
+    let t_start = t;
+
+    var axis = 2;
+    if t_near.x >= t_near.y && t_near.x >= t_near.z {
+        axis = 0;
+    } else if t_near.y >= t_near.z {
+        axis = 1;
+    }
 
+    let base = objects[obj].octree_offset;
+    // Every step leaves a node, moving at least one voxel along one axis, as in `march`.
+    let max_steps = dims.x + dims.y + dims.z;
+    for (var i = 0u; i < max_steps; i = i + 1u) {
+        hit.steps = i + 1u;
         let coord = vec3<u32>(voxel);
-        let idx = textureLoad(voxel_texture, coord, 0).r;
-
-        if idx != 0u {
-            hit_idx = idx;
-            hit_voxel = coord;
-            hit_t = t;
-            
-            if last_axis == 0 {
-                hit_normal = vec3<f32>(-f32(step.x), 0.0, 0.0);
-            } else if last_axis == 1 {
-                hit_normal = vec3<f32>(0.0, -f32(step.y), 0.0);
-            } else {
-                hit_normal = vec3<f32>(0.0, 0.0, -f32(step.z));
-            }
-            
+        var node = octree[base];
+        var size = 1u << objects[obj].octree_depth;
+        while node != EMPTY_NODE && (node & LEAF_NODE) == 0u {
+            size = size >> 1u;
+            let octant = select(vec3<u32>(0u), vec3<u32>(1u, 2u, 4u), (coord & vec3<u32>(size)) != vec3<u32>(0u));
+            node = octree[base + node + octant.x + octant.y + octant.z];
+        }
+        if node != EMPTY_NODE {
+            hit.index = node & 0xffu;
+            hit.voxel = coord;
+            hit.t = t;
+            hit.normal[axis] = -f32(step[axis]);
             break;
         }
 
-        if t_max.x < t_max.y && t_max.x < t_max.z {
-            voxel.x += step.x;
-            t += t_max.x;
-            t_max.x += t_delta.x;
-            last_axis = 0;
-        } else if t_max.y < t_max.z {
-            voxel.y += step.y;
-            t += t_max.y;
-            t_max.y += t_delta.y;
-            last_axis = 1;
+        // Leave the empty node through the face the ray reaches first.
+        let node_size = i32(size);
+        let node_min = vec3<i32>(coord & ~vec3<u32>(size - 1u));
+        let t_node = t_start + (vec3<f32>(node_min + far_side * node_size) - start) * inv_dir_voxel;
+        if t_node.x < t_node.y && t_node.x < t_node.z {
+            axis = 0;
+        } else if t_node.y < t_node.z {
+            axis = 1;
         } else {
-            voxel.z += step.z;
-            t += t_max.z;
-            t_max.z += t_delta.z;
-            last_axis = 2;
+            axis = 2;
+        }
+        t = t_node[axis];
+        let p = start + (t - t_start) * safe_dir * dims_f;
+        voxel = clamp(
+            vec3<i32>(floor(p)),
+            node_min,
+            min(node_min + vec3<i32>(node_size - 1), dims_i - vec3<i32>(1))
+        );
+        voxel[axis] = select(node_min[axis] - 1, node_min[axis] + node_size, positive[axis]);
+        if t > t_exit || voxel[axis] < 0 || voxel[axis] >= dims_i[axis] {
+            break;
         }
     }
+    return hit;
+}
 
-    if hit_idx == 0u {
-        discard;
+// Shared by `fs_main` and, where supported, `fs_main_conservative`.
+fn gbuffer(in: VertexOutput) -> GBuffer {
+    let obj = in.object;
+    let cam_os = (objects[obj].inv_model_matrix * vec4<f32>(u_frame.cam_pos_ws, 1.0)).xyz;
+    let dir_os = normalize(in.obj_pos - cam_os);
+
+    var hit: Hit;
+    if u_frame.voxel_storage == 1u {
+        hit = march_octree(obj, objects[obj].dims, cam_os, dir_os, 3.40282347e38);
+    } else {
+        hit = march(obj, objects[obj].dims, cam_os, dir_os, 3.40282347e38);
+    }
+    if hit.index == 0u {
+        if u_frame.keep_misses == 0u {
+            discard;
+        }
+        // The clear values, just in front of the clear depth so that any hit replaces them.
+        let black = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+        return GBuffer(black, black, 0u, 0.0, vec2<u32>(0u), hit.steps, 0.999999);
     }
 
-    let hit_pos_os = cam_os + hit_t * dir_os;
-    let hit_pos_ws = (u_draw.model_matrix * vec4<f32>(hit_pos_os, 1.0)).xyz;
+    let hit_pos_os = cam_os + hit.t * dir_os;
+    let hit_pos_ws = (objects[obj].model_matrix * vec4<f32>(hit_pos_os, 1.0)).xyz;
 
-    let packed = u_static.palette[hit_idx / 4u][hit_idx % 4u];
+    let packed = u_static.palette[hit.index / 4u][hit.index % 4u];
     let albedo = unpack4x8unorm(packed);
 
     let linear_z = length(hit_pos_ws - u_frame.cam_pos_ws);
+    let hit_clip = u_frame.vp_matrix * vec4<f32>(hit_pos_ws, 1.0);
     return GBuffer(
         albedo,
-        vec4<f32>(hit_normal * 0.5 + 0.5, 1.0),
-        u32(clamp(linear_z / 100.0, 0.0, 1.0) * 65535.0)
+        vec4<f32>(hit.normal * 0.5 + 0.5, 1.0),
+        u32(clamp(linear_z / 100.0, 0.0, 1.0) * 65535.0),
+        ambient_occlusion(obj, hit, hit_pos_os),
+        pick_texel(obj, hit),
+        hit.steps,
+        hit_clip.z / hit_clip.w
     );
-}
\ No newline at end of file
+}
+
+// Depth of the surface hit, so objects sort by voxels rather than by proxy cube.
+@fragment
+fn fs_main(in: VertexOutput) -> GBuffer {
+    return gbuffer(in);
+}
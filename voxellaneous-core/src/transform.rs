@@ -0,0 +1,88 @@
+use glam::{Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+/// Placement of a voxel object's unit cube in world space.
+///
+/// The inverse is always derived from the forward transform, so the two can never disagree.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Transform {
+    /// Translation, rotation quaternion `[x, y, z, w]` and per-axis scale, applied as `T * R * S`.
+    Trs {
+        translation: [f32; 3],
+        rotation: [f32; 4],
+        scale: [f32; 3],
+    },
+    /// Raw column-major model matrix, for transforms that are not expressible as TRS.
+    Matrix([f32; 16]),
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform::Trs {
+        translation: [0.0; 3],
+        rotation: [0.0, 0.0, 0.0, 1.0],
+        scale: [1.0; 3],
+    };
+
+    pub fn from_translation_scale(translation: [f32; 3], scale: [f32; 3]) -> Self {
+        Transform::Trs {
+            translation,
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            scale,
+        }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        match *self {
+            Transform::Trs {
+                translation,
+                rotation,
+                scale,
+            } => Mat4::from_scale_rotation_translation(
+                Vec3::from(scale),
+                Quat::from_array(rotation).normalize(),
+                Vec3::from(translation),
+            ),
+            Transform::Matrix(m) => Mat4::from_cols_array(&m),
+        }
+    }
+
+    pub fn inverse_matrix(&self) -> Mat4 {
+        match *self {
+            Transform::Trs {
+                translation,
+                rotation,
+                scale,
+            } => {
+                // (T * R * S)^-1 = S^-1 * R^-1 * T^-1
+                let inv_rotation = Quat::from_array(rotation).normalize().inverse();
+                Mat4::from_scale(Vec3::from(scale).recip())
+                    * Mat4::from_quat(inv_rotation)
+                    * Mat4::from_translation(-Vec3::from(translation))
+            }
+            Transform::Matrix(m) => Mat4::from_cols_array(&m).inverse(),
+        }
+    }
+
+    /// Decomposes into translation, rotation and scale; shear in a raw matrix is lost.
+    pub fn to_trs(&self) -> Transform {
+        match self {
+            Transform::Trs { .. } => *self,
+            Transform::Matrix(m) => {
+                let (scale, rotation, translation) =
+                    Mat4::from_cols_array(m).to_scale_rotation_translation();
+                Transform::Trs {
+                    translation: translation.to_array(),
+                    rotation: rotation.to_array(),
+                    scale: scale.to_array(),
+                }
+            }
+        }
+    }
+}
@@ -1,8 +1,10 @@
+#[cfg(feature = "web")]
 use wasm_bindgen::JsValue;
 
 use crate::primitives::RGBA;
 
 // e should accept any type which has to_string method
+#[cfg(feature = "web")]
 pub fn map_wgpu_err(e: impl std::fmt::Display) -> JsValue {
     JsValue::from_str(&e.to_string())
 }
@@ -0,0 +1,91 @@
+//! MagicaVoxel `.vox` interchange.
+//!
+//! MagicaVoxel is Z-up while the renderer is Y-up; the basis change is folded into each
+//! object's transform so voxel data keeps the file's axis order.
+
+mod reader;
+mod writer;
+
+use std::fmt;
+
+use glam::{Mat4, Vec3, Vec4};
+
+use crate::primitives::RGBA;
+use crate::scene::SceneError;
+
+pub use reader::read_vox;
+pub use writer::write_vox;
+
+const MAGIC: &[u8; 4] = b"VOX ";
+
+/// Largest model extent MagicaVoxel supports along any axis.
+pub const MAX_MODEL_SIZE: u32 = 256;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum VoxError {
+    InvalidMagic,
+    UnexpectedEof { chunk: String },
+    MalformedChunk { chunk: String, reason: String },
+    InvalidSceneGraph { node_id: i32, reason: String },
+    InvalidScene(SceneError),
+    UnsupportedTransform { id: String },
+}
+
+impl fmt::Display for VoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VoxError::InvalidMagic => write!(f, "not a MagicaVoxel file"),
+            VoxError::UnexpectedEof { chunk } => {
+                write!(f, "unexpected end of data in {chunk} chunk")
+            }
+            VoxError::MalformedChunk { chunk, reason } => {
+                write!(f, "malformed {chunk} chunk: {reason}")
+            }
+            VoxError::InvalidSceneGraph { node_id, reason } => {
+                write!(f, "invalid scene graph node {node_id}: {reason}")
+            }
+            VoxError::InvalidScene(e) => write!(f, "cannot export invalid scene: {e}"),
+            VoxError::UnsupportedTransform { id } => write!(
+                f,
+                "object '{id}' must have one voxel per world unit and an axis-aligned rotation"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VoxError {}
+
+/// Maps MagicaVoxel's Z-up space onto the renderer's Y-up space.
+fn z_up_to_y_up() -> Mat4 {
+    Mat4::from_cols(Vec4::X, Vec4::NEG_Z, Vec4::Y, Vec4::W)
+}
+
+/// Places the unit cube over a model's voxels, which MagicaVoxel centers on `floor(size / 2)`.
+fn model_pivot(size: [u32; 3]) -> Mat4 {
+    let size = Vec3::from(size.map(|s| s as f32));
+    let center = size * 0.5 - (size * 0.5).floor();
+    Mat4::from_translation(center) * Mat4::from_scale(size)
+}
+
+/// MagicaVoxel's built-in palette, used when a file has no `RGBA` chunk.
+fn default_palette() -> Vec<RGBA> {
+    const STEPS: [u8; 6] = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+    const RAMP: [u8; 10] = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+
+    let mut palette = Vec::with_capacity(256);
+    palette.push(RGBA(0, 0, 0, 0));
+    for r in STEPS {
+        for g in STEPS {
+            for b in STEPS {
+                if (r, g, b) != (0, 0, 0) {
+                    palette.push(RGBA(r, g, b, 255));
+                }
+            }
+        }
+    }
+    palette.extend(RAMP.map(|v| RGBA(v, 0, 0, 255)));
+    palette.extend(RAMP.map(|v| RGBA(0, v, 0, 255)));
+    palette.extend(RAMP.map(|v| RGBA(0, 0, v, 255)));
+    palette.extend(RAMP.map(|v| RGBA(v, v, v, 255)));
+    palette
+}
@@ -0,0 +1,403 @@
+use std::collections::{HashMap, HashSet};
+
+use glam::{Mat3, Mat4, Vec3};
+
+use super::{default_palette, model_pivot, z_up_to_y_up, VoxError, MAGIC, MAX_MODEL_SIZE};
+use crate::primitives::RGBA;
+use crate::scene::{Scene, VoxelObject};
+use crate::transform::Transform;
+
+type Dict = HashMap<String, String>;
+
+struct Model {
+    size: [u32; 3],
+    voxels: Vec<u8>,
+}
+
+enum Node {
+    Transform {
+        name: Option<String>,
+        child: i32,
+        matrix: Mat4,
+    },
+    Group {
+        children: Vec<i32>,
+    },
+    Shape {
+        models: Vec<i32>,
+    },
+}
+
+struct Chunk<'a> {
+    id: [u8; 4],
+    content: &'a [u8],
+    children: &'a [u8],
+}
+
+/// Bounds-checked little-endian reader over the bytes of one chunk.
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    chunk: [u8; 4],
+}
+
+impl<'a> Cursor<'a> {
+    fn new(bytes: &'a [u8], chunk: [u8; 4]) -> Self {
+        Cursor {
+            bytes,
+            pos: 0,
+            chunk,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], VoxError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| VoxError::UnexpectedEof {
+                chunk: chunk_name(&self.chunk),
+            })?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn read_i32(&mut self) -> Result<i32, VoxError> {
+        let bytes = self.take(4)?;
+        Ok(i32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// Reads a non-negative count or length.
+    fn read_len(&mut self) -> Result<usize, VoxError> {
+        let len = self.read_i32()?;
+        usize::try_from(len).map_err(|_| malformed(&self.chunk, format!("negative length {len}")))
+    }
+
+    fn read_string(&mut self) -> Result<String, VoxError> {
+        let len = self.read_len()?;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+
+    fn read_dict(&mut self) -> Result<Dict, VoxError> {
+        let count = self.read_len()?;
+        let mut dict = Dict::new();
+        for _ in 0..count {
+            let key = self.read_string()?;
+            let value = self.read_string()?;
+            dict.insert(key, value);
+        }
+        Ok(dict)
+    }
+
+    fn read_chunk(&mut self) -> Result<Chunk<'a>, VoxError> {
+        let id: [u8; 4] = self.take(4)?.try_into().unwrap();
+        let content_len = self.read_len()?;
+        let children_len = self.read_len()?;
+        // Report truncation against the chunk being read, not its parent.
+        let mut body = Cursor::new(&self.bytes[self.pos..], id);
+        let content = body.take(content_len)?;
+        let children = body.take(children_len)?;
+        self.pos += body.pos;
+        Ok(Chunk {
+            id,
+            content,
+            children,
+        })
+    }
+}
+
+fn chunk_name(id: &[u8; 4]) -> String {
+    String::from_utf8_lossy(id).into_owned()
+}
+
+fn malformed(id: &[u8; 4], reason: impl Into<String>) -> VoxError {
+    VoxError::MalformedChunk {
+        chunk: chunk_name(id),
+        reason: reason.into(),
+    }
+}
+
+/// Parses a MagicaVoxel `.vox` file into a [`Scene`].
+///
+/// Each shape instance in the scene graph becomes one [`VoxelObject`]; files without a scene
+/// graph place every model at the origin. The palette keeps MagicaVoxel's numbering, so index
+/// 0 stays empty.
+pub fn read_vox(bytes: &[u8]) -> Result<Scene, VoxError> {
+    if bytes.len() < 8 || &bytes[..4] != MAGIC {
+        return Err(VoxError::InvalidMagic);
+    }
+
+    let mut file = Cursor::new(&bytes[8..], *b"MAIN");
+    let main = file.read_chunk()?;
+    if &main.id != b"MAIN" {
+        return Err(malformed(&main.id, "expected MAIN chunk"));
+    }
+
+    let mut models = Vec::new();
+    let mut pending_size = None;
+    let mut palette = None;
+    let mut nodes = HashMap::new();
+
+    let mut children = Cursor::new(main.children, main.id);
+    while !children.is_empty() {
+        let chunk = children.read_chunk()?;
+        match &chunk.id {
+            b"SIZE" => pending_size = Some(parse_size(&chunk)?),
+            b"XYZI" => {
+                let size = pending_size
+                    .take()
+                    .ok_or_else(|| malformed(&chunk.id, "not preceded by a SIZE chunk"))?;
+                models.push(parse_xyzi(&chunk, size)?);
+            }
+            b"RGBA" => palette = Some(parse_rgba(&chunk)?),
+            b"nTRN" | b"nGRP" | b"nSHP" => {
+                let (node_id, node) = parse_node(&chunk)?;
+                if nodes.insert(node_id, node).is_some() {
+                    return Err(VoxError::InvalidSceneGraph {
+                        node_id,
+                        reason: "duplicate node id".to_owned(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut builder = SceneBuilder {
+        models: &models,
+        nodes: &nodes,
+        objects: Vec::new(),
+        ids: HashSet::new(),
+        visited: HashSet::new(),
+    };
+    if nodes.is_empty() {
+        for model_id in 0..models.len() {
+            builder.push_object(model_id as i32, z_up_to_y_up(), None, 0)?;
+        }
+    } else {
+        builder.visit(0, z_up_to_y_up(), None)?;
+    }
+
+    Ok(Scene {
+        palette: palette.unwrap_or_else(default_palette),
+        objects: builder.objects,
+    })
+}
+
+fn parse_size(chunk: &Chunk) -> Result<[u32; 3], VoxError> {
+    let mut cursor = Cursor::new(chunk.content, chunk.id);
+    let mut size = [0; 3];
+    for extent in &mut size {
+        let value = cursor.read_i32()?;
+        *extent = u32::try_from(value)
+            .ok()
+            .filter(|&v| (1..=MAX_MODEL_SIZE).contains(&v))
+            .ok_or_else(|| malformed(&chunk.id, format!("model size {value} out of range")))?;
+    }
+    Ok(size)
+}
+
+fn parse_xyzi(chunk: &Chunk, size: [u32; 3]) -> Result<Model, VoxError> {
+    let mut cursor = Cursor::new(chunk.content, chunk.id);
+    let count = cursor.read_len()?;
+    let [nx, ny, nz] = size.map(|s| s as usize);
+    let mut voxels = vec![0; nx * ny * nz];
+    for _ in 0..count {
+        let [x, y, z, index]: [u8; 4] = cursor.take(4)?.try_into().unwrap();
+        let (x, y, z) = (x as usize, y as usize, z as usize);
+        if x >= nx || y >= ny || z >= nz {
+            return Err(malformed(
+                &chunk.id,
+                format!("voxel ({x}, {y}, {z}) lies outside model size {size:?}"),
+            ));
+        }
+        voxels[x + nx * (y + ny * z)] = index;
+    }
+    Ok(Model { size, voxels })
+}
+
+fn parse_rgba(chunk: &Chunk) -> Result<Vec<RGBA>, VoxError> {
+    let mut cursor = Cursor::new(chunk.content, chunk.id);
+    // Entry `i` of the chunk is color index `i + 1`; the last entry is unused.
+    let mut palette = Vec::with_capacity(256);
+    palette.push(RGBA(0, 0, 0, 0));
+    for _ in 1..256 {
+        let [r, g, b, a]: [u8; 4] = cursor.take(4)?.try_into().unwrap();
+        palette.push(RGBA(r, g, b, a));
+    }
+    Ok(palette)
+}
+
+fn parse_node(chunk: &Chunk) -> Result<(i32, Node), VoxError> {
+    let mut cursor = Cursor::new(chunk.content, chunk.id);
+    let node_id = cursor.read_i32()?;
+    let attributes = cursor.read_dict()?;
+
+    let node = match &chunk.id {
+        b"nTRN" => {
+            let child = cursor.read_i32()?;
+            let _reserved = cursor.read_i32()?;
+            let _layer = cursor.read_i32()?;
+            let frame_count = cursor.read_len()?;
+            if frame_count == 0 {
+                return Err(malformed(&chunk.id, "transform has no frames"));
+            }
+            // Only the first animation frame is imported.
+            let frame = cursor.read_dict()?;
+            for _ in 1..frame_count {
+                cursor.read_dict()?;
+            }
+            Node::Transform {
+                name: attributes.get("_name").cloned(),
+                child,
+                matrix: parse_frame(chunk, &frame)?,
+            }
+        }
+        b"nGRP" => {
+            let count = cursor.read_len()?;
+            let children = (0..count)
+                .map(|_| cursor.read_i32())
+                .collect::<Result<_, _>>()?;
+            Node::Group { children }
+        }
+        _ => {
+            let count = cursor.read_len()?;
+            let mut models = Vec::with_capacity(count.min(256));
+            for _ in 0..count {
+                models.push(cursor.read_i32()?);
+                let _model_attributes = cursor.read_dict()?;
+            }
+            Node::Shape { models }
+        }
+    };
+    Ok((node_id, node))
+}
+
+/// Builds the `T * R` matrix of an `nTRN` frame from its `_t` and `_r` attributes.
+fn parse_frame(chunk: &Chunk, frame: &Dict) -> Result<Mat4, VoxError> {
+    let translation = match frame.get("_t") {
+        Some(t) => {
+            let parts = t
+                .split_whitespace()
+                .map(|p| p.parse::<i32>().map(|v| v as f32))
+                .collect::<Result<Vec<_>, _>>()
+                .ok()
+                .filter(|parts| parts.len() == 3)
+                .ok_or_else(|| malformed(&chunk.id, format!("invalid translation '{t}'")))?;
+            Vec3::new(parts[0], parts[1], parts[2])
+        }
+        None => Vec3::ZERO,
+    };
+    let rotation = match frame.get("_r") {
+        Some(r) => {
+            let bits = r
+                .trim()
+                .parse::<u8>()
+                .map_err(|_| malformed(&chunk.id, format!("invalid rotation '{r}'")))?;
+            decode_rotation(bits)
+                .ok_or_else(|| malformed(&chunk.id, format!("invalid rotation '{r}'")))?
+        }
+        None => Mat3::IDENTITY,
+    };
+    Ok(Mat4::from_translation(translation) * Mat4::from_mat3(rotation))
+}
+
+/// Decodes MagicaVoxel's packed rotation: bits 0-1 and 2-3 give the column of the non-zero
+/// entry in rows 0 and 1, bits 4-6 flag negative entries in rows 0-2.
+fn decode_rotation(bits: u8) -> Option<Mat3> {
+    let row0 = (bits & 3) as usize;
+    let row1 = ((bits >> 2) & 3) as usize;
+    if row0 > 2 || row1 > 2 || row0 == row1 {
+        return None;
+    }
+    let row2 = 3 - row0 - row1;
+
+    let mut cols = [[0.0f32; 3]; 3];
+    for (row, col) in [row0, row1, row2].into_iter().enumerate() {
+        let negative = bits & (1 << (4 + row)) != 0;
+        cols[col][row] = if negative { -1.0 } else { 1.0 };
+    }
+    Some(Mat3::from_cols_array_2d(&cols))
+}
+
+struct SceneBuilder<'a> {
+    models: &'a [Model],
+    nodes: &'a HashMap<i32, Node>,
+    objects: Vec<VoxelObject>,
+    ids: HashSet<String>,
+    /// Nodes already walked; the scene graph is a tree, so reaching one twice means a shared
+    /// node or a cycle.
+    visited: HashSet<i32>,
+}
+
+impl SceneBuilder<'_> {
+    fn visit(&mut self, node_id: i32, parent: Mat4, name: Option<&str>) -> Result<(), VoxError> {
+        let invalid = |reason: &str| VoxError::InvalidSceneGraph {
+            node_id,
+            reason: reason.to_owned(),
+        };
+        if !self.visited.insert(node_id) {
+            return Err(invalid("node is reached more than once"));
+        }
+
+        match self.nodes.get(&node_id) {
+            Some(Node::Transform {
+                name: own_name,
+                child,
+                matrix,
+            }) => self.visit(*child, parent * *matrix, own_name.as_deref()),
+            Some(Node::Group { children }) => {
+                for &child in children {
+                    self.visit(child, parent, None)?;
+                }
+                Ok(())
+            }
+            Some(Node::Shape { models }) => {
+                for &model_id in models {
+                    self.push_object(model_id, parent, name, node_id)?;
+                }
+                Ok(())
+            }
+            None => Err(invalid("node does not exist")),
+        }
+    }
+
+    fn push_object(
+        &mut self,
+        model_id: i32,
+        parent: Mat4,
+        name: Option<&str>,
+        node_id: i32,
+    ) -> Result<(), VoxError> {
+        let model = usize::try_from(model_id)
+            .ok()
+            .and_then(|i| self.models.get(i))
+            .ok_or_else(|| VoxError::InvalidSceneGraph {
+                node_id,
+                reason: format!("model {model_id} does not exist"),
+            })?;
+
+        let base = name.map_or_else(|| format!("model_{model_id}"), str::to_owned);
+        let mut id = base.clone();
+        let mut suffix = 1;
+        while self.ids.contains(&id) {
+            id = format!("{base}_{suffix}");
+            suffix += 1;
+        }
+        self.ids.insert(id.clone());
+
+        let matrix = parent * model_pivot(model.size);
+        self.objects.push(VoxelObject {
+            id,
+            transform: Transform::Matrix(matrix.to_cols_array()),
+            dims: model.size,
+            voxels: model.voxels.clone(),
+        });
+        Ok(())
+    }
+}
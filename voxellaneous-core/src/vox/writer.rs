@@ -0,0 +1,213 @@
+use glam::{Mat3, Mat4, Vec3};
+
+use super::{model_pivot, z_up_to_y_up, VoxError, MAGIC, MAX_MODEL_SIZE};
+use crate::scene::{Scene, VoxelObject};
+
+const VERSION: i32 = 150;
+
+/// Largest deviation from a signed permutation matrix still treated as axis-aligned.
+const ROTATION_EPSILON: f32 = 1e-3;
+
+/// One `SIZE`/`XYZI` model and the `nTRN` placing it.
+struct Piece {
+    name: String,
+    size: [u32; 3],
+    voxels: Vec<[u8; 4]>,
+    rotation: u8,
+    translation: [i32; 3],
+}
+
+/// Serializes a [`Scene`] as a MagicaVoxel `.vox` file.
+///
+/// Palette indices are written unchanged: both formats reserve index 0 for empty voxels, and
+/// the `RGBA` chunk stores color index `i` at entry `i - 1`. Objects must be placed at one
+/// world unit per voxel with axis-aligned rotations; translations snap to the voxel grid.
+/// Objects larger than 256 voxels along an axis are split into several models.
+pub fn write_vox(scene: &Scene) -> Result<Vec<u8>, VoxError> {
+    scene.validate().map_err(VoxError::InvalidScene)?;
+
+    let from_y_up = z_up_to_y_up().inverse();
+    let mut pieces = Vec::new();
+    for obj in &scene.objects {
+        split_object(obj, from_y_up, &mut pieces)?;
+    }
+
+    let mut children = Vec::new();
+    for piece in &pieces {
+        let [sx, sy, sz] = piece.size.map(|s| s as i32);
+        write_chunk(&mut children, b"SIZE", &ints(&[sx, sy, sz]));
+        let mut xyzi = ints(&[piece.voxels.len() as i32]);
+        xyzi.extend(piece.voxels.concat());
+        write_chunk(&mut children, b"XYZI", &xyzi);
+    }
+
+    // Root transform -> group -> one transform + shape pair per model.
+    let shape_node = |i: usize| 2 + 2 * i as i32;
+    let mut root = ints(&[0]);
+    root.extend(dict(&[]));
+    root.extend(ints(&[1, -1, 0, 1]));
+    root.extend(dict(&[]));
+    write_chunk(&mut children, b"nTRN", &root);
+
+    let mut group = ints(&[1]);
+    group.extend(dict(&[]));
+    group.extend(ints(&[pieces.len() as i32]));
+    group.extend(ints(&(0..pieces.len()).map(shape_node).collect::<Vec<_>>()));
+    write_chunk(&mut children, b"nGRP", &group);
+
+    for (i, piece) in pieces.iter().enumerate() {
+        let node_id = shape_node(i);
+        let [tx, ty, tz] = piece.translation;
+        let mut transform = ints(&[node_id]);
+        transform.extend(dict(&[("_name", &piece.name)]));
+        transform.extend(ints(&[node_id + 1, -1, 0, 1]));
+        transform.extend(dict(&[
+            ("_t", &format!("{tx} {ty} {tz}")),
+            ("_r", &piece.rotation.to_string()),
+        ]));
+        write_chunk(&mut children, b"nTRN", &transform);
+
+        let mut shape = ints(&[node_id + 1]);
+        shape.extend(dict(&[]));
+        shape.extend(ints(&[1, i as i32]));
+        shape.extend(dict(&[]));
+        write_chunk(&mut children, b"nSHP", &shape);
+    }
+
+    let mut rgba = Vec::with_capacity(1024);
+    for index in 1..=256 {
+        let color = scene
+            .palette
+            .get(index)
+            .map_or([0; 4], |c| [c.0, c.1, c.2, c.3]);
+        rgba.extend(color);
+    }
+    write_chunk(&mut children, b"RGBA", &rgba);
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(VERSION.to_le_bytes());
+    bytes.extend(b"MAIN");
+    bytes.extend(0i32.to_le_bytes());
+    bytes.extend((children.len() as i32).to_le_bytes());
+    bytes.extend(children);
+    Ok(bytes)
+}
+
+/// Cuts an object into models of at most [`MAX_MODEL_SIZE`] per axis, skipping empty ones.
+fn split_object(
+    obj: &VoxelObject,
+    from_y_up: Mat4,
+    pieces: &mut Vec<Piece>,
+) -> Result<(), VoxError> {
+    let (rotation, translation) = node_transform(obj, from_y_up)?;
+    let rotation_bits = encode_rotation(rotation);
+
+    let dims = obj.dims;
+    let [nx, ny, _] = dims.map(|d| d as usize);
+    let counts = dims.map(|d| d.div_ceil(MAX_MODEL_SIZE));
+    let split = counts != [1, 1, 1];
+    let first = pieces.len();
+
+    for pz in 0..counts[2] {
+        for py in 0..counts[1] {
+            for px in 0..counts[0] {
+                let origin = [px, py, pz].map(|p| p * MAX_MODEL_SIZE);
+                let size = [0, 1, 2].map(|axis| (dims[axis] - origin[axis]).min(MAX_MODEL_SIZE));
+
+                let mut voxels = Vec::new();
+                for z in 0..size[2] {
+                    for y in 0..size[1] {
+                        for x in 0..size[0] {
+                            let [vx, vy, vz] =
+                                [x + origin[0], y + origin[1], z + origin[2]].map(|v| v as usize);
+                            let index = obj.voxels[vx + nx * (vy + ny * vz)];
+                            if index != 0 {
+                                voxels.push([x as u8, y as u8, z as u8, index]);
+                            }
+                        }
+                    }
+                }
+                if voxels.is_empty() && split {
+                    continue;
+                }
+
+                // Shift the piece so its own pivot lands where it sat inside the whole object.
+                let offset = Vec3::from(
+                    [0, 1, 2].map(|a| (origin[a] + size[a] / 2) as f32 - (dims[a] / 2) as f32),
+                );
+                let piece_translation = (translation + rotation * offset).round();
+                let name = if split {
+                    format!("{}_{}", obj.id, pieces.len() - first)
+                } else {
+                    obj.id.clone()
+                };
+                pieces.push(Piece {
+                    name,
+                    size,
+                    voxels,
+                    rotation: rotation_bits,
+                    translation: piece_translation.to_array().map(|t| t as i32),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Recovers the `nTRN` rotation and translation (in MagicaVoxel space) from an object transform.
+fn node_transform(obj: &VoxelObject, from_y_up: Mat4) -> Result<(Mat3, Vec3), VoxError> {
+    let node = from_y_up * obj.transform.matrix() * model_pivot(obj.dims).inverse();
+    let linear = Mat3::from_mat4(node);
+    let snapped = Mat3::from_cols(
+        linear.x_axis.round(),
+        linear.y_axis.round(),
+        linear.z_axis.round(),
+    );
+
+    let is_signed_permutation = [snapped.x_axis, snapped.y_axis, snapped.z_axis]
+        .iter()
+        .all(|col| col.abs().element_sum() == 1.0 && col.abs().max_element() == 1.0)
+        && snapped.determinant().abs() == 1.0;
+    if !is_signed_permutation || !snapped.abs_diff_eq(linear, ROTATION_EPSILON) {
+        return Err(VoxError::UnsupportedTransform { id: obj.id.clone() });
+    }
+    Ok((snapped, node.w_axis.truncate().round()))
+}
+
+/// Inverse of the reader's rotation decoding.
+fn encode_rotation(rotation: Mat3) -> u8 {
+    let rows = rotation.transpose().to_cols_array_2d();
+    let mut bits = 0u8;
+    for (row, values) in rows.iter().enumerate() {
+        let col = values.iter().position(|&v| v != 0.0).unwrap();
+        if row < 2 {
+            bits |= (col as u8) << (2 * row);
+        }
+        if values[col] < 0.0 {
+            bits |= 1 << (4 + row);
+        }
+    }
+    bits
+}
+
+fn write_chunk(out: &mut Vec<u8>, id: &[u8; 4], content: &[u8]) {
+    out.extend(id);
+    out.extend((content.len() as i32).to_le_bytes());
+    out.extend(0i32.to_le_bytes());
+    out.extend(content);
+}
+
+fn ints(values: &[i32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn dict(pairs: &[(&str, &str)]) -> Vec<u8> {
+    let mut bytes = ints(&[pairs.len() as i32]);
+    for (key, value) in pairs {
+        for s in [key, value] {
+            bytes.extend(ints(&[s.len() as i32]));
+            bytes.extend(s.as_bytes());
+        }
+    }
+    bytes
+}
@@ -0,0 +1,180 @@
+//! `wasm-bindgen` bindings exposing [`Renderer`] to JavaScript.
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::ao::AoMode;
+use crate::lighting::{Light, Lighting};
+use crate::primitives::RGBA;
+use crate::renderer::{DepthMode, PresentTarget, Renderer, VoxelStorage};
+use crate::scene::{Scene, VoxelObject};
+use crate::transform::Transform;
+use crate::utils::map_wgpu_err;
+
+#[derive(Serialize)]
+struct SerializableAdapterInfo {
+    name: String,
+    vendor: u32,
+    device: u32,
+    device_type: String,
+    driver: String,
+    driver_info: String,
+    backend: String,
+}
+
+#[wasm_bindgen(js_name = Renderer)]
+pub struct WebRenderer {
+    renderer: Renderer,
+}
+
+#[wasm_bindgen(js_class = Renderer)]
+impl WebRenderer {
+    pub async fn new(html_canvas: web_sys::HtmlCanvasElement) -> Result<WebRenderer, JsValue> {
+        // Initialize the GPU
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+
+        let canvas_width = html_canvas.width();
+        let canvas_height = html_canvas.height();
+
+        let surface_target = wgpu::SurfaceTarget::Canvas(html_canvas);
+        let surface = instance
+            .create_surface(surface_target)
+            .map_err(map_wgpu_err)?;
+
+        let renderer = Renderer::new(&instance, surface, canvas_width, canvas_height)
+            .await
+            .map_err(map_wgpu_err)?;
+
+        Ok(WebRenderer { renderer })
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), JsValue> {
+        self.renderer.resize(width, height);
+        Ok(())
+    }
+
+    pub fn render(
+        &mut self,
+        vp_matrix: &[f32],
+        view_position: &[f32],
+        present_target: usize,
+    ) -> Result<(), JsValue> {
+        let vp_matrix = vp_matrix
+            .try_into()
+            .map_err(|_| JsValue::from_str("vp_matrix must have 16 elements"))?;
+        let view_position = view_position
+            .try_into()
+            .map_err(|_| JsValue::from_str("view_position must have 3 elements"))?;
+        self.renderer
+            .render(
+                vp_matrix,
+                view_position,
+                PresentTarget::from(present_target),
+            )
+            .map_err(map_wgpu_err)
+    }
+
+    pub fn get_gpu_info(&self) -> JsValue {
+        let adapter_info = self.renderer.adapter_info();
+        let gpu_info = SerializableAdapterInfo {
+            name: adapter_info.name.clone(),
+            vendor: adapter_info.vendor,
+            device: adapter_info.device,
+            device_type: format!("{:?}", adapter_info.device_type),
+            driver: adapter_info.driver.clone(),
+            driver_info: adapter_info.driver_info.clone(),
+            backend: format!("{:?}", adapter_info.backend),
+        };
+        serde_wasm_bindgen::to_value(&gpu_info).unwrap()
+    }
+
+    pub fn get_render_stats(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.renderer.stats()).unwrap()
+    }
+
+    pub fn upload_scene(&mut self, scene: JsValue) -> Result<(), JsValue> {
+        let scene: Scene = serde_wasm_bindgen::from_value(scene)?;
+        self.renderer.upload_scene(&scene).map_err(map_wgpu_err)
+    }
+
+    pub fn add_object(&mut self, object: JsValue) -> Result<(), JsValue> {
+        let object: VoxelObject = serde_wasm_bindgen::from_value(object)?;
+        self.renderer.add_object(&object).map_err(map_wgpu_err)
+    }
+
+    pub fn remove_object(&mut self, id: &str) -> Result<(), JsValue> {
+        self.renderer.remove_object(id).map_err(map_wgpu_err)
+    }
+
+    pub fn set_object_transform(&mut self, id: &str, transform: JsValue) -> Result<(), JsValue> {
+        let transform: Transform = serde_wasm_bindgen::from_value(transform)?;
+        self.renderer
+            .set_object_transform(id, &transform)
+            .map_err(map_wgpu_err)
+    }
+
+    pub fn write_voxels(
+        &mut self,
+        id: &str,
+        origin: &[u32],
+        extent: &[u32],
+        data: &[u8],
+    ) -> Result<(), JsValue> {
+        let origin = origin
+            .try_into()
+            .map_err(|_| JsValue::from_str("origin must have 3 elements"))?;
+        let extent = extent
+            .try_into()
+            .map_err(|_| JsValue::from_str("extent must have 3 elements"))?;
+        self.renderer
+            .write_voxels(id, origin, extent, data)
+            .map_err(map_wgpu_err)
+    }
+
+    pub fn set_lighting(&mut self, lighting: JsValue) -> Result<(), JsValue> {
+        let lighting: Lighting = serde_wasm_bindgen::from_value(lighting)?;
+        self.renderer.set_lighting(&lighting).map_err(map_wgpu_err)
+    }
+
+    pub fn set_depth_mode(&mut self, mode: JsValue) -> Result<(), JsValue> {
+        let mode: DepthMode = serde_wasm_bindgen::from_value(mode)?;
+        self.renderer.set_depth_mode(mode).map_err(map_wgpu_err)
+    }
+
+    pub fn set_ao_mode(&mut self, mode: JsValue) -> Result<(), JsValue> {
+        let mode: AoMode = serde_wasm_bindgen::from_value(mode)?;
+        self.renderer.set_ao_mode(mode);
+        Ok(())
+    }
+
+    pub fn set_voxel_storage(&mut self, storage: JsValue) -> Result<(), JsValue> {
+        let storage: VoxelStorage = serde_wasm_bindgen::from_value(storage)?;
+        self.renderer.set_voxel_storage(storage);
+        Ok(())
+    }
+
+    pub fn add_light(&mut self, id: &str, light: JsValue) -> Result<(), JsValue> {
+        let light: Light = serde_wasm_bindgen::from_value(light)?;
+        self.renderer.add_light(id, &light).map_err(map_wgpu_err)
+    }
+
+    pub fn update_light(&mut self, id: &str, light: JsValue) -> Result<(), JsValue> {
+        let light: Light = serde_wasm_bindgen::from_value(light)?;
+        self.renderer.update_light(id, &light).map_err(map_wgpu_err)
+    }
+
+    pub fn remove_light(&mut self, id: &str) -> Result<(), JsValue> {
+        self.renderer.remove_light(id).map_err(map_wgpu_err)
+    }
+
+    /// Resolves to the voxel drawn at pixel `(x, y)` by the last render, or `null`.
+    pub async fn pick(&self, x: u32, y: u32) -> Result<JsValue, JsValue> {
+        let hit = self.renderer.pick(x, y).await.map_err(map_wgpu_err)?;
+        Ok(serde_wasm_bindgen::to_value(&hit)?)
+    }
+
+    pub fn update_palette(&mut self, palette: JsValue) -> Result<(), JsValue> {
+        let palette: Vec<RGBA> = serde_wasm_bindgen::from_value(palette)?;
+        self.renderer.update_palette(&palette).map_err(map_wgpu_err)
+    }
+}
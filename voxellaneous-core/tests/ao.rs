@@ -0,0 +1,92 @@
+mod common;
+
+use common::{object, view_projection};
+use glam::{IVec3, Vec3};
+use voxellaneous_core::ao::{ao_bit, bake_ao, corner_ao, face_ao};
+use voxellaneous_core::reference::render_reference;
+use voxellaneous_core::{AoMode, Scene, Transform, VoxelObject, RGBA};
+
+/// A 3x2x3 object: a full floor layer with one voxel on top of its -X, -Z corner.
+fn step() -> VoxelObject {
+    let voxels = (0..18)
+        .map(|i| {
+            let (x, y, z) = (i % 3, i / 3 % 2, i / 6);
+            (y == 0 || (x == 0 && z == 0)) as u8
+        })
+        .collect();
+    object("step", Transform::IDENTITY, [3, 2, 3], voxels)
+}
+
+fn level(entry: [u8; 6], axis: usize, sign: i32, corner: usize) -> u32 {
+    let bit = ao_bit(axis, sign, corner);
+    (entry[(bit / 8) as usize] as u32 >> (bit % 8)) & 3
+}
+
+#[test]
+fn corner_levels() {
+    assert_eq!(corner_ao(false, false, false), 3);
+    assert_eq!(corner_ao(false, false, true), 2);
+    assert_eq!(corner_ao(true, false, true), 1);
+    // Two sides already hide the corner completely.
+    assert_eq!(corner_ao(true, true, false), 0);
+}
+
+#[test]
+fn bakes_face_corners_next_to_neighbors() {
+    let obj = step();
+    let baked = bake_ao(&obj);
+    assert_eq!(baked.len(), 18);
+
+    // Top face (+Y) of the floor voxel at x = 1, z = 0 touches the step on its -X edge.
+    let entry = baked[1];
+    // Corners: bit 0 picks -Z/+Z, bit 1 picks -X/+X for faces along Y.
+    assert_eq!(level(entry, 1, 1, 0), 2);
+    assert_eq!(level(entry, 1, 1, 1), 2);
+    assert_eq!(level(entry, 1, 1, 2), 3);
+    assert_eq!(level(entry, 1, 1, 3), 3);
+
+    // The diagonal neighbor at x = 1, z = 1 only sees the step through its -X, -Z corner.
+    let entry = baked[7];
+    assert_eq!(level(entry, 1, 1, 0), 2);
+    assert_eq!([1, 2, 3].map(|c| level(entry, 1, 1, c)), [3; 3]);
+
+    // Baking agrees with the per-face lookup the shader does.
+    let solid = |v: IVec3| {
+        v.cmpge(IVec3::ZERO).all()
+            && v.cmplt(IVec3::new(3, 2, 3)).all()
+            && obj.voxels[(v.x + 3 * (v.y + 2 * v.z)) as usize] != 0
+    };
+    for (i, entry) in baked.iter().enumerate() {
+        let voxel = IVec3::new(i as i32 % 3, i as i32 / 3 % 2, i as i32 / 6);
+        for axis in 0..3 {
+            for sign in [-1, 1] {
+                let levels = face_ao(solid, voxel, axis, sign);
+                for (corner, &expected) in levels.iter().enumerate() {
+                    assert_eq!(level(*entry, axis, sign, corner), expected);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn shader_and_precomputed_modes_agree() {
+    let scene = Scene {
+        palette: vec![RGBA(0, 0, 0, 0), RGBA(255, 255, 255, 255)],
+        objects: vec![step()],
+    };
+    let eye = Vec3::new(1.5, 2.0, 2.5);
+    let vp_matrix = view_projection(eye, Vec3::ZERO, 32, 32);
+    let render = |mode| render_reference(&scene, vp_matrix, eye.to_array(), 32, 32, mode);
+
+    let off = render(AoMode::Off);
+    let shader = render(AoMode::Shader);
+    assert_eq!(shader, render(AoMode::Precomputed));
+    // Covered pixels are fully open without AO and partly occluded next to the step with it.
+    assert!(off
+        .ao
+        .iter()
+        .zip(&off.normal)
+        .all(|(&ao, n)| ao == if n[..3] == [0; 3] { 0 } else { 255 }));
+    assert!(shader.ao.iter().any(|&ao| ao > 0 && ao < 200));
+}
@@ -0,0 +1,60 @@
+use voxellaneous_core::bricks::{
+    brick_dims, brick_local_index, bricks_to_fill, extract_brick, BrickAllocator, BRICK_VOXELS,
+    EMPTY_BRICK,
+};
+
+#[test]
+fn allocator_reuses_freed_slots() {
+    let mut bricks = BrickAllocator::new();
+    assert!(bricks.is_empty());
+    let slots: Vec<_> = (0..4).map(|_| bricks.allocate()).collect();
+    assert_eq!(slots, [0, 1, 2, 3]);
+
+    bricks.free(1);
+    bricks.free(3);
+    assert_eq!((bricks.len(), bricks.in_use()), (4, 2));
+    let mut reused = [bricks.allocate(), bricks.allocate()];
+    reused.sort_unstable();
+    assert_eq!(reused, [1, 3]);
+    // Only then does the allocator hand out new slots.
+    assert_eq!(bricks.allocate(), 4);
+    assert_eq!((bricks.len(), bricks.in_use()), (5, 5));
+}
+
+#[test]
+fn extracts_bricks_padded_past_the_grid() {
+    let dims = [10, 3, 9];
+    assert_eq!(brick_dims(dims), [2, 1, 2]);
+    let voxels: Vec<u32> = (0..dims.iter().product()).collect();
+
+    let first = extract_brick(&voxels, dims, [0, 0, 0], u32::MAX);
+    assert_eq!(first[brick_local_index([7, 2, 7])], 7 + 10 * (2 + 3 * 7));
+    assert_eq!(first[brick_local_index([0, 3, 0])], u32::MAX);
+
+    // The far corner brick holds a 2x3x1 sliver of the grid.
+    let corner = extract_brick(&voxels, dims, [1, 0, 1], u32::MAX);
+    let inside = corner.iter().filter(|&&v| v != u32::MAX).count();
+    assert_eq!(inside, 2 * 3);
+    assert_eq!(corner[brick_local_index([1, 2, 0])], 9 + 10 * (2 + 3 * 8));
+    assert_eq!(corner.len(), BRICK_VOXELS);
+}
+
+#[test]
+fn counts_only_bricks_a_write_fills() {
+    // A 2x1x2-brick grid where only the first brick holds voxels.
+    let dims = [16, 8, 16];
+    let table = [0, EMPTY_BRICK, EMPTY_BRICK, EMPTY_BRICK];
+    let origin = [4, 2, 4];
+    let extent = [8, 1, 8];
+
+    // Clearing voxels, or filling ones in bricks that already have a slot, takes no new slots.
+    assert_eq!(bricks_to_fill(&table, dims, origin, extent, &[0; 64]), 0);
+    let mut data = [0; 64];
+    data[0] = 1;
+    assert_eq!(bricks_to_fill(&table, dims, origin, extent, &data), 0);
+    // The last row lies at z = 11, in the third and fourth bricks.
+    data[63] = 2;
+    data[56] = 3;
+    assert_eq!(bricks_to_fill(&table, dims, origin, extent, &data), 2);
+    assert_eq!(bricks_to_fill(&table, dims, origin, extent, &[1; 64]), 3);
+}
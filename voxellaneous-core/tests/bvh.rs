@@ -0,0 +1,113 @@
+mod common;
+
+use common::random;
+use glam::{Mat4, Vec3};
+use voxellaneous_core::bvh::{Aabb, Bvh, Frustum};
+use voxellaneous_core::{Transform, VoxelObject};
+
+/// Deterministic boxes of up to 2 units scattered over `[-20, 20]^3`.
+fn scattered(count: usize, seed: u32) -> Vec<Aabb> {
+    let mut random = random(seed);
+    (0..count)
+        .map(|_| {
+            let min = Vec3::from([0, 1, 2].map(|_| random() * 40.0 - 20.0));
+            let size = Vec3::from([0, 1, 2].map(|_| random() * 2.0));
+            Aabb {
+                min,
+                max: min + size,
+            }
+        })
+        .collect()
+}
+
+fn collect(query: impl FnOnce(&mut dyn FnMut(usize))) -> Vec<usize> {
+    let mut found = Vec::new();
+    query(&mut |index| found.push(index));
+    found.sort_unstable();
+    found
+}
+
+#[test]
+fn bounds_rotated_unit_cube() {
+    let (sin, cos) = std::f32::consts::FRAC_PI_8.sin_cos();
+    let obj = VoxelObject {
+        id: "diamond".to_owned(),
+        transform: Transform::Trs {
+            translation: [1.0, 2.0, 3.0],
+            rotation: [0.0, sin, 0.0, cos],
+            scale: [2.0, 1.0, 2.0],
+        },
+        dims: [1, 1, 1],
+        voxels: vec![1],
+    };
+    let bounds = obj.world_bounds();
+    // An eighth of a turn about y puts the cube's corners on the x and z axes.
+    let half = 2f32.sqrt();
+    let expected_min = Vec3::new(1.0 - half, 1.5, 3.0 - half);
+    let expected_max = Vec3::new(1.0 + half, 2.5, 3.0 + half);
+    assert!(bounds.min.abs_diff_eq(expected_min, 1e-5), "{bounds:?}");
+    assert!(bounds.max.abs_diff_eq(expected_max, 1e-5), "{bounds:?}");
+}
+
+#[test]
+fn queries_match_brute_force_after_refit() {
+    let mut boxes = scattered(300, 7);
+    let mut bvh = Bvh::new(&boxes);
+    assert_eq!(bvh.len(), boxes.len());
+
+    let view = Mat4::look_at_rh(Vec3::new(0.0, 5.0, 30.0), Vec3::ZERO, Vec3::Y);
+    let projection = Mat4::perspective_rh(40f32.to_radians(), 1.5, 0.1, 45.0);
+    let frustum = Frustum::new(projection * view);
+    let region = Aabb {
+        min: Vec3::splat(-5.0),
+        max: Vec3::new(5.0, 2.0, 8.0),
+    };
+
+    let check = |bvh: &Bvh, boxes: &[Aabb]| {
+        let in_frustum = collect(|visit| bvh.query_frustum(&frustum, visit));
+        let expected: Vec<_> = (0..boxes.len())
+            .filter(|&i| frustum.intersects(&boxes[i]))
+            .collect();
+        assert_eq!(in_frustum, expected);
+        // The camera sees some boxes but not all of them.
+        assert!(!expected.is_empty() && expected.len() < boxes.len());
+
+        let overlapping = collect(|visit| bvh.query_aabb(&region, visit));
+        let expected: Vec<_> = (0..boxes.len())
+            .filter(|&i| boxes[i].intersects(&region))
+            .collect();
+        assert_eq!(overlapping, expected);
+        assert!(!expected.is_empty());
+    };
+    check(&bvh, &boxes);
+
+    // Move a third of the boxes somewhere else entirely.
+    let moved = scattered(100, 99);
+    for (i, bounds) in moved.into_iter().enumerate() {
+        let index = i * 3;
+        boxes[index] = bounds;
+        bvh.refit(index, bounds);
+        assert_eq!(bvh.bounds(index), bounds);
+    }
+    check(&bvh, &boxes);
+}
+
+#[test]
+fn frustum_rejects_boxes_outside() {
+    let view = Mat4::look_at_rh(Vec3::ZERO, Vec3::NEG_Z, Vec3::Y);
+    let projection = Mat4::perspective_rh(60f32.to_radians(), 1.0, 1.0, 10.0);
+    let frustum = Frustum::new(projection * view);
+    let at = |center: Vec3| Aabb {
+        min: center - 0.25,
+        max: center + 0.25,
+    };
+
+    assert!(frustum.intersects(&at(Vec3::new(0.0, 0.0, -5.0))));
+    // Behind the camera, before the near plane, beyond the far plane and off to the side.
+    assert!(!frustum.intersects(&at(Vec3::new(0.0, 0.0, 5.0))));
+    assert!(!frustum.intersects(&at(Vec3::new(0.0, 0.0, -0.5))));
+    assert!(!frustum.intersects(&at(Vec3::new(0.0, 0.0, -11.0))));
+    assert!(!frustum.intersects(&at(Vec3::new(6.0, 0.0, -5.0))));
+    // Straddling a plane still counts.
+    assert!(frustum.intersects(&at(Vec3::new(0.0, 0.0, -10.0))));
+}
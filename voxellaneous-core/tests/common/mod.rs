@@ -0,0 +1,52 @@
+//! Fixtures shared by the integration tests; each test crate uses only some of them.
+#![allow(dead_code)]
+
+use glam::{Mat4, Vec3};
+use voxellaneous_core::{Transform, VoxelObject, RGBA};
+
+/// Empty, then opaque red, green and blue.
+pub fn palette() -> Vec<RGBA> {
+    vec![
+        RGBA(0, 0, 0, 0),
+        RGBA(255, 0, 0, 255),
+        RGBA(0, 255, 0, 255),
+        RGBA(0, 0, 255, 255),
+    ]
+}
+
+pub fn object(id: &str, transform: Transform, dims: [u32; 3], voxels: Vec<u8>) -> VoxelObject {
+    VoxelObject {
+        id: id.to_owned(),
+        transform,
+        dims,
+        voxels,
+    }
+}
+
+/// A `size`-voxel cube of palette index `index`, scaled to `size` world units at `translation`.
+pub fn block(id: &str, translation: [f32; 3], size: u32, index: u8) -> VoxelObject {
+    object(
+        id,
+        Transform::from_translation_scale(translation, [size as f32; 3]),
+        [size; 3],
+        vec![index; (size * size * size) as usize],
+    )
+}
+
+/// Camera at `eye` looking at `target` with a 60° vertical field of view, for a `width` ×
+/// `height` target and wgpu's `[0, 1]` depth range.
+pub fn view_projection(eye: Vec3, target: Vec3, width: u32, height: u32) -> [f32; 16] {
+    let view = Mat4::look_at_rh(eye, target, Vec3::Y);
+    let projection =
+        Mat4::perspective_rh(60f32.to_radians(), width as f32 / height as f32, 0.1, 100.0);
+    (projection * view).to_cols_array()
+}
+
+/// Deterministic numbers in `[0, 1)`, the same sequence for the same `seed`.
+pub fn random(seed: u32) -> impl FnMut() -> f32 {
+    let mut seed = seed;
+    move || {
+        seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+        (seed >> 8) as f32 / 16777216.0
+    }
+}
@@ -0,0 +1,157 @@
+use glam::{Mat4, Quat, Vec3, Vec4};
+use serde_json::Value;
+use voxellaneous_core::gltf::write_glb;
+use voxellaneous_core::{
+    InvalidReason, ObjectField, Scene, SceneError, Transform, VoxelObject, RGBA,
+};
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Splits a GLB into its JSON document and binary chunk, checking the container layout.
+fn parse_glb(glb: &[u8]) -> (Value, &[u8]) {
+    assert_eq!(&glb[..4], b"glTF");
+    assert_eq!(read_u32(glb, 4), 2);
+    assert_eq!(read_u32(glb, 8) as usize, glb.len());
+
+    let json_len = read_u32(glb, 12) as usize;
+    assert_eq!(&glb[16..20], b"JSON");
+    assert_eq!(json_len % 4, 0);
+    let document = serde_json::from_slice(&glb[20..20 + json_len]).unwrap();
+
+    let bin_start = 20 + json_len;
+    let bin_len = read_u32(glb, bin_start) as usize;
+    assert_eq!(&glb[bin_start + 4..bin_start + 8], b"BIN\0");
+    (document, &glb[bin_start + 8..bin_start + 8 + bin_len])
+}
+
+#[test]
+fn exports_nodes_meshes_and_vertex_colors() {
+    let transform = Transform::from_translation_scale([1.0, 2.0, 3.0], [2.0, 1.0, 1.0]);
+    let scene = Scene {
+        palette: vec![RGBA(0, 0, 0, 0), RGBA(255, 0, 0, 255)],
+        objects: vec![
+            VoxelObject {
+                id: "brick".to_owned(),
+                transform,
+                dims: [2, 1, 1],
+                voxels: vec![1, 1],
+            },
+            VoxelObject {
+                id: "air".to_owned(),
+                transform: Transform::IDENTITY,
+                dims: [1, 1, 1],
+                voxels: vec![0],
+            },
+        ],
+    };
+
+    let glb = write_glb(&scene).unwrap();
+    let (doc, bin) = parse_glb(&glb);
+    assert_eq!(doc["asset"]["version"], "2.0");
+    assert_eq!(
+        doc["buffers"][0]["byteLength"].as_u64().unwrap() as usize,
+        bin.len()
+    );
+
+    let nodes = doc["nodes"].as_array().unwrap();
+    assert_eq!(nodes.len(), 2);
+    assert_eq!(nodes[0]["name"], "brick");
+    assert!(nodes[0].get("matrix").is_none());
+    assert_eq!(nodes[0]["translation"], serde_json::json!([1.0, 2.0, 3.0]));
+    assert_eq!(
+        nodes[0]["rotation"],
+        serde_json::json!([0.0, 0.0, 0.0, 1.0])
+    );
+    assert_eq!(nodes[0]["scale"], serde_json::json!([2.0, 1.0, 1.0]));
+    assert_eq!(nodes[1]["name"], "air");
+    assert!(nodes[1].get("mesh").is_none());
+
+    // A 2x1x1 box merges into six quads.
+    let primitive = &doc["meshes"][0]["primitives"][0];
+    let accessor =
+        |name: &str| &doc["accessors"][primitive["attributes"][name].as_u64().unwrap() as usize];
+    assert_eq!(accessor("POSITION")["count"], 24);
+    assert_eq!(
+        accessor("POSITION")["min"],
+        serde_json::json!([-0.5, -0.5, -0.5])
+    );
+    assert_eq!(accessor("NORMAL")["count"], 24);
+    assert_eq!(
+        doc["accessors"][primitive["indices"].as_u64().unwrap() as usize]["count"],
+        36
+    );
+
+    // Vertex colors are the linearized palette entry.
+    let colors = accessor("COLOR_0");
+    let view = &doc["bufferViews"][colors["bufferView"].as_u64().unwrap() as usize];
+    let offset = view["byteOffset"].as_u64().unwrap() as usize;
+    let first: Vec<f32> = (0..4)
+        .map(|k| f32::from_le_bytes(bin[offset + 4 * k..offset + 4 * k + 4].try_into().unwrap()))
+        .collect();
+    assert_eq!(first, [1.0, 0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn rejects_invalid_scenes() {
+    let scene = Scene {
+        palette: vec![RGBA(0, 0, 0, 0)],
+        objects: vec![VoxelObject {
+            id: "broken".to_owned(),
+            transform: Transform::IDENTITY,
+            dims: [2, 2, 2],
+            voxels: vec![0; 3],
+        }],
+    };
+    assert!(write_glb(&scene).is_err());
+}
+
+#[test]
+fn exports_raw_matrices_only_without_shear() {
+    let (sin, cos) = 0.3f32.sin_cos();
+    let rotated_scaled = Mat4::from_cols(
+        Vec4::new(2.0 * cos, 2.0 * sin, 0.0, 0.0),
+        Vec4::new(-sin, cos, 0.0, 0.0),
+        Vec4::new(0.0, 0.0, 3.0, 0.0),
+        Vec4::new(4.0, 5.0, 6.0, 1.0),
+    );
+    let mut scene = Scene {
+        palette: vec![RGBA(0, 0, 0, 0), RGBA(255, 0, 0, 255)],
+        objects: vec![VoxelObject {
+            id: "raw".to_owned(),
+            transform: Transform::Matrix(rotated_scaled.to_cols_array()),
+            dims: [1, 1, 1],
+            voxels: vec![1],
+        }],
+    };
+    let (doc, _) = parse_glb(&write_glb(&scene).unwrap());
+    let node = &doc["nodes"][0];
+    let floats = |name: &str| -> Vec<f32> {
+        node[name]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v.as_f64().unwrap() as f32)
+            .collect()
+    };
+    let rotation = Quat::from_slice(&floats("rotation"));
+    let rebuilt = Mat4::from_scale_rotation_translation(
+        Vec3::from_slice(&floats("scale")),
+        rotation,
+        Vec3::from_slice(&floats("translation")),
+    );
+    assert!(rebuilt.abs_diff_eq(rotated_scaled, 1e-5));
+
+    let mut sheared = Mat4::IDENTITY;
+    sheared.y_axis.x = 0.5;
+    scene.objects[0].transform = Transform::Matrix(sheared.to_cols_array());
+    assert_eq!(
+        write_glb(&scene),
+        Err(SceneError::InvalidObject {
+            id: "raw".to_owned(),
+            field: ObjectField::Transform,
+            reason: InvalidReason::NotDecomposable,
+        })
+    );
+}
@@ -0,0 +1,1034 @@
+mod common;
+
+use common::{object, palette, view_projection};
+use glam::{Mat4, Vec3};
+use voxellaneous_core::reference::{render_reference, shade_reference};
+use voxellaneous_core::{
+    AoMode, DepthMode, InvalidReason, Light, LightError, Lighting, ObjectField, PickHit,
+    PointLight, PresentTarget, Raycaster, RenderStats, Renderer, RendererError, Scene, SceneError,
+    ShadowMode, Shadows, SpotLight, Sun, Transform, VoxelStorage, RGBA,
+};
+
+const WIDTH: u32 = 70;
+const HEIGHT: u32 = 40;
+
+/// Creates a headless renderer, or `None` on machines without any GPU adapter.
+fn headless() -> Option<Renderer> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+    match pollster::block_on(Renderer::new_headless(&instance, WIDTH, HEIGHT)) {
+        Ok(renderer) => Some(renderer),
+        Err(RendererError::RequestAdapter(e)) => {
+            eprintln!("skipping GPU test: {e}");
+            None
+        }
+        Err(e) => panic!("{e}"),
+    }
+}
+
+fn scene() -> Scene {
+    let voxels = (0..64).map(|i| (i % 3 + 1) as u8).collect();
+    Scene {
+        palette: palette(),
+        objects: vec![
+            object(
+                "cube",
+                Transform::from_translation_scale([0.0, 0.0, 0.0], [2.0, 2.0, 2.0]),
+                [4, 4, 4],
+                voxels,
+            ),
+            object(
+                "floor",
+                Transform::from_translation_scale([0.0, -1.5, 0.0], [6.0, 0.5, 6.0]),
+                [6, 1, 6],
+                vec![2; 36],
+            ),
+        ],
+    }
+}
+
+fn widen(bytes: &[u8]) -> Vec<u16> {
+    bytes.iter().map(|&b| b as u16).collect()
+}
+
+/// Compares GPU texels against the CPU reference. Other GPUs may rasterize silhouettes or round
+/// slightly differently, so up to 2% of texels may be off by more than `tolerance`.
+fn assert_close(target: &str, gpu: &[u16], cpu: &[u16], channels: usize, tolerance: u16) {
+    assert_eq!(gpu.len(), cpu.len());
+    let mismatches = gpu
+        .chunks(channels)
+        .zip(cpu.chunks(channels))
+        .filter(|(a, b)| a.iter().zip(*b).any(|(a, b)| a.abs_diff(*b) > tolerance))
+        .count();
+    let texels = gpu.len() / channels;
+    assert!(
+        mismatches * 50 <= texels,
+        "{mismatches} of {texels} {target} texels differ"
+    );
+}
+
+#[test]
+fn reads_back_gbuffer_targets() {
+    let Some(mut renderer) = headless() else {
+        return;
+    };
+    let scene = scene();
+    renderer.upload_scene(&scene).unwrap();
+
+    let eye = Vec3::new(2.0, 3.0, 5.0);
+    let vp_matrix = view_projection(eye, Vec3::ZERO, WIDTH, HEIGHT);
+    renderer
+        .render(vp_matrix, eye.to_array(), PresentTarget::Albedo)
+        .unwrap();
+
+    let albedo = pollster::block_on(renderer.read_pixels(PresentTarget::Albedo)).unwrap();
+    let normal = pollster::block_on(renderer.read_pixels(PresentTarget::Normal)).unwrap();
+    let linear_z = pollster::block_on(renderer.read_pixels(PresentTarget::LinearZ)).unwrap();
+    let pixels = (WIDTH * HEIGHT) as usize;
+    assert_eq!(albedo.len(), pixels * 4);
+    assert_eq!(normal.len(), pixels * 4);
+    assert_eq!(linear_z.len(), pixels * 2);
+
+    let reference = render_reference(
+        &scene,
+        vp_matrix,
+        eye.to_array(),
+        WIDTH,
+        HEIGHT,
+        AoMode::Off,
+    );
+    assert_close(
+        "albedo",
+        &widen(&albedo),
+        &widen(&reference.albedo.concat()),
+        4,
+        0,
+    );
+    assert_close(
+        "normal",
+        &widen(&normal),
+        &widen(&reference.normal.concat()),
+        4,
+        0,
+    );
+    let linear_z: Vec<u16> = linear_z
+        .chunks(2)
+        .map(|texel| u16::from_le_bytes([texel[0], texel[1]]))
+        .collect();
+    assert_close("linear Z", &linear_z, &reference.linear_z, 1, 1);
+
+    // The lit target is filled on every render, whichever target is presented.
+    let lighting = Lighting {
+        sun: Sun {
+            direction: [0.3, -1.0, -0.5],
+            color: [1.0, 0.9, 0.8],
+            intensity: 0.8,
+        },
+        ambient: [0.1, 0.1, 0.2],
+        shadows: Shadows::default(),
+    };
+    let lights = [
+        Light::Point(PointLight {
+            position: [1.5, 1.5, 1.5],
+            color: [1.0, 0.2, 0.2],
+            intensity: 4.0,
+            range: 3.0,
+        }),
+        Light::Spot(SpotLight {
+            position: [-2.0, 0.0, 2.0],
+            direction: [1.0, 0.0, -1.0],
+            color: [0.2, 0.2, 1.0],
+            intensity: 6.0,
+            range: 6.0,
+            inner_angle: 0.2,
+            outer_angle: 0.5,
+        }),
+    ];
+    renderer.set_lighting(&lighting).unwrap();
+    renderer.add_light("point", &lights[0]).unwrap();
+    renderer.add_light("spot", &lights[1]).unwrap();
+    renderer
+        .render(vp_matrix, eye.to_array(), PresentTarget::Normal)
+        .unwrap();
+    let lit = pollster::block_on(renderer.read_pixels(PresentTarget::Lit)).unwrap();
+    let lit_reference = shade_reference(&reference, &scene, &lighting, &lights, vp_matrix).concat();
+    assert_close("lit", &widen(&lit), &widen(&lit_reference), 4, 2);
+
+    // Shadows from the cube onto the floor and from the floor onto the cube's underside.
+    for mode in [ShadowMode::Hard, ShadowMode::Soft] {
+        let lighting = Lighting {
+            shadows: Shadows {
+                mode,
+                samples: 4,
+                light_radius: 0.3,
+                sun_angle: 0.1,
+                ..Shadows::default()
+            },
+            ..lighting
+        };
+        renderer.set_lighting(&lighting).unwrap();
+        renderer
+            .render(vp_matrix, eye.to_array(), PresentTarget::Lit)
+            .unwrap();
+        let lit = pollster::block_on(renderer.read_pixels(PresentTarget::Lit)).unwrap();
+        let lit_reference =
+            shade_reference(&reference, &scene, &lighting, &lights, vp_matrix).concat();
+        assert_close("shadowed", &widen(&lit), &widen(&lit_reference), 4, 2);
+    }
+
+    assert_eq!(
+        renderer.add_light("point", &lights[0]),
+        Err(LightError::Duplicate {
+            id: "point".to_owned()
+        })
+    );
+    renderer.remove_light("point").unwrap();
+    assert_eq!(
+        renderer.update_light("point", &lights[0]),
+        Err(LightError::NotFound {
+            id: "point".to_owned()
+        })
+    );
+
+    assert!(matches!(
+        pollster::block_on(renderer.read_pixels(PresentTarget::Depth)),
+        Err(RendererError::UnreadableTarget(PresentTarget::Depth))
+    ));
+}
+
+#[test]
+fn reads_back_ambient_occlusion() {
+    let Some(mut renderer) = headless() else {
+        return;
+    };
+    // A 4x4 floor with a 2-voxel wall along its back edge, so the floor darkens next to it.
+    let voxels = (0..32)
+        .map(|i| {
+            let (y, z) = (i / 4 % 2, i / 8);
+            (y == 0 || z == 0) as u8
+        })
+        .collect();
+    let mut scene = Scene {
+        palette: vec![RGBA(0, 0, 0, 0), RGBA(200, 200, 200, 255)],
+        objects: vec![object(
+            "corner",
+            Transform::from_translation_scale([0.0, 0.0, 0.0], [4.0, 2.0, 4.0]),
+            [4, 2, 4],
+            voxels,
+        )],
+    };
+    renderer.upload_scene(&scene).unwrap();
+
+    let eye = Vec3::new(1.0, 5.0, 6.0);
+    let vp_matrix = view_projection(eye, Vec3::ZERO, WIDTH, HEIGHT);
+    let read_ao = |renderer: &mut Renderer, mode| {
+        renderer.set_ao_mode(mode);
+        renderer
+            .render(vp_matrix, eye.to_array(), PresentTarget::AmbientOcclusion)
+            .unwrap();
+        pollster::block_on(renderer.read_pixels(PresentTarget::AmbientOcclusion)).unwrap()
+    };
+
+    for mode in [AoMode::Off, AoMode::Shader, AoMode::Precomputed] {
+        let ao = read_ao(&mut renderer, mode);
+        assert_eq!(ao.len(), (WIDTH * HEIGHT) as usize);
+        let reference = render_reference(&scene, vp_matrix, eye.to_array(), WIDTH, HEIGHT, mode);
+        assert!(reference.ao.iter().any(|&a| a > 0 && a < 255) || mode == AoMode::Off);
+        assert_close("AO", &widen(&ao), &widen(&reference.ao), 1, 1);
+    }
+
+    // Edits rebake the occlusion of the voxels around them.
+    scene.objects[0]
+        .set_region([0, 1, 3], [4, 1, 1], &[1; 4])
+        .unwrap();
+    renderer
+        .write_voxels("corner", [0, 1, 3], [4, 1, 1], &[1; 4])
+        .unwrap();
+    let ao = read_ao(&mut renderer, AoMode::Precomputed);
+    let reference = render_reference(
+        &scene,
+        vp_matrix,
+        eye.to_array(),
+        WIDTH,
+        HEIGHT,
+        AoMode::Shader,
+    );
+    assert_close("rebaked AO", &widen(&ao), &widen(&reference.ao), 1, 1);
+
+    // Other modes keep no baked levels; entering precomputed mode bakes edits made meanwhile.
+    read_ao(&mut renderer, AoMode::Shader);
+    scene.objects[0]
+        .set_region([0, 1, 0], [2, 1, 1], &[0; 2])
+        .unwrap();
+    renderer
+        .write_voxels("corner", [0, 1, 0], [2, 1, 1], &[0; 2])
+        .unwrap();
+    let ao = read_ao(&mut renderer, AoMode::Precomputed);
+    let reference = render_reference(
+        &scene,
+        vp_matrix,
+        eye.to_array(),
+        WIDTH,
+        HEIGHT,
+        AoMode::Shader,
+    );
+    assert_close("baked AO", &widen(&ao), &widen(&reference.ao), 1, 1);
+}
+
+#[test]
+fn sorts_nested_objects_by_hit_depth() {
+    let Some(mut renderer) = headless() else {
+        return;
+    };
+    // The shell's proxy cube encloses the inner block but only its back layer is solid.
+    let shell_voxels = (0..64).map(|i| if i < 16 { 1 } else { 0 }).collect();
+    let scene = Scene {
+        palette: vec![RGBA(0, 0, 0, 0), RGBA(0, 0, 255, 255), RGBA(0, 255, 0, 255)],
+        objects: vec![
+            object(
+                "inner",
+                Transform::from_translation_scale([0.3, 0.0, 0.0], [1.0, 1.0, 1.0]),
+                [2, 2, 2],
+                vec![2; 8],
+            ),
+            object(
+                "shell",
+                Transform::from_translation_scale([0.0, 0.0, 0.0], [8.0, 4.0, 8.0]),
+                [4, 4, 4],
+                shell_voxels,
+            ),
+        ],
+    };
+    renderer.upload_scene(&scene).unwrap();
+
+    let eye = Vec3::new(1.0, 1.5, 6.0);
+    let vp_matrix = view_projection(eye, Vec3::ZERO, WIDTH, HEIGHT);
+    let reference = render_reference(
+        &scene,
+        vp_matrix,
+        eye.to_array(),
+        WIDTH,
+        HEIGHT,
+        AoMode::Off,
+    );
+    assert!(reference.albedo.contains(&[0, 255, 0, 255]));
+
+    let mut modes = vec![DepthMode::Exact];
+    match renderer.set_depth_mode(DepthMode::Conservative) {
+        Ok(()) => modes.push(DepthMode::Conservative),
+        Err(RendererError::MissingFeatures(_)) => eprintln!("skipping conservative depth"),
+        Err(e) => panic!("{e}"),
+    }
+    for mode in modes {
+        renderer.set_depth_mode(mode).unwrap();
+        renderer
+            .render(vp_matrix, eye.to_array(), PresentTarget::Albedo)
+            .unwrap();
+        let albedo = pollster::block_on(renderer.read_pixels(PresentTarget::Albedo)).unwrap();
+        assert_close(
+            &format!("{mode:?} albedo"),
+            &widen(&albedo),
+            &widen(&reference.albedo.concat()),
+            4,
+            0,
+        );
+    }
+}
+
+#[test]
+fn keeps_conservative_depth_with_the_camera_inside_an_object() {
+    let Some(mut renderer) = headless() else {
+        return;
+    };
+    // The camera stands inside a room whose proxy cube shows only back faces. A pillar of the
+    // room lies in front of a crate that is drawn first, and the room's back wall behind it.
+    let dims = [8, 4, 8];
+    let voxels = (0..dims.iter().product::<u32>())
+        .map(|i| {
+            let (x, z) = (i % dims[0], i / (dims[0] * dims[1]));
+            let pillar = x == 4 && z == 3;
+            (z == 0 || pillar) as u8
+        })
+        .collect();
+    let scene = Scene {
+        palette: palette(),
+        objects: vec![
+            object(
+                "crate",
+                Transform::from_translation_scale([0.0, 0.0, -3.0], [3.0, 2.0, 0.5]),
+                [3, 2, 1],
+                vec![3; 6],
+            ),
+            object(
+                "room",
+                Transform::from_translation_scale([0.0, 0.0, 0.0], [16.0, 8.0, 16.0]),
+                dims,
+                voxels,
+            ),
+        ],
+    };
+    renderer.upload_scene(&scene).unwrap();
+
+    let eye = Vec3::new(0.0, 0.5, 6.0);
+    let vp_matrix = view_projection(eye, Vec3::new(0.0, 0.0, -8.0), WIDTH, HEIGHT);
+    let reference = render_reference(
+        &scene,
+        vp_matrix,
+        eye.to_array(),
+        WIDTH,
+        HEIGHT,
+        AoMode::Off,
+    );
+    // Both the pillar and the crate around it show.
+    assert!(reference.albedo.contains(&[255, 0, 0, 255]));
+    assert!(reference.albedo.contains(&[0, 0, 255, 255]));
+
+    match renderer.set_depth_mode(DepthMode::Conservative) {
+        Ok(()) => {}
+        Err(RendererError::MissingFeatures(_)) => {
+            eprintln!("skipping conservative depth");
+            return;
+        }
+        Err(e) => panic!("{e}"),
+    }
+    renderer
+        .render(vp_matrix, eye.to_array(), PresentTarget::Albedo)
+        .unwrap();
+    let albedo = pollster::block_on(renderer.read_pixels(PresentTarget::Albedo)).unwrap();
+    assert_close(
+        "albedo",
+        &widen(&albedo),
+        &widen(&reference.albedo.concat()),
+        4,
+        0,
+    );
+}
+
+#[test]
+fn picks_voxels_under_pixels() {
+    let Some(mut renderer) = headless() else {
+        return;
+    };
+    let scene = scene();
+    renderer.upload_scene(&scene).unwrap();
+
+    let eye = Vec3::new(2.0, 3.0, 5.0);
+    let vp_matrix = view_projection(eye, Vec3::ZERO, WIDTH, HEIGHT);
+    renderer
+        .render(vp_matrix, eye.to_array(), PresentTarget::Albedo)
+        .unwrap();
+
+    let pick = |x, y| pollster::block_on(renderer.pick(x, y)).unwrap();
+    let center = pick(WIDTH / 2, HEIGHT / 2).unwrap();
+    assert_eq!(center.object_id, "cube");
+    let expected = scene.pick(
+        vp_matrix,
+        eye.to_array(),
+        WIDTH,
+        HEIGHT,
+        WIDTH / 2,
+        HEIGHT / 2,
+    );
+    assert_eq!(expected.as_ref(), Some(&center));
+    assert_eq!(pick(0, 0), None);
+    assert_eq!(pick(WIDTH, 0), None);
+
+    // Silhouettes may rasterize differently on other GPUs, as in `assert_close`.
+    let raycaster = Raycaster::new(&scene);
+    let mut pixels = 0;
+    let mut mismatches = 0;
+    for y in (0..HEIGHT).step_by(3) {
+        for x in (0..WIDTH).step_by(3) {
+            let expected = raycaster.pick(vp_matrix, eye.to_array(), WIDTH, HEIGHT, x, y);
+            pixels += 1;
+            mismatches += (pick(x, y) != expected) as usize;
+        }
+    }
+    assert!(
+        mismatches * 50 <= pixels,
+        "{mismatches} of {pixels} picks differ"
+    );
+
+    // Picks of removed objects find nothing, even before the next render.
+    renderer.remove_object("cube").unwrap();
+    let removed = pollster::block_on(renderer.pick(WIDTH / 2, HEIGHT / 2)).unwrap();
+    assert_eq!(removed, None);
+}
+
+#[test]
+fn culls_objects_outside_the_frustum() {
+    let Some(mut renderer) = headless() else {
+        return;
+    };
+    let mut scene = scene();
+    scene.objects.push(object(
+        "behind",
+        Transform::from_translation_scale([0.0, 0.0, 12.0], [1.0, 1.0, 1.0]),
+        [1, 1, 1],
+        vec![3],
+    ));
+    renderer.upload_scene(&scene).unwrap();
+
+    let eye = Vec3::new(2.0, 3.0, 5.0);
+    let vp_matrix = view_projection(eye, Vec3::ZERO, WIDTH, HEIGHT);
+    let render = |renderer: &mut Renderer| {
+        renderer
+            .render(vp_matrix, eye.to_array(), PresentTarget::Albedo)
+            .unwrap();
+        pollster::block_on(renderer.read_pixels(PresentTarget::Albedo)).unwrap()
+    };
+
+    let albedo = render(&mut renderer);
+    assert_eq!(
+        renderer.stats(),
+        RenderStats {
+            drawn: 2,
+            culled: 1
+        }
+    );
+    let reference = render_reference(
+        &scene,
+        vp_matrix,
+        eye.to_array(),
+        WIDTH,
+        HEIGHT,
+        AoMode::Off,
+    );
+    assert_close(
+        "albedo",
+        &widen(&albedo),
+        &widen(&reference.albedo.concat()),
+        4,
+        0,
+    );
+
+    // Moving the object into view draws it again.
+    renderer
+        .set_object_transform(
+            "behind",
+            &Transform::from_translation_scale([0.0, 1.5, 0.0], [1.0, 1.0, 1.0]),
+        )
+        .unwrap();
+    render(&mut renderer);
+    assert_eq!(
+        renderer.stats(),
+        RenderStats {
+            drawn: 3,
+            culled: 0
+        }
+    );
+}
+
+#[test]
+fn packs_objects_into_the_brick_atlas() {
+    let Some(mut renderer) = headless() else {
+        return;
+    };
+    // A terrace of 18x18 columns of bricks, more than one atlas layer holds, with a pit of 4x4
+    // empty columns in the middle.
+    let dims = [144, 12, 144];
+    let voxels = (0..dims.iter().product::<u32>())
+        .map(|i| {
+            let (x, y, z) = (i % dims[0], i / dims[0] % dims[1], i / (dims[0] * dims[1]));
+            let height = 4 + (x / 8 + z / 8) % 3 * 4;
+            let hole = (7..11).contains(&(x / 8)) && (7..11).contains(&(z / 8));
+            (y < height && !hole) as u8 * (1 + (x + z) % 3) as u8
+        })
+        .collect();
+    let mut scene = scene();
+    scene.objects = vec![object(
+        "terrace",
+        Transform::from_translation_scale([0.0, 0.0, 0.0], [6.0, 0.5, 6.0]),
+        dims,
+        voxels,
+    )];
+    renderer.upload_scene(&scene).unwrap();
+    renderer.set_ao_mode(AoMode::Precomputed);
+
+    let eye = Vec3::new(0.0, 2.5, 1.5);
+    let vp_matrix = view_projection(eye, Vec3::new(-0.5, 0.0, 0.0), WIDTH, HEIGHT);
+    let check = |renderer: &mut Renderer, scene: &Scene| {
+        renderer
+            .render(vp_matrix, eye.to_array(), PresentTarget::AmbientOcclusion)
+            .unwrap();
+        let ao = pollster::block_on(renderer.read_pixels(PresentTarget::AmbientOcclusion)).unwrap();
+        let reference = render_reference(
+            scene,
+            vp_matrix,
+            eye.to_array(),
+            WIDTH,
+            HEIGHT,
+            AoMode::Shader,
+        );
+        assert_close("AO", &widen(&ao), &widen(&reference.ao), 1, 1);
+
+        let raycaster = Raycaster::new(scene);
+        let mut pixels = 0;
+        let mut mismatches = 0;
+        for y in (0..HEIGHT).step_by(3) {
+            for x in (0..WIDTH).step_by(3) {
+                let expected = raycaster.pick(vp_matrix, eye.to_array(), WIDTH, HEIGHT, x, y);
+                let picked = pollster::block_on(renderer.pick(x, y)).unwrap();
+                pixels += 1;
+                mismatches += (picked != expected) as usize;
+            }
+        }
+        assert!(
+            mismatches * 50 <= pixels,
+            "{mismatches} of {pixels} picks differ"
+        );
+    };
+    check(&mut renderer, &scene);
+
+    // Filling the pit takes new bricks and digging another frees bricks.
+    let fill = vec![3; 32 * 8 * 32];
+    let clear = vec![0; 32 * 12 * 32];
+    for (origin, extent, data) in [
+        ([56, 0, 56], [32, 8, 32], &fill),
+        ([24, 0, 56], [32, 12, 32], &clear),
+    ] {
+        scene.objects[0].set_region(origin, extent, data).unwrap();
+        renderer
+            .write_voxels("terrace", origin, extent, data)
+            .unwrap();
+    }
+    check(&mut renderer, &scene);
+
+    // Objects added after others were removed reuse their bricks.
+    for i in 0..3 {
+        let id = format!("block{i}");
+        let obj = object(
+            &id,
+            Transform::from_translation_scale([0.0, 0.5, 0.0], [1.0, 1.0, 1.0]),
+            [10, 10, 10],
+            vec![1 + i as u8; 1000],
+        );
+        renderer.add_object(&obj).unwrap();
+        scene.objects.push(obj);
+        if i < 2 {
+            renderer.remove_object(&id).unwrap();
+            scene.objects.pop();
+        }
+    }
+    check(&mut renderer, &scene);
+}
+
+#[test]
+fn reads_back_traversal_steps() {
+    let Some(mut renderer) = headless() else {
+        return;
+    };
+    // A mostly empty grid with a thin floor and a few pillars, so rays cross wide empty cells.
+    let dims = [48, 48, 48];
+    let voxels = (0..dims.iter().product::<u32>())
+        .map(|i| {
+            let (x, y, z) = (i % dims[0], i / dims[0] % dims[1], i / (dims[0] * dims[1]));
+            let pillar = x % 16 == 3 && z % 16 == 5 && y < 30;
+            (y < 2 || pillar) as u8 * (1 + (x + z) % 3) as u8
+        })
+        .collect();
+    let mut scene = scene();
+    scene.objects = vec![object(
+        "sparse",
+        Transform::from_translation_scale([0.0, 0.0, 0.0], [8.0, 4.0, 8.0]),
+        dims,
+        voxels,
+    )];
+    renderer.upload_scene(&scene).unwrap();
+
+    let eye = Vec3::new(1.5, 3.5, 2.0);
+    let vp_matrix = view_projection(eye, Vec3::new(0.0, -2.0, -0.5), WIDTH, HEIGHT);
+    renderer
+        .render(vp_matrix, eye.to_array(), PresentTarget::Steps)
+        .unwrap();
+    let steps = pollster::block_on(renderer.read_pixels(PresentTarget::Steps)).unwrap();
+    assert_eq!(steps.len(), (WIDTH * HEIGHT * 2) as usize);
+    let steps: Vec<u16> = steps
+        .chunks(2)
+        .map(|texel| u16::from_le_bytes([texel[0], texel[1]]))
+        .collect();
+
+    let reference = render_reference(
+        &scene,
+        vp_matrix,
+        eye.to_array(),
+        WIDTH,
+        HEIGHT,
+        AoMode::Off,
+    );
+    // Misses keep their steps on the GPU only, so compare where the reference hits something.
+    let hits: Vec<usize> = (0..steps.len())
+        .filter(|&i| reference.depth[i] < 1.0)
+        .collect();
+    assert!(hits.len() * 2 > steps.len());
+    let gpu: Vec<u16> = hits.iter().map(|&i| steps[i]).collect();
+    let cpu: Vec<u16> = hits.iter().map(|&i| reference.steps[i]).collect();
+    assert_close("steps", &gpu, &cpu, 1, 0);
+    // Crossing the grid voxel by voxel would take dozens of steps.
+    let mean = cpu.iter().map(|&s| s as usize).sum::<usize>() / cpu.len();
+    assert!((1..24).contains(&mean), "{mean} steps on average");
+}
+
+#[test]
+fn octree_edits_change_only_the_edited_voxels() {
+    let Some(mut renderer) = headless() else {
+        return;
+    };
+    let dims = [40, 24, 36];
+    let voxels = (0..dims.iter().product::<u32>())
+        .map(|i| {
+            let (x, y, z) = (i % dims[0], i / dims[0] % dims[1], i / (dims[0] * dims[1]));
+            let pillar = x % 9 == 2 && z % 7 == 4 && y < 18;
+            (y < 2 || pillar) as u8 * (1 + (x + z) % 3) as u8
+        })
+        .collect();
+    let mut scene = scene();
+    scene.objects.push(object(
+        "sparse",
+        Transform::from_translation_scale([0.0, -0.5, -4.0], [6.0, 3.0, 5.0]),
+        dims,
+        voxels,
+    ));
+    renderer.set_voxel_storage(VoxelStorage::Octree);
+    renderer.upload_scene(&scene).unwrap();
+
+    let eye = Vec3::new(3.0, 3.0, 5.0);
+    let vp_matrix = view_projection(eye, Vec3::new(0.0, -0.5, -2.0), WIDTH, HEIGHT);
+    let render = |renderer: &mut Renderer| {
+        renderer
+            .render(vp_matrix, eye.to_array(), PresentTarget::Albedo)
+            .unwrap();
+        pollster::block_on(renderer.read_pixels(PresentTarget::Albedo)).unwrap()
+    };
+    let before = render(&mut renderer);
+
+    // A block on the floor, splitting empty nodes, then a checkered slab that outgrows the
+    // room the octree had in the buffer.
+    let edits = [
+        ([26, 2, 4], [6, 5, 6], vec![3; 180]),
+        (
+            [0, 6, 0],
+            [40, 4, 36],
+            (0..5760)
+                .map(|i| ((i % 40 + i / 40 % 4 + i / 160) % 2) as u8)
+                .collect(),
+        ),
+    ];
+    let mut previous = (before, scene.clone());
+    for (origin, extent, data) in edits {
+        scene.objects[2].set_region(origin, extent, &data).unwrap();
+        renderer
+            .write_voxels("sparse", origin, extent, &data)
+            .unwrap();
+        let after = render(&mut renderer);
+        let reference = render_reference(
+            &scene,
+            vp_matrix,
+            eye.to_array(),
+            WIDTH,
+            HEIGHT,
+            AoMode::Off,
+        );
+        assert_close(
+            "edited albedo",
+            &widen(&after),
+            &widen(&reference.albedo.concat()),
+            4,
+            0,
+        );
+
+        // Pixels that changed show an edited voxel now or did before.
+        let edited = |hit: Option<PickHit>| {
+            hit.is_some_and(|hit| {
+                hit.object_id == "sparse"
+                    && (0..3).all(|axis| {
+                        (origin[axis]..origin[axis] + extent[axis]).contains(&hit.voxel[axis])
+                    })
+            })
+        };
+        let (old, new) = (Raycaster::new(&previous.1), Raycaster::new(&scene));
+        let mut changed = 0;
+        let mut elsewhere = 0;
+        for (i, (a, b)) in previous.0.chunks(4).zip(after.chunks(4)).enumerate() {
+            if a == b {
+                continue;
+            }
+            let (x, y) = (i as u32 % WIDTH, i as u32 / WIDTH);
+            let pick = |raycaster: &Raycaster| {
+                raycaster.pick(vp_matrix, eye.to_array(), WIDTH, HEIGHT, x, y)
+            };
+            changed += 1;
+            elsewhere += !(edited(pick(&old)) || edited(pick(&new))) as usize;
+        }
+        assert!(changed > 0);
+        assert!(
+            elsewhere * 50 <= (WIDTH * HEIGHT) as usize,
+            "{elsewhere} of {changed} changed texels show unedited voxels"
+        );
+        previous = (after, scene.clone());
+    }
+}
+
+#[test]
+fn octree_storage_matches_the_brick_atlas() {
+    let Some(mut renderer) = headless() else {
+        return;
+    };
+    // Pillars on a floor in a grid that is not a power of two, next to the standard scene.
+    let dims = [40, 24, 36];
+    let voxels = (0..dims.iter().product::<u32>())
+        .map(|i| {
+            let (x, y, z) = (i % dims[0], i / dims[0] % dims[1], i / (dims[0] * dims[1]));
+            let pillar = x % 9 == 2 && z % 7 == 4 && y < 18;
+            (y < 2 || pillar) as u8 * (1 + (x + z) % 3) as u8
+        })
+        .collect();
+    let mut scene = scene();
+    scene.objects.push(object(
+        "sparse",
+        Transform::from_translation_scale([0.0, -0.5, -4.0], [6.0, 3.0, 5.0]),
+        dims,
+        voxels,
+    ));
+    renderer.upload_scene(&scene).unwrap();
+
+    let eye = Vec3::new(3.0, 3.0, 5.0);
+    let vp_matrix = view_projection(eye, Vec3::new(0.0, -0.5, -2.0), WIDTH, HEIGHT);
+    let read = |renderer: &mut Renderer, storage| {
+        renderer.set_voxel_storage(storage);
+        let targets = [
+            PresentTarget::Albedo,
+            PresentTarget::Normal,
+            PresentTarget::LinearZ,
+        ];
+        targets.map(|target| {
+            renderer.render(vp_matrix, eye.to_array(), target).unwrap();
+            pollster::block_on(renderer.read_pixels(target)).unwrap()
+        })
+    };
+    let compare = |renderer: &mut Renderer| {
+        let atlas = read(renderer, VoxelStorage::BrickAtlas);
+        let octree = read(renderer, VoxelStorage::Octree);
+        assert!(atlas[0].chunks(4).any(|texel| texel[3] > 0));
+        let targets = [("albedo", 4), ("normal", 4), ("linear Z", 2)];
+        for ((target, channels), (atlas, octree)) in
+            targets.into_iter().zip(atlas.iter().zip(&octree))
+        {
+            assert_close(target, &widen(octree), &widen(atlas), channels, 0);
+        }
+    };
+    compare(&mut renderer);
+
+    // Edits made while marching the octree rebuild it.
+    renderer.set_voxel_storage(VoxelStorage::Octree);
+    renderer
+        .write_voxels(
+            "sparse",
+            [0, 2, 0],
+            [dims[0], 4, 12],
+            &vec![3; (dims[0] * 48) as usize],
+        )
+        .unwrap();
+    compare(&mut renderer);
+}
+
+#[test]
+fn lights_surfaces_past_the_linear_z_range() {
+    let Some(mut renderer) = headless() else {
+        return;
+    };
+    // A wall 150 units away, lit only by a point light just in front of it.
+    let scene = Scene {
+        palette: palette(),
+        objects: vec![object(
+            "wall",
+            Transform::from_translation_scale([0.0, 0.0, -150.0], [40.0, 40.0, 1.0]),
+            [4, 4, 1],
+            vec![1; 16],
+        )],
+    };
+    renderer.upload_scene(&scene).unwrap();
+    let lighting = Lighting {
+        sun: Sun {
+            direction: [0.0, -1.0, 0.0],
+            color: [0.0; 3],
+            intensity: 0.0,
+        },
+        ambient: [0.0; 3],
+        shadows: Shadows::default(),
+    };
+    let lights = [Light::Point(PointLight {
+        position: [0.0, 0.0, -146.0],
+        color: [1.0; 3],
+        intensity: 20.0,
+        range: 10.0,
+    })];
+    renderer.set_lighting(&lighting).unwrap();
+    renderer.add_light("lamp", &lights[0]).unwrap();
+
+    let eye = Vec3::ZERO;
+    let view = Mat4::look_at_rh(eye, Vec3::NEG_Z, Vec3::Y);
+    let projection = Mat4::perspective_rh(
+        60f32.to_radians(),
+        WIDTH as f32 / HEIGHT as f32,
+        0.1,
+        1000.0,
+    );
+    let vp_matrix = (projection * view).to_cols_array();
+    renderer
+        .render(vp_matrix, eye.to_array(), PresentTarget::Lit)
+        .unwrap();
+    let lit = pollster::block_on(renderer.read_pixels(PresentTarget::Lit)).unwrap();
+    let reference = render_reference(
+        &scene,
+        vp_matrix,
+        eye.to_array(),
+        WIDTH,
+        HEIGHT,
+        AoMode::Off,
+    );
+    let lit_reference = shade_reference(&reference, &scene, &lighting, &lights, vp_matrix).concat();
+    assert_close("lit", &widen(&lit), &widen(&lit_reference), 4, 2);
+    let center = ((WIDTH / 2 + WIDTH * (HEIGHT / 2)) * 4) as usize;
+    assert!(lit[center] > 100, "{:?}", &lit[center..center + 4]);
+}
+
+#[test]
+fn shadows_from_many_objects_follow_the_hierarchy() {
+    let Some(mut renderer) = headless() else {
+        return;
+    };
+    // A floor under a 4x4 grid of pillars, enough objects for several levels of hierarchy.
+    let mut scene = Scene {
+        palette: palette(),
+        objects: vec![object(
+            "floor",
+            Transform::from_translation_scale([0.0, -0.25, 0.0], [8.0, 0.5, 8.0]),
+            [8, 1, 8],
+            vec![1; 64],
+        )],
+    };
+    for i in 0..16 {
+        let (x, z) = ((i % 4) as f32 * 2.0 - 3.0, (i / 4) as f32 * 2.0 - 3.0);
+        scene.objects.push(object(
+            &format!("pillar{i}"),
+            Transform::from_translation_scale([x, 1.0, z], [0.5, 2.0, 0.5]),
+            [1, 4, 1],
+            vec![2 + (i % 2) as u8; 4],
+        ));
+    }
+    renderer.upload_scene(&scene).unwrap();
+    let lighting = Lighting {
+        sun: Sun {
+            direction: [0.6, -1.0, 0.3],
+            color: [1.0; 3],
+            intensity: 1.0,
+        },
+        ambient: [0.1; 3],
+        shadows: Shadows {
+            mode: ShadowMode::Hard,
+            ..Shadows::default()
+        },
+    };
+    renderer.set_lighting(&lighting).unwrap();
+
+    let eye = Vec3::new(1.0, 9.0, 6.0);
+    let vp_matrix = view_projection(eye, Vec3::ZERO, WIDTH, HEIGHT);
+    let check = |renderer: &mut Renderer, scene: &Scene| {
+        renderer
+            .render(vp_matrix, eye.to_array(), PresentTarget::Lit)
+            .unwrap();
+        let lit = pollster::block_on(renderer.read_pixels(PresentTarget::Lit)).unwrap();
+        let reference =
+            render_reference(scene, vp_matrix, eye.to_array(), WIDTH, HEIGHT, AoMode::Off);
+        let lit_reference = shade_reference(&reference, scene, &lighting, &[], vp_matrix).concat();
+        assert_close("shadowed", &widen(&lit), &widen(&lit_reference), 4, 2);
+    };
+    check(&mut renderer, &scene);
+
+    // Moving a pillar refits the hierarchy, which shadow rays must see too.
+    let moved = Transform::from_translation_scale([0.0, 1.0, 0.0], [0.5, 2.0, 0.5]);
+    renderer.set_object_transform("pillar5", &moved).unwrap();
+    scene.objects[6].transform = moved;
+    check(&mut renderer, &scene);
+}
+
+#[test]
+fn rejects_invalid_scenes_on_upload() {
+    let Some(mut renderer) = headless() else {
+        return;
+    };
+    let scene = scene();
+    renderer.upload_scene(&scene).unwrap();
+    let eye = Vec3::new(2.0, 3.0, 5.0);
+    let vp_matrix = view_projection(eye, Vec3::ZERO, WIDTH, HEIGHT);
+    let render = |renderer: &mut Renderer| {
+        renderer
+            .render(vp_matrix, eye.to_array(), PresentTarget::Albedo)
+            .unwrap();
+        pollster::block_on(renderer.read_pixels(PresentTarget::Albedo)).unwrap()
+    };
+    let before = render(&mut renderer);
+
+    let mut broken = scene.clone();
+    broken.objects[0].voxels[5] = 9;
+    broken.objects[1].dims = [5, 1, 6];
+    assert_eq!(
+        renderer.upload_scene(&broken),
+        Err(SceneError::InvalidObject {
+            id: "cube".to_owned(),
+            field: ObjectField::Voxels,
+            reason: InvalidReason::PaletteIndexOutOfRange {
+                index: 9,
+                palette_len: 4
+            },
+        })
+    );
+    broken.objects[0].voxels[5] = 1;
+    assert!(matches!(
+        renderer.upload_scene(&broken),
+        Err(SceneError::InvalidObject {
+            field: ObjectField::Voxels,
+            reason: InvalidReason::LengthMismatch { .. },
+            ..
+        })
+    ));
+
+    // The scene uploaded before is left as it was.
+    assert_eq!(render(&mut renderer), before);
+}
+
+#[test]
+fn rejects_palettes_missing_indices_in_use() {
+    let Some(mut renderer) = headless() else {
+        return;
+    };
+    // Without a palette only empty objects can be added.
+    let mut obj = scene().objects.remove(1);
+    assert!(matches!(
+        renderer.add_object(&obj),
+        Err(SceneError::InvalidObject {
+            reason: InvalidReason::PaletteIndexOutOfRange { palette_len: 0, .. },
+            ..
+        })
+    ));
+    obj.voxels.fill(0);
+    renderer.add_object(&obj).unwrap();
+
+    let scene = scene();
+    renderer.upload_scene(&scene).unwrap();
+    // The cube uses indices up to 3, so a palette of 3 entries no longer covers it.
+    assert_eq!(
+        renderer.update_palette(&scene.palette[..3]),
+        Err(SceneError::InvalidObject {
+            id: "cube".to_owned(),
+            field: ObjectField::Voxels,
+            reason: InvalidReason::PaletteIndexOutOfRange {
+                index: 3,
+                palette_len: 3
+            },
+        })
+    );
+    renderer.remove_object("cube").unwrap();
+    renderer.update_palette(&scene.palette[..3]).unwrap();
+    renderer.update_palette(&scene.palette[..2]).unwrap_err();
+}
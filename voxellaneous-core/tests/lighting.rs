@@ -0,0 +1,142 @@
+mod common;
+
+use common::view_projection;
+use glam::Vec3;
+use voxellaneous_core::lighting::{bin_lights, TILE_SIZE};
+use voxellaneous_core::{Light, LightError, Lighting, PointLight, SpotLight};
+
+const WIDTH: u32 = 128;
+const HEIGHT: u32 = 64;
+
+fn point(position: [f32; 3], range: f32) -> Light {
+    Light::Point(PointLight {
+        position,
+        color: [1.0; 3],
+        intensity: 1.0,
+        range,
+    })
+}
+
+fn spot(inner_angle: f32, outer_angle: f32) -> Light {
+    Light::Spot(SpotLight {
+        position: [0.0; 3],
+        direction: [0.0, -1.0, 0.0],
+        color: [1.0; 3],
+        intensity: 1.0,
+        range: 5.0,
+        inner_angle,
+        outer_angle,
+    })
+}
+
+/// Lights listed for the tile containing `pixel`.
+fn tile_lights(bins: &voxellaneous_core::lighting::TileBins, pixel: [u32; 2]) -> &[u32] {
+    let tile = pixel[0] / TILE_SIZE + pixel[1] / TILE_SIZE * bins.tiles_x;
+    let [offset, count] = bins.ranges[tile as usize];
+    &bins.indices[offset as usize..(offset + count) as usize]
+}
+
+#[test]
+fn bins_lights_by_screen_footprint() {
+    let lights = [
+        // Small light at the center of the view.
+        point([0.0, 0.0, 0.0], 0.5),
+        // Small light off to the left.
+        point([-6.0, 0.0, 0.0], 0.5),
+        // Behind the camera and out of reach.
+        point([0.0, 0.0, 20.0], 2.0),
+        // Surrounds the camera, so it reaches every pixel.
+        point([0.0, 0.0, 9.0], 3.0),
+    ];
+    let bins = bin_lights(
+        &lights,
+        view_projection(Vec3::new(0.0, 0.0, 10.0), Vec3::ZERO, WIDTH, HEIGHT),
+        WIDTH,
+        HEIGHT,
+    );
+    assert_eq!((bins.tiles_x, bins.tiles_y), (8, 4));
+    assert_eq!(bins.ranges.len(), 32);
+
+    assert_eq!(tile_lights(&bins, [WIDTH / 2, HEIGHT / 2]), [0, 3]);
+    assert_eq!(tile_lights(&bins, [24, HEIGHT / 2]), [1, 3]);
+    assert_eq!(tile_lights(&bins, [8, HEIGHT / 2]), [3]);
+    assert_eq!(tile_lights(&bins, [WIDTH - 1, 0]), [3]);
+    assert!(bins.indices.iter().all(|&i| i != 2));
+}
+
+#[test]
+fn bins_nothing_for_empty_targets() {
+    let lights = [point([0.0; 3], 3.0)];
+    let vp_matrix = view_projection(Vec3::new(0.0, 0.0, 10.0), Vec3::ZERO, WIDTH, HEIGHT);
+    for (width, height) in [(0, HEIGHT), (WIDTH, 0), (0, 0)] {
+        let bins = bin_lights(&lights, vp_matrix, width, height);
+        assert_eq!((bins.tiles_x, bins.tiles_y), (0, 0));
+        assert!(bins.ranges.is_empty() && bins.indices.is_empty());
+    }
+}
+
+#[test]
+fn validates_lights() {
+    assert_eq!(point([0.0; 3], 1.0).validate("a"), Ok(()));
+    assert_eq!(spot(0.2, 0.4).validate("a"), Ok(()));
+    assert_eq!(
+        point([0.0, f32::NAN, 0.0], 1.0).validate("a"),
+        Err(LightError::NonFinite { id: "a".to_owned() })
+    );
+    assert_eq!(
+        point([0.0; 3], 0.0).validate("a"),
+        Err(LightError::InvalidRange {
+            id: "a".to_owned(),
+            range: 0.0
+        })
+    );
+    assert!(matches!(
+        spot(0.5, 0.4).validate("a"),
+        Err(LightError::InvalidCone { .. })
+    ));
+    assert!(matches!(
+        spot(0.2, 2.0).validate("a"),
+        Err(LightError::InvalidCone { .. })
+    ));
+    let Light::Point(mut dim) = point([0.0; 3], 1.0) else {
+        unreachable!()
+    };
+    dim.intensity = -1.0;
+    assert_eq!(
+        Light::Point(dim).validate("a"),
+        Err(LightError::NegativeIntensity {
+            id: "a".to_owned(),
+            intensity: -1.0
+        })
+    );
+}
+
+#[test]
+fn validates_the_sun() {
+    let non_finite = || LightError::NonFinite {
+        id: "sun".to_owned(),
+    };
+    let mut lighting = Lighting::default();
+    assert_eq!(lighting.validate(), Ok(()));
+
+    lighting.sun.direction = [0.0; 3];
+    assert_eq!(
+        lighting.validate(),
+        Err(LightError::ZeroDirection {
+            id: "sun".to_owned()
+        })
+    );
+    lighting.sun.direction = [f32::NAN, -1.0, 0.0];
+    assert_eq!(lighting.validate(), Err(non_finite()));
+
+    let mut lighting = Lighting::default();
+    lighting.ambient[1] = f32::INFINITY;
+    assert_eq!(lighting.validate(), Err(non_finite()));
+
+    let mut lighting = Lighting::default();
+    lighting.sun.intensity = -0.5;
+    assert_eq!(
+        lighting.validate().unwrap_err().to_string(),
+        "light 'sun' has intensity -0.5, expected a non-negative value"
+    );
+}
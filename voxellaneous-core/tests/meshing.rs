@@ -0,0 +1,85 @@
+use glam::Vec3;
+use voxellaneous_core::meshing::{greedy_mesh, Mesh};
+use voxellaneous_core::{Transform, VoxelObject};
+
+fn object(dims: [u32; 3], voxels: Vec<u8>) -> VoxelObject {
+    VoxelObject {
+        id: "test".to_owned(),
+        transform: Transform::IDENTITY,
+        dims,
+        voxels,
+    }
+}
+
+/// One line per quad: palette index, normal and corners in voxel-grid coordinates.
+fn snapshot(mesh: &Mesh, dims: [u32; 3]) -> String {
+    let dims = Vec3::from(dims.map(|d| d as f32));
+    let mut lines = Vec::new();
+    for quad in 0..mesh.positions.len() / 4 {
+        let corners: Vec<String> = (0..4)
+            .map(|k| {
+                let p = (Vec3::from(mesh.positions[quad * 4 + k]) + 0.5) * dims;
+                format!("({} {} {})", p.x, p.y, p.z)
+            })
+            .collect();
+        let n = mesh.normals[quad * 4];
+        lines.push(format!(
+            "{} [{} {} {}] {}",
+            mesh.palette_indices[quad * 4],
+            n[0],
+            n[1],
+            n[2],
+            corners.join(" ")
+        ));
+    }
+    lines.join("\n")
+}
+
+#[test]
+fn merges_faces_of_a_solid_box() {
+    let mesh = greedy_mesh(&object([2, 2, 2], vec![5; 8]));
+    assert_eq!(mesh.positions.len(), 24);
+    assert_eq!(mesh.indices.len(), 36);
+    assert!(mesh.palette_indices.iter().all(|&i| i == 5));
+}
+
+#[test]
+fn snapshot_of_two_colored_voxels() {
+    // Two voxels along X with different colors: the shared face is culled and nothing merges.
+    let mesh = greedy_mesh(&object([2, 1, 1], vec![1, 2]));
+    let expected = "\
+1 [-1 0 0] (0 0 0) (0 1 0) (0 1 1) (0 0 1)
+2 [1 0 0] (2 0 0) (2 1 0) (2 1 1) (2 0 1)
+1 [0 -1 0] (0 0 0) (0 0 1) (1 0 1) (1 0 0)
+2 [0 -1 0] (1 0 0) (1 0 1) (2 0 1) (2 0 0)
+1 [0 1 0] (0 1 0) (0 1 1) (1 1 1) (1 1 0)
+2 [0 1 0] (1 1 0) (1 1 1) (2 1 1) (2 1 0)
+1 [0 0 -1] (0 0 0) (1 0 0) (1 1 0) (0 1 0)
+2 [0 0 -1] (1 0 0) (2 0 0) (2 1 0) (1 1 0)
+1 [0 0 1] (0 0 1) (1 0 1) (1 1 1) (0 1 1)
+2 [0 0 1] (1 0 1) (2 0 1) (2 1 1) (1 1 1)";
+    assert_eq!(snapshot(&mesh, [2, 1, 1]), expected);
+}
+
+#[test]
+fn culls_hidden_faces_and_skips_empty_voxels() {
+    // An L shape in a 2x2x1 grid: three voxels, one empty corner.
+    let mesh = greedy_mesh(&object([2, 2, 1], vec![3, 3, 3, 0]));
+    // Two quads on each Z side plus six walls around the L; inner faces are culled.
+    let quads = mesh.positions.len() / 4;
+    assert_eq!(quads, 10);
+    assert!(greedy_mesh(&object([3, 3, 3], vec![0; 27])).is_empty());
+}
+
+#[test]
+fn triangles_face_outwards() {
+    let mesh = greedy_mesh(&object(
+        [3, 2, 2],
+        (0..12).map(|i| (i % 2 + 1) as u8).collect(),
+    ));
+    for tri in mesh.indices.chunks(3) {
+        let [a, b, c] = [0, 1, 2].map(|k| Vec3::from(mesh.positions[tri[k] as usize]));
+        let normal = Vec3::from(mesh.normals[tri[0] as usize]);
+        assert!((b - a).cross(c - a).dot(normal) > 0.0);
+    }
+}
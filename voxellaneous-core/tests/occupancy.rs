@@ -0,0 +1,71 @@
+use voxellaneous_core::occupancy::{level_dims, OccupancyPyramid, OCCUPANCY_LEVELS};
+
+/// Whether any voxel of `cell` of `level` is set, checked voxel by voxel.
+fn any_voxel(voxels: &[u8], dims: [u32; 3], level: u32, cell: [u32; 3]) -> bool {
+    let size = 1 << level;
+    let start = cell.map(|c| c * size);
+    (start[2]..(start[2] + size).min(dims[2])).any(|z| {
+        (start[1]..(start[1] + size).min(dims[1])).any(|y| {
+            (start[0]..(start[0] + size).min(dims[0]))
+                .any(|x| voxels[(x + dims[0] * (y + dims[1] * z)) as usize] != 0)
+        })
+    })
+}
+
+fn assert_matches_voxels(pyramid: &OccupancyPyramid, voxels: &[u8]) {
+    let dims = pyramid.dims();
+    for level in 0..OCCUPANCY_LEVELS {
+        let [nx, ny, nz] = level_dims(dims, level);
+        for z in 0..nz {
+            for y in 0..ny {
+                for x in 0..nx {
+                    assert_eq!(
+                        pyramid.is_occupied(level, [x, y, z]),
+                        any_voxel(voxels, dims, level, [x, y, z]),
+                        "level {level} cell {:?}",
+                        [x, y, z]
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn levels_cover_partial_cells() {
+    // Not a multiple of 8 along any axis, so the far cells of every level stick out.
+    let dims = [13, 7, 19];
+    assert_eq!(level_dims(dims, 3), [2, 1, 3]);
+    let mut voxels = vec![0u8; 13 * 7 * 19];
+    for (i, voxel) in voxels.iter_mut().enumerate() {
+        *voxel = (i % 37 == 0) as u8;
+    }
+    let pyramid = OccupancyPyramid::new(&voxels, dims);
+    assert_matches_voxels(&pyramid, &voxels);
+    assert!(pyramid.is_occupied(3, [1, 0, 2]));
+}
+
+#[test]
+fn set_voxel_updates_every_level() {
+    let dims = [20, 9, 11];
+    let mut voxels = vec![0u8; 20 * 9 * 11];
+    let mut pyramid = OccupancyPyramid::new(&voxels, dims);
+    assert!(pyramid.words().iter().all(|&w| w == 0));
+
+    let edits = [
+        ([19, 8, 10], 1),
+        ([17, 8, 10], 2),
+        ([0, 0, 0], 3),
+        ([19, 8, 10], 0),
+        ([4, 5, 6], 1),
+        ([0, 0, 0], 0),
+        ([4, 5, 6], 0),
+        ([5, 5, 6], 4),
+    ];
+    for ([x, y, z], value) in edits {
+        voxels[(x + 20 * (y + 9 * z)) as usize] = value;
+        pyramid.set_voxel([x, y, z], value != 0);
+        assert_matches_voxels(&pyramid, &voxels);
+    }
+    assert_eq!(pyramid, OccupancyPyramid::new(&voxels, dims));
+}
@@ -0,0 +1,190 @@
+mod common;
+
+use common::{object, random};
+use glam::Vec3;
+use voxellaneous_core::occupancy::OccupancyPyramid;
+use voxellaneous_core::octree::{SparseVoxelOctree, EMPTY_NODE, LEAF_NODE};
+use voxellaneous_core::reference::trace_object;
+use voxellaneous_core::{Transform, VoxelObject};
+
+/// A `dims` grid with a solid floor, a few clumps of two colours and scattered voxels.
+fn sparse(dims: [u32; 3], seed: u32) -> VoxelObject {
+    let mut random = random(seed);
+    let voxels = (0..dims.iter().product::<u32>())
+        .map(|i| {
+            let (x, y, z) = (i % dims[0], i / dims[0] % dims[1], i / (dims[0] * dims[1]));
+            if y < 3 {
+                1
+            } else if (x / 6 + y / 6 + z / 6) % 5 == 0 && y < 12 {
+                2 + (x / 6 % 2) as u8
+            } else {
+                (random() < 0.002) as u8 * 4
+            }
+        })
+        .collect();
+    object("sparse", Transform::IDENTITY, dims, voxels)
+}
+
+#[test]
+fn converts_to_and_from_objects() {
+    let obj = sparse([37, 20, 29], 3);
+    let octree = SparseVoxelOctree::from_object(&obj);
+    assert_eq!(octree.depth(), 6);
+    let back = octree.to_object(
+        "copy",
+        Transform::from_translation_scale([1.0; 3], [2.0; 3]),
+    );
+    assert_eq!((back.id.as_str(), back.dims), ("copy", obj.dims));
+    assert_eq!(back.voxels, obj.voxels);
+
+    let [nx, ny, nz] = obj.dims;
+    for z in 0..nz {
+        for y in 0..ny {
+            for x in 0..nx {
+                let expected = obj.voxels[(x + nx * (y + ny * z)) as usize];
+                assert_eq!(octree.get([x, y, z]), expected);
+            }
+        }
+    }
+    assert_eq!(octree.get([nx, 0, 0]), 0);
+    assert_eq!(octree.get([0, 0, 64]), 0);
+}
+
+#[test]
+fn stores_uniform_nodes_as_one_word() {
+    let solid = SparseVoxelOctree::new(&vec![5; 16 * 16 * 16], [16, 16, 16]);
+    assert_eq!(solid.nodes(), [LEAF_NODE | 5]);
+    let empty = SparseVoxelOctree::new(&vec![0; 10 * 3 * 7], [10, 3, 7]);
+    assert_eq!(empty.nodes(), [EMPTY_NODE]);
+    assert!(empty.to_voxels().iter().all(|&v| v == 0));
+
+    // A long, mostly empty grid takes far fewer words than it has voxels.
+    let dims = [512, 64, 64];
+    let len = dims.iter().product::<u32>() as usize;
+    let mut voxels = vec![0; len];
+    for i in (0..len).step_by(70001) {
+        voxels[i] = 1;
+    }
+    let octree = SparseVoxelOctree::new(&voxels, dims);
+    assert!(
+        octree.nodes().len() * 4 < len / 100,
+        "{}",
+        octree.nodes().len()
+    );
+    assert_eq!(octree.to_voxels(), voxels);
+}
+
+#[test]
+fn visits_solid_voxels_in_regions() {
+    let obj = sparse([40, 18, 33], 11);
+    let octree = SparseVoxelOctree::from_object(&obj);
+    let [nx, ny, _] = obj.dims;
+    for (origin, extent) in [
+        ([0, 0, 0], [40, 18, 33]),
+        ([5, 2, 7], [13, 9, 20]),
+        ([30, 10, 30], [100, 100, 100]),
+        ([39, 0, 0], [1, 1, 1]),
+    ] {
+        let mut visited = Vec::new();
+        octree.visit_region(origin, extent, |voxel, index| visited.push((voxel, index)));
+        visited.sort_unstable_by_key(|&([x, y, z], _)| (z, y, x));
+
+        let mut expected = Vec::new();
+        for z in origin[2]..(origin[2] + extent[2]).min(obj.dims[2]) {
+            for y in origin[1]..(origin[1] + extent[1]).min(ny) {
+                for x in origin[0]..(origin[0] + extent[0]).min(nx) {
+                    let index = obj.voxels[(x + nx * (y + ny * z)) as usize];
+                    if index != 0 {
+                        expected.push(([x, y, z], index));
+                    }
+                }
+            }
+        }
+        assert_eq!(visited, expected, "{origin:?} {extent:?}");
+    }
+}
+
+#[test]
+fn updates_edited_regions_in_place() {
+    let mut obj = sparse([40, 18, 33], 7);
+    let mut octree = SparseVoxelOctree::from_object(&obj);
+    let len = octree.nodes().len();
+    for (origin, extent, index) in [
+        // Fills a clump's corner, splitting uniform nodes.
+        ([5, 4, 5], [3, 2, 3], 4u8),
+        // Clears part of the floor.
+        ([16, 0, 16], [8, 3, 8], 0),
+        // Fills a whole empty node, which stays one word.
+        ([32, 16, 0], [8, 2, 8], 2),
+        // Puts the floor back, merging the cleared nodes again.
+        ([16, 0, 16], [8, 3, 8], 1),
+    ] {
+        let data = vec![index; extent.iter().product::<u32>() as usize];
+        obj.set_region(origin, extent, &data).unwrap();
+        let before = octree.nodes().to_vec();
+        let changed = octree.update_region(&obj.voxels, origin, extent);
+        assert_eq!(octree.to_voxels(), obj.voxels, "{origin:?} {extent:?}");
+
+        // Only the reported words changed, and far fewer than a rebuild writes.
+        let mut reported = vec![false; octree.nodes().len()];
+        for range in &changed {
+            reported[range.clone()].fill(true);
+        }
+        for (i, &word) in octree.nodes().iter().enumerate() {
+            assert!(reported[i] || before.get(i) == Some(&word), "word {i}");
+        }
+        let written: usize = changed.iter().map(|range| range.len()).sum();
+        assert!(written * 4 < len, "{written} of {len} words");
+    }
+    // Merged blocks are reused rather than growing the buffer edit after edit.
+    assert!(octree.nodes().len() < len + 64, "{}", octree.nodes().len());
+
+    // Clearing everything merges the whole tree into its root.
+    let cleared = vec![0; obj.voxels.len()];
+    let changed = octree.update_region(&cleared, [0; 3], obj.dims);
+    assert_eq!(octree.nodes()[0], EMPTY_NODE);
+    assert_eq!(changed[0].start, 0);
+    assert_eq!(octree.get([0, 0, 0]), 0);
+}
+
+#[test]
+fn raycasts_like_the_dense_traversal() {
+    let obj = sparse([48, 24, 40], 5);
+    let octree = SparseVoxelOctree::from_object(&obj);
+    let occupancy = OccupancyPyramid::new(&obj.voxels, obj.dims);
+
+    let mut random = random(17);
+    let mut hits = 0;
+    for i in 0..500 {
+        // Rays from outside toward points inside, and some from inside the cube.
+        let origin = if i % 5 == 0 {
+            Vec3::new(random(), random(), random()) - 0.5
+        } else {
+            (Vec3::new(random(), random(), random()) - 0.5).normalize() * 1.5
+        };
+        let target = Vec3::new(random(), random(), random()) - 0.5;
+        let dir = (target - origin).normalize();
+        let hit = octree.raycast(origin, dir, f32::MAX);
+        let expected = trace_object(&obj, &occupancy, origin, dir);
+        match (hit, expected) {
+            (Some(hit), Some(expected)) => {
+                assert_eq!(
+                    (hit.voxel, hit.normal, hit.palette_index),
+                    (expected.voxel, expected.normal, expected.palette_index),
+                    "{origin} {dir}"
+                );
+                assert!((hit.t - expected.t).abs() < 1e-4);
+                hits += 1;
+            }
+            (None, None) => {}
+            _ => panic!("{origin} {dir}: {hit:?} != {expected:?}"),
+        }
+    }
+    assert!(hits > 300, "{hits} hits");
+
+    // The ray stops at `t_limit`.
+    let origin = Vec3::new(0.0, 1.0, 0.0);
+    let hit = octree.raycast(origin, Vec3::NEG_Y, 2.0).unwrap();
+    assert_eq!(hit.normal, [0.0, 1.0, 0.0]);
+    assert!(octree.raycast(origin, Vec3::NEG_Y, hit.t - 0.01).is_none());
+}
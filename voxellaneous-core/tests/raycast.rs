@@ -0,0 +1,119 @@
+mod common;
+
+use common::{block, palette, random};
+use voxellaneous_core::{Raycaster, Scene, Transform};
+
+#[test]
+fn finds_nearest_hit_along_ray() {
+    // Glass in front of a wall, both straddling the z axis.
+    let scene = Scene {
+        palette: palette(),
+        objects: vec![
+            block("wall", [0.0, 0.0, -4.0], 2, 1),
+            block("glass", [0.0, 0.0, 0.0], 2, 2),
+        ],
+    };
+
+    let hit = scene
+        .raycast([0.5, 0.5, 5.0], [0.0, 0.0, -2.0], 100.0, &[])
+        .unwrap();
+    assert_eq!(hit.object_id, "glass");
+    assert_eq!(hit.voxel, [1, 1, 1]);
+    assert_eq!(hit.normal, [0.0, 0.0, 1.0]);
+    assert_eq!(hit.palette_index, 2);
+    assert!((hit.distance - 4.0).abs() < 1e-4);
+
+    let raycaster = Raycaster::new(&scene).ignore_palette_indices(&[2]);
+    let hit = raycaster
+        .raycast([0.5, 0.5, 5.0], [0.0, 0.0, -1.0], 100.0)
+        .unwrap();
+    assert_eq!(hit.object_id, "wall");
+    assert!((hit.distance - 8.0).abs() < 1e-4);
+    let through = scene.raycast([0.5, 0.5, 5.0], [0.0, 0.0, -1.0], 100.0, &[2]);
+    assert_eq!(through, Some(hit));
+
+    // Hits beyond `max_dist` and behind the origin do not count.
+    let origin = [0.5, 0.5, 5.0];
+    assert_eq!(scene.raycast(origin, [0.0, 0.0, -1.0], 3.9, &[]), None);
+    assert_eq!(scene.raycast(origin, [0.0, 0.0, 1.0], 100.0, &[]), None);
+    assert_eq!(scene.raycast(origin, [0.0; 3], 100.0, &[]), None);
+
+    let all = scene.raycast_all(origin, [0.0, 0.0, -1.0], 100.0, &[]);
+    let ids: Vec<_> = all.iter().map(|hit| hit.object_id.as_str()).collect();
+    assert_eq!(ids, ["glass", "wall"]);
+    let all = scene.raycast_all(origin, [0.0, 0.0, -1.0], 100.0, &[1, 2]);
+    assert!(all.is_empty());
+}
+
+#[test]
+fn transforms_hits_into_world_space() {
+    let mut obj = block("tilted", [0.0; 3], 4, 1);
+    // Rotated a quarter turn about y and stretched along its own x axis.
+    let (sin, cos) = std::f32::consts::FRAC_PI_4.sin_cos();
+    obj.transform = Transform::Trs {
+        translation: [0.0, 0.0, 0.0],
+        rotation: [0.0, sin, 0.0, cos],
+        scale: [8.0, 4.0, 4.0],
+    };
+    let scene = Scene {
+        palette: palette(),
+        objects: vec![obj],
+    };
+
+    // Local +x now points along world -z, so the ray enters the object's +x face.
+    let hit = scene
+        .raycast([0.1, 0.1, -10.0], [0.0, 0.0, 1.0], 100.0, &[])
+        .unwrap();
+    assert_eq!(hit.voxel[0], 3);
+    assert!((hit.distance - 6.0).abs() < 1e-4);
+    let normal = hit.normal;
+    assert!(normal[0].abs() < 1e-5 && normal[1].abs() < 1e-5);
+    assert!((normal[2] + 1.0).abs() < 1e-5);
+}
+
+#[test]
+fn hierarchy_matches_brute_force() {
+    // A deterministic jumble of small blocks, some hollow.
+    let mut random = random(12345);
+    let objects: Vec<_> = (0..60)
+        .map(|i| {
+            let translation = [0, 1, 2].map(|_| random() * 20.0 - 10.0);
+            let mut obj = block(&format!("block{i}"), translation, 3, 1);
+            obj.voxels[13] = 2;
+            if i % 3 == 0 {
+                obj.voxels.iter_mut().step_by(2).for_each(|v| *v = 0);
+            }
+            obj
+        })
+        .collect();
+    let scene = Scene {
+        palette: palette(),
+        objects,
+    };
+    let raycaster = Raycaster::new(&scene);
+
+    for _ in 0..200 {
+        let origin = [0, 1, 2].map(|_| random() * 30.0 - 15.0);
+        let dir = [0, 1, 2].map(|_| random() * 2.0 - 1.0);
+        let all = raycaster.raycast_all(origin, dir, 40.0);
+
+        // Every object on its own gives the same hits.
+        let mut expected: Vec<_> = scene
+            .objects
+            .iter()
+            .filter_map(|obj| {
+                let single = Scene {
+                    palette: palette(),
+                    objects: vec![obj.clone()],
+                };
+                single.raycast(origin, dir, 40.0, &[])
+            })
+            .collect();
+        expected.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        assert_eq!(all, expected);
+        assert_eq!(
+            raycaster.raycast(origin, dir, 40.0),
+            expected.first().cloned()
+        );
+    }
+}
@@ -0,0 +1,383 @@
+mod common;
+
+use common::{object, palette, random, view_projection};
+use glam::{Mat4, Vec3};
+use voxellaneous_core::occupancy::OccupancyPyramid;
+use voxellaneous_core::reference::{render_reference, shade_reference, trace_object, GBufferImage};
+use voxellaneous_core::{AoMode, Lighting, Scene, ShadowMode, Shadows, Sun, Transform, RGBA};
+
+const WIDTH: u32 = 24;
+const HEIGHT: u32 = 12;
+
+fn render(scene: &Scene, eye: Vec3) -> GBufferImage {
+    render_reference(
+        scene,
+        view_projection(eye, Vec3::ZERO, WIDTH, HEIGHT),
+        eye.to_array(),
+        WIDTH,
+        HEIGHT,
+        AoMode::Off,
+    )
+}
+
+/// One character per pixel: the palette index whose color landed in the albedo target.
+fn albedo_snapshot(image: &GBufferImage, palette: &[RGBA]) -> String {
+    image
+        .albedo
+        .chunks(image.width as usize)
+        .map(|row| {
+            row.iter()
+                .map(
+                    |&[r, g, b, a]| match palette.iter().position(|&c| c == RGBA(r, g, b, a)) {
+                        Some(0) | None => '.',
+                        Some(i) => char::from_digit(i as u32, 10).unwrap(),
+                    },
+                )
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn snapshot_of_two_blocks() {
+    // A red/green split block in front of a blue slab that pokes out on the right.
+    let split: Vec<u8> = (0..64).map(|i| if i % 4 < 2 { 1 } else { 2 }).collect();
+    let scene = Scene {
+        palette: palette(),
+        objects: vec![
+            object(
+                "split",
+                Transform::from_translation_scale([0.0, 0.0, 0.0], [2.0, 2.0, 2.0]),
+                [4, 4, 4],
+                split,
+            ),
+            object(
+                "slab",
+                Transform::from_translation_scale([1.5, 0.0, -2.0], [4.0, 1.0, 1.0]),
+                [4, 1, 1],
+                vec![3; 4],
+            ),
+        ],
+    };
+    let image = render(&scene, Vec3::new(0.0, 0.0, 5.0));
+    let expected = "\
+........................
+........................
+........................
+.........111222.........
+.........111222.........
+.........111222333......
+.........111222333......
+.........111222.........
+.........111222.........
+........................
+........................
+........................";
+    assert_eq!(albedo_snapshot(&image, &scene.palette), expected);
+}
+
+#[test]
+fn encodes_normal_linear_z_and_depth() {
+    let scene = Scene {
+        palette: palette(),
+        objects: vec![object(
+            "cube",
+            Transform::from_translation_scale([0.0, 0.0, 0.0], [2.0, 2.0, 2.0]),
+            [4, 4, 4],
+            vec![1; 64],
+        )],
+    };
+    let image = render(&scene, Vec3::new(0.0, 0.0, 5.0));
+    let pixel = (10 + 6 * WIDTH) as usize;
+    assert_eq!(image.albedo[pixel], [255, 0, 0, 255]);
+    // The ray hits the voxel it enters through, on the cube's front face.
+    assert_eq!(image.normal[pixel], [128, 128, 255, 255]);
+    let linear_z = image.linear_z[pixel] as f32 / 65535.0 * 100.0;
+    assert!((4.0..4.1).contains(&linear_z), "{linear_z}");
+
+    // Depth is that of the voxel hit, here on the cube's front face at z = 1.
+    let face = Mat4::from_cols_array(&view_projection(
+        Vec3::new(0.0, 0.0, 5.0),
+        Vec3::ZERO,
+        WIDTH,
+        HEIGHT,
+    )) * Vec3::new(0.0, 0.0, 1.0).extend(1.0);
+    assert!((image.depth[pixel] - face.z / face.w).abs() < 1e-5);
+
+    // Uncovered pixels keep the clear values.
+    assert_eq!(image.albedo[0], [0, 0, 0, 255]);
+    assert_eq!(image.normal[0], [0, 0, 0, 255]);
+    assert_eq!(image.linear_z[0], 0);
+    assert_eq!(image.depth[0], 1.0);
+}
+
+#[test]
+fn nearest_proxy_cube_wins_regardless_of_draw_order() {
+    let near = object(
+        "near",
+        Transform::from_translation_scale([0.0, 0.0, 1.0], [1.0, 1.0, 1.0]),
+        [1, 1, 1],
+        vec![2],
+    );
+    let far = object(
+        "far",
+        Transform::from_translation_scale([0.0, 0.0, -1.0], [4.0, 4.0, 1.0]),
+        [2, 2, 1],
+        vec![3; 4],
+    );
+    let eye = Vec3::new(0.0, 0.0, 5.0);
+    let front_to_back = render(
+        &Scene {
+            palette: palette(),
+            objects: vec![near.clone(), far.clone()],
+        },
+        eye,
+    );
+    let back_to_front = render(
+        &Scene {
+            palette: palette(),
+            objects: vec![far, near],
+        },
+        eye,
+    );
+    assert_eq!(front_to_back, back_to_front);
+    let center = (WIDTH / 2 + HEIGHT / 2 * WIDTH) as usize;
+    assert_eq!(front_to_back.albedo[center], [0, 255, 0, 255]);
+}
+
+/// A large object that is empty except for its back layer, with a small block nested inside.
+fn nested_scene(block_first: bool) -> Scene {
+    let shell_voxels = (0..64).map(|i| if i < 16 { 3 } else { 0 }).collect();
+    let shell = object(
+        "shell",
+        Transform::from_translation_scale([0.0, 0.0, 0.0], [4.0, 4.0, 4.0]),
+        [4, 4, 4],
+        shell_voxels,
+    );
+    let inner = object(
+        "inner",
+        Transform::from_translation_scale([0.0, 0.0, 0.0], [1.0, 1.0, 1.0]),
+        [1, 1, 1],
+        vec![2],
+    );
+    let objects = if block_first {
+        vec![inner, shell]
+    } else {
+        vec![shell, inner]
+    };
+    Scene {
+        palette: palette(),
+        objects,
+    }
+}
+
+#[test]
+fn nested_objects_sort_by_surface() {
+    // The shell's proxy cube is in front of the inner block, but its voxels are behind it.
+    let eye = Vec3::new(0.0, 0.0, 5.0);
+    let block_first = render(&nested_scene(true), eye);
+    assert_eq!(block_first, render(&nested_scene(false), eye));
+    let center = (WIDTH / 2 + HEIGHT / 2 * WIDTH) as usize;
+    assert_eq!(block_first.albedo[center], [0, 255, 0, 255]);
+    assert_eq!(block_first.albedo[center - 3], [0, 0, 255, 255]);
+
+    let inner_front = Mat4::from_cols_array(&view_projection(eye, Vec3::ZERO, WIDTH, HEIGHT))
+        .project_point3(Vec3::Z * 0.5);
+    assert!((block_first.depth[center] - inner_front.z).abs() < 1e-5);
+}
+
+#[test]
+fn traces_through_empty_voxels() {
+    // Only the back layer of a 4x4x4 grid is solid.
+    let voxels = (0..64).map(|i| if i < 16 { 3 } else { 0 }).collect();
+    let obj = object("wall", Transform::IDENTITY, [4, 4, 4], voxels);
+    let occupancy = OccupancyPyramid::new(&obj.voxels, obj.dims);
+    let dir = Vec3::new(0.05, 0.03, -1.0).normalize();
+    let hit = trace_object(&obj, &occupancy, Vec3::new(0.1, 0.05, 2.0), dir).unwrap();
+    assert_eq!(hit.palette_index, 3);
+    assert_eq!(hit.voxel[2], 0);
+    assert_eq!(hit.normal, [0.0, 0.0, 1.0]);
+
+    // Rays that pass by or point away from the cube are discarded.
+    assert!(trace_object(&obj, &occupancy, Vec3::new(2.0, 0.0, 2.0), dir).is_none());
+    assert!(trace_object(&obj, &occupancy, Vec3::new(0.1, 0.05, 2.0), -dir).is_none());
+}
+
+#[test]
+fn skips_empty_cells_without_missing_voxels() {
+    // A few scattered voxels and one solid slab in a mostly empty 40x40x40 grid.
+    let dims = [40, 40, 40];
+    let mut random = random(99);
+    let voxels: Vec<u8> = (0..40 * 40 * 40)
+        .map(|i| {
+            let slab = i / (40 * 40) == 30;
+            (slab || random() < 0.002) as u8 * 2
+        })
+        .collect();
+    let obj = object("sparse", Transform::IDENTITY, dims, voxels);
+    let occupancy = OccupancyPyramid::new(&obj.voxels, dims);
+    // With every cell marked occupied, the traversal visits voxels one by one.
+    let dense = OccupancyPyramid::new(&vec![1; obj.voxels.len()], dims);
+
+    let (mut steps, mut dense_steps) = (0, 0);
+    for _ in 0..500 {
+        let origin = Vec3::new(random() - 0.5, random() - 0.5, 1.0) * 2.0;
+        let target = Vec3::new(random(), random(), random()) - 0.5;
+        let dir = (target - origin).normalize();
+        let hit = trace_object(&obj, &occupancy, origin, dir);
+        let expected = trace_object(&obj, &dense, origin, dir);
+        match (hit, expected) {
+            (Some(hit), Some(expected)) => {
+                assert_eq!(
+                    (hit.voxel, hit.normal, hit.palette_index),
+                    (expected.voxel, expected.normal, expected.palette_index)
+                );
+                assert!((hit.t - expected.t).abs() < 1e-4);
+                steps += hit.steps;
+                dense_steps += expected.steps;
+            }
+            (None, None) => {}
+            _ => panic!("{origin} {dir}: {hit:?} != {expected:?}"),
+        }
+    }
+    assert!(steps * 2 < dense_steps, "{steps} vs {dense_steps} steps");
+}
+
+#[test]
+fn shades_with_sun_and_ambient() {
+    // Background, a red texel facing the sun and a gray one facing away from it.
+    let image = GBufferImage {
+        width: 3,
+        height: 1,
+        albedo: vec![[0, 0, 0, 255], [255, 0, 0, 255], [188, 188, 188, 255]],
+        normal: vec![[0, 0, 0, 255], [128, 255, 128, 255], [128, 0, 128, 255]],
+        linear_z: vec![0, 1000, 1000],
+        ao: vec![0, 255, 255],
+        steps: vec![0, 1, 1],
+        depth: vec![1.0, 0.5, 0.5],
+    };
+    let lighting = Lighting {
+        sun: Sun {
+            direction: [0.0, -2.0, 0.0],
+            color: [1.0, 0.5, 0.5],
+            intensity: 0.5,
+        },
+        ambient: [0.25; 3],
+        shadows: Shadows::default(),
+    };
+    let lit = shade_reference(
+        &image,
+        &Scene {
+            palette: palette(),
+            objects: Vec::new(),
+        },
+        &lighting,
+        &[],
+        Mat4::IDENTITY.to_cols_array(),
+    );
+    assert_eq!(lit[0], [0, 0, 0, 255]);
+    // Linear red of 1.0 under 0.25 ambient plus a 0.5 sun.
+    assert_eq!(lit[1], [225, 0, 0, 255]);
+    // sRGB 188 is linear 0.5; ambient alone leaves 0.125.
+    assert_eq!(lit[2], [99, 99, 99, 255]);
+}
+
+#[test]
+fn casts_voxel_shadows() {
+    // A block floating over a floor, seen from above, with the sun shining down and along +X.
+    let scene = Scene {
+        palette: palette(),
+        objects: vec![
+            object(
+                "floor",
+                Transform::from_translation_scale([0.0, 0.0, 0.0], [8.0, 0.5, 8.0]),
+                [8, 1, 8],
+                vec![1; 64],
+            ),
+            object(
+                "block",
+                Transform::from_translation_scale([0.0, 2.0, 0.0], [2.0, 2.0, 2.0]),
+                [2, 2, 2],
+                vec![2; 8],
+            ),
+        ],
+    };
+    let eye = Vec3::new(0.0, 10.0, 0.0);
+    let view = Mat4::look_at_rh(eye, Vec3::ZERO, Vec3::NEG_Z);
+    let projection =
+        Mat4::perspective_rh(60f32.to_radians(), WIDTH as f32 / HEIGHT as f32, 0.1, 100.0);
+    let vp = projection * view;
+    let vp_matrix = vp.to_cols_array();
+    let image = render_reference(
+        &scene,
+        vp_matrix,
+        eye.to_array(),
+        WIDTH,
+        HEIGHT,
+        AoMode::Off,
+    );
+    let pixel_of = |point: Vec3| {
+        let ndc = vp.project_point3(point);
+        let x = ((ndc.x * 0.5 + 0.5) * WIDTH as f32) as u32;
+        let y = ((0.5 - ndc.y * 0.5) * HEIGHT as f32) as u32;
+        (x + y * WIDTH) as usize
+    };
+    // The block's shadow on the floor top spans x in [-0.25, 3.75] and z in [-1, 1].
+    let shadowed = pixel_of(Vec3::new(2.5, 0.25, 0.0));
+    let open = pixel_of(Vec3::new(-2.5, 0.25, 0.0));
+
+    let shade = |mode| {
+        let lighting = Lighting {
+            sun: Sun {
+                direction: [1.0, -1.0, 0.0],
+                color: [1.0; 3],
+                intensity: 1.0,
+            },
+            ambient: [0.1; 3],
+            shadows: Shadows {
+                mode,
+                ..Shadows::default()
+            },
+        };
+        shade_reference(&image, &scene, &lighting, &[], vp_matrix)
+    };
+    let unshadowed = shade(ShadowMode::Off);
+    let hard = shade(ShadowMode::Hard);
+    assert_eq!(unshadowed[shadowed], unshadowed[open]);
+    assert_eq!(hard[open], unshadowed[open]);
+    // Only ambient light reaches the shadowed floor: linear 0.1 is sRGB 89.
+    assert_eq!(hard[shadowed], [89, 0, 0, 255]);
+
+    // Soft shadows with a tiny sun match hard ones away from the penumbra.
+    let soft = shade(ShadowMode::Soft);
+    assert_eq!(soft[shadowed], hard[shadowed]);
+    assert_eq!(soft[open], hard[open]);
+}
+
+#[test]
+fn traverses_the_full_diagonal_of_large_grids() {
+    for dims in [[512, 64, 64], [64, 300, 64], [40, 40, 700]] {
+        // Only the voxel in the far corner is solid.
+        let len = dims.iter().product::<u32>() as usize;
+        let mut voxels = vec![0; len];
+        voxels[len - 1] = 1;
+        let obj = object("long", Transform::IDENTITY, dims, voxels);
+        let occupancy = OccupancyPyramid::new(&obj.voxels, dims);
+        // With every cell marked occupied the ray is walked voxel by voxel, far more than 256.
+        let dense = OccupancyPyramid::new(&vec![1; len], dims);
+
+        // From just outside the near corner to the centre of the far one.
+        let [nx, ny, nz] = dims.map(|d| d as f32);
+        let target = Vec3::new(0.5 - 0.5 / nx, 0.5 - 0.5 / ny, 0.5 - 0.5 / nz);
+        let origin = Vec3::splat(-0.5) - (target + 0.5) * 0.01;
+        let dir = (target - origin).normalize();
+        for pyramid in [&occupancy, &dense] {
+            let hit = trace_object(&obj, pyramid, origin, dir).unwrap();
+            assert_eq!(hit.voxel, dims.map(|d| d - 1), "{dims:?}");
+            assert_eq!(hit.palette_index, 1);
+        }
+        let steps = trace_object(&obj, &dense, origin, dir).unwrap().steps;
+        assert!(steps > 256 && steps <= dims.iter().sum(), "{steps} steps");
+    }
+}
@@ -0,0 +1,161 @@
+use voxellaneous_core::{
+    InvalidReason, ObjectField, Scene, SceneError, Transform, VoxelObject, RGBA,
+};
+
+fn object(id: &str, dims: [u32; 3], voxels: Vec<u8>) -> VoxelObject {
+    VoxelObject {
+        id: id.to_owned(),
+        transform: Transform::IDENTITY,
+        dims,
+        voxels,
+    }
+}
+
+fn scene(objects: Vec<VoxelObject>) -> Scene {
+    Scene {
+        palette: vec![RGBA(0, 0, 0, 0), RGBA(255, 0, 0, 255), RGBA(0, 255, 0, 255)],
+        objects,
+    }
+}
+
+fn invalid(id: &str, field: ObjectField, reason: InvalidReason) -> Result<(), SceneError> {
+    Err(SceneError::InvalidObject {
+        id: id.to_owned(),
+        field,
+        reason,
+    })
+}
+
+#[test]
+fn accepts_valid_scenes() {
+    let scene = scene(vec![
+        object("a", [2, 1, 1], vec![1, 2]),
+        object("b", [1, 1, 3], vec![0, 0, 1]),
+    ]);
+    assert_eq!(scene.validate(), Ok(()));
+}
+
+#[test]
+fn rejects_large_palettes() {
+    let mut scene = scene(vec![]);
+    scene.palette = vec![RGBA(1, 2, 3, 255); 257];
+    assert_eq!(
+        scene.validate(),
+        Err(SceneError::PaletteTooLarge { len: 257 })
+    );
+    scene.palette.pop();
+    assert_eq!(scene.validate(), Ok(()));
+}
+
+#[test]
+fn rejects_voxel_counts_not_matching_dims() {
+    let short = scene(vec![object("short", [2, 2, 2], vec![1; 7])]);
+    assert_eq!(
+        short.validate(),
+        invalid(
+            "short",
+            ObjectField::Voxels,
+            InvalidReason::LengthMismatch {
+                expected: 8,
+                actual: 7
+            }
+        )
+    );
+
+    let flat = scene(vec![object("flat", [2, 0, 2], vec![])]);
+    assert_eq!(
+        flat.validate(),
+        invalid("flat", ObjectField::Dims, InvalidReason::ZeroDimension)
+    );
+
+    let huge = scene(vec![object("huge", [u32::MAX; 3], vec![])]);
+    let error = huge.validate().unwrap_err();
+    assert_eq!(
+        error,
+        SceneError::InvalidObject {
+            id: "huge".to_owned(),
+            field: ObjectField::Dims,
+            reason: InvalidReason::VoxelCountOverflow,
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "object 'huge': dims holds more voxels than can be addressed"
+    );
+}
+
+#[test]
+fn rejects_palette_indices_out_of_range() {
+    let scene = scene(vec![object("bright", [3, 1, 1], vec![0, 2, 3])]);
+    let error = scene.validate().unwrap_err();
+    assert_eq!(
+        error,
+        SceneError::InvalidObject {
+            id: "bright".to_owned(),
+            field: ObjectField::Voxels,
+            reason: InvalidReason::PaletteIndexOutOfRange {
+                index: 3,
+                palette_len: 3
+            },
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "object 'bright': voxels references palette index 3 but the palette has 3 entries"
+    );
+}
+
+#[test]
+fn rejects_duplicate_ids() {
+    let scene = scene(vec![
+        object("twin", [1, 1, 1], vec![1]),
+        object("other", [1, 1, 1], vec![1]),
+        object("twin", [1, 1, 1], vec![2]),
+    ]);
+    assert_eq!(
+        scene.validate(),
+        invalid("twin", ObjectField::Id, InvalidReason::Duplicate)
+    );
+}
+
+#[test]
+fn rejects_singular_and_non_finite_transforms() {
+    let mut flat = object("flat", [1, 1, 1], vec![1]);
+    flat.transform = Transform::from_translation_scale([0.0; 3], [1.0, 0.0, 1.0]);
+    assert_eq!(
+        scene(vec![flat]).validate(),
+        invalid("flat", ObjectField::Transform, InvalidReason::Singular)
+    );
+
+    let mut lost = object("lost", [1, 1, 1], vec![1]);
+    lost.transform = Transform::from_translation_scale([f32::NAN, 0.0, 0.0], [1.0; 3]);
+    assert_eq!(
+        scene(vec![lost]).validate(),
+        invalid("lost", ObjectField::Transform, InvalidReason::NonFinite)
+    );
+}
+
+#[test]
+fn rejects_objects_with_stale_matrix_fields() {
+    let current = r#"{"id": "a", "transform": {"trs": {"translation": [1, 2, 3],
+        "rotation": [0, 0, 0, 1], "scale": [1, 1, 1]}}, "dims": [1, 1, 1], "voxels": [1]}"#;
+    let obj: VoxelObject = serde_json::from_str(current).unwrap();
+    assert_eq!(
+        obj.transform,
+        Transform::from_translation_scale([1.0, 2.0, 3.0], [1.0; 3])
+    );
+
+    let identity = "[1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1]";
+    let stale = format!(
+        r#"{{"id": "a", "model_matrix": {identity}, "inv_model_matrix": {identity},
+        "dims": [1, 1, 1], "voxels": [1]}}"#
+    );
+    assert!(serde_json::from_str::<VoxelObject>(&stale).is_err());
+    // Nor may the old fields ride along with a transform.
+    let mixed = current.replacen(
+        "\"dims\"",
+        &format!("\"model_matrix\": {identity}, \"dims\""),
+        1,
+    );
+    assert!(serde_json::from_str::<VoxelObject>(&mixed).is_err());
+}
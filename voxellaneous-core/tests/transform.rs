@@ -0,0 +1,99 @@
+use glam::{Mat4, Quat, Vec3, Vec4};
+use voxellaneous_core::Transform;
+
+fn trs(translation: [f32; 3], axis: Vec3, angle: f32, scale: [f32; 3]) -> Transform {
+    Transform::Trs {
+        translation,
+        rotation: Quat::from_axis_angle(axis.normalize(), angle).to_array(),
+        scale,
+    }
+}
+
+fn assert_trs_eq(a: Transform, b: Transform) {
+    let (
+        Transform::Trs {
+            translation: ta,
+            rotation: ra,
+            scale: sa,
+        },
+        Transform::Trs {
+            translation: tb,
+            rotation: rb,
+            scale: sb,
+        },
+    ) = (a, b)
+    else {
+        panic!("{a:?} and {b:?} are not both TRS");
+    };
+    assert!(
+        Vec3::from(ta).abs_diff_eq(Vec3::from(tb), 1e-5),
+        "{a:?} != {b:?}"
+    );
+    assert!(
+        Vec3::from(sa).abs_diff_eq(Vec3::from(sb), 1e-5),
+        "{a:?} != {b:?}"
+    );
+    // `q` and `-q` are the same rotation.
+    let (ra, rb) = (Quat::from_array(ra), Quat::from_array(rb));
+    assert!(ra.dot(rb).abs() > 1.0 - 1e-5, "{a:?} != {b:?}");
+}
+
+#[test]
+fn decomposes_matrices_back_to_trs() {
+    for transform in [
+        Transform::IDENTITY,
+        Transform::from_translation_scale([1.0, -2.0, 3.5], [2.0, 0.5, 4.0]),
+        trs([0.0, 5.0, -1.0], Vec3::Y, 1.2, [1.0, 1.0, 1.0]),
+        trs(
+            [-3.0, 0.25, 8.0],
+            Vec3::new(1.0, 2.0, -0.5),
+            2.7,
+            [0.3, 6.0, 1.5],
+        ),
+    ] {
+        let matrix = Transform::Matrix(transform.matrix().to_cols_array());
+        assert_trs_eq(matrix.to_trs(), transform);
+        // Already TRS transforms come back unchanged.
+        assert_eq!(transform.to_trs(), transform);
+    }
+}
+
+#[test]
+fn inverts_trs_and_raw_matrices() {
+    let sheared = Mat4::from_cols(
+        Vec4::new(1.0, 0.0, 0.0, 0.0),
+        Vec4::new(0.7, 2.0, 0.0, 0.0),
+        Vec4::new(0.0, -0.4, 0.5, 0.0),
+        Vec4::new(3.0, 1.0, -2.0, 1.0),
+    );
+    for transform in [
+        Transform::IDENTITY,
+        trs(
+            [-3.0, 0.25, 8.0],
+            Vec3::new(1.0, 2.0, -0.5),
+            2.7,
+            [0.3, 6.0, 1.5],
+        ),
+        Transform::Matrix(sheared.to_cols_array()),
+    ] {
+        let product = transform.matrix() * transform.inverse_matrix();
+        assert!(product.abs_diff_eq(Mat4::IDENTITY, 1e-5), "{transform:?}");
+        let product = transform.inverse_matrix() * transform.matrix();
+        assert!(product.abs_diff_eq(Mat4::IDENTITY, 1e-5), "{transform:?}");
+    }
+}
+
+#[test]
+fn normalizes_rotations() {
+    // A quaternion scaled by 3 is the same rotation.
+    let unit = Quat::from_axis_angle(Vec3::X, 0.8);
+    let scaled = Transform::Trs {
+        translation: [0.0; 3],
+        rotation: (unit * 3.0).to_array(),
+        scale: [1.0; 3],
+    };
+    assert!(scaled.matrix().abs_diff_eq(Mat4::from_quat(unit), 1e-5));
+    assert!(scaled
+        .inverse_matrix()
+        .abs_diff_eq(Mat4::from_quat(unit.inverse()), 1e-5));
+}
@@ -0,0 +1,328 @@
+use glam::{Mat4, Quat, Vec3};
+use voxellaneous_core::vox::{read_vox, write_vox, VoxError};
+use voxellaneous_core::{Scene, Transform, VoxelObject, RGBA};
+
+fn chunk(id: &[u8; 4], content: &[u8], children: &[u8]) -> Vec<u8> {
+    let mut bytes = id.to_vec();
+    bytes.extend((content.len() as i32).to_le_bytes());
+    bytes.extend((children.len() as i32).to_le_bytes());
+    bytes.extend(content);
+    bytes.extend(children);
+    bytes
+}
+
+fn file(children: &[Vec<u8>]) -> Vec<u8> {
+    let mut bytes = b"VOX ".to_vec();
+    bytes.extend(150i32.to_le_bytes());
+    bytes.extend(chunk(b"MAIN", &[], &children.concat()));
+    bytes
+}
+
+fn ints(values: &[i32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn dict(pairs: &[(&str, &str)]) -> Vec<u8> {
+    let mut bytes = ints(&[pairs.len() as i32]);
+    for (key, value) in pairs {
+        for s in [key, value] {
+            bytes.extend(ints(&[s.len() as i32]));
+            bytes.extend(s.as_bytes());
+        }
+    }
+    bytes
+}
+
+fn size(x: i32, y: i32, z: i32) -> Vec<u8> {
+    chunk(b"SIZE", &ints(&[x, y, z]), &[])
+}
+
+fn xyzi(voxels: &[[u8; 4]]) -> Vec<u8> {
+    let mut content = ints(&[voxels.len() as i32]);
+    content.extend(voxels.concat());
+    chunk(b"XYZI", &content, &[])
+}
+
+fn transform(node_id: i32, name: &str, child: i32, frame: &[(&str, &str)]) -> Vec<u8> {
+    let mut content = ints(&[node_id]);
+    content.extend(dict(&[("_name", name)]));
+    content.extend(ints(&[child, -1, 0, 1]));
+    content.extend(dict(frame));
+    chunk(b"nTRN", &content, &[])
+}
+
+fn group(node_id: i32, children: &[i32]) -> Vec<u8> {
+    let mut content = ints(&[node_id]);
+    content.extend(dict(&[]));
+    content.extend(ints(&[children.len() as i32]));
+    content.extend(ints(children));
+    chunk(b"nGRP", &content, &[])
+}
+
+fn shape(node_id: i32, model_id: i32) -> Vec<u8> {
+    let mut content = ints(&[node_id]);
+    content.extend(dict(&[]));
+    content.extend(ints(&[1, model_id]));
+    content.extend(dict(&[]));
+    chunk(b"nSHP", &content, &[])
+}
+
+fn world_position(matrix: &[f32; 16], local: Vec3) -> Vec3 {
+    Mat4::from_cols_array(matrix).transform_point3(local)
+}
+
+#[test]
+fn reads_single_model_with_palette() {
+    let mut rgba = Vec::new();
+    for i in 0..256u32 {
+        rgba.extend([i as u8, 0, 0, 255]);
+    }
+    let bytes = file(&[
+        size(2, 3, 4),
+        xyzi(&[[0, 0, 0, 1], [1, 2, 3, 7]]),
+        chunk(b"RGBA", &rgba, &[]),
+    ]);
+
+    let scene = read_vox(&bytes).unwrap();
+    assert_eq!(scene.palette.len(), 256);
+    assert_eq!(scene.palette[0], RGBA(0, 0, 0, 0));
+    assert_eq!(scene.palette[1], RGBA(0, 0, 0, 255));
+    assert_eq!(scene.palette[7], RGBA(6, 0, 0, 255));
+
+    assert_eq!(scene.objects.len(), 1);
+    let obj = &scene.objects[0];
+    assert_eq!(obj.id, "model_0");
+    assert_eq!(obj.dims, [2, 3, 4]);
+    assert_eq!(obj.voxels.len(), 24);
+    assert_eq!(obj.voxels[0], 1);
+    assert_eq!(obj.voxels[1 + 2 * (2 + 3 * 3)], 7);
+    assert_eq!(obj.voxels.iter().filter(|&&v| v != 0).count(), 2);
+    assert!(scene.validate().is_ok());
+}
+
+#[test]
+fn uses_default_palette_without_rgba_chunk() {
+    let scene = read_vox(&file(&[size(1, 1, 1), xyzi(&[[0, 0, 0, 1]])])).unwrap();
+    assert_eq!(scene.palette.len(), 256);
+    assert_eq!(scene.palette[1], RGBA(255, 255, 255, 255));
+    assert_eq!(scene.palette[255], RGBA(0x11, 0x11, 0x11, 255));
+}
+
+#[test]
+fn scene_graph_becomes_model_matrices() {
+    // Root transform -> group -> transform (translated, rotated 90° about Z) -> shape.
+    // `_r = 17` puts -1 at row 0 col 1 and +1 at row 1 col 0.
+    let bytes = file(&[
+        size(2, 2, 2),
+        xyzi(&[[0, 0, 0, 1]]),
+        transform(0, "root", 1, &[]),
+        group(1, &[2]),
+        transform(2, "crate", 3, &[("_t", "10 20 30"), ("_r", "17")]),
+        shape(3, 0),
+    ]);
+
+    let scene = read_vox(&bytes).unwrap();
+    assert_eq!(scene.objects.len(), 1);
+    let obj = &scene.objects[0];
+    assert_eq!(obj.id, "crate");
+
+    // The unit cube center maps to the node translation, converted from Z-up to Y-up.
+    let center = world_position(&obj.model_matrix(), Vec3::ZERO);
+    assert!(center.abs_diff_eq(Vec3::new(10.0, 30.0, -20.0), 1e-4));
+
+    // Local +X rotates onto MagicaVoxel's +Y, which is -Z in Y-up space.
+    let x_axis = world_position(&obj.model_matrix(), Vec3::new(0.5, 0.0, 0.0)) - center;
+    assert!(x_axis.abs_diff_eq(Vec3::new(0.0, 0.0, -1.0), 1e-4));
+}
+
+#[test]
+fn shared_models_get_unique_ids() {
+    let bytes = file(&[
+        size(1, 1, 1),
+        xyzi(&[[0, 0, 0, 1]]),
+        transform(0, "root", 1, &[]),
+        group(1, &[2, 4]),
+        transform(2, "tree", 3, &[]),
+        shape(3, 0),
+        transform(4, "tree", 5, &[("_t", "5 0 0")]),
+        shape(5, 0),
+    ]);
+
+    let scene = read_vox(&bytes).unwrap();
+    let ids: Vec<_> = scene.objects.iter().map(|o| o.id.as_str()).collect();
+    assert_eq!(ids, ["tree", "tree_1"]);
+}
+
+#[test]
+fn rejects_malformed_input() {
+    assert_eq!(
+        read_vox(b"NOPE\0\0\0\0").err(),
+        Some(VoxError::InvalidMagic)
+    );
+
+    let err = read_vox(&file(&[xyzi(&[[0, 0, 0, 1]])])).unwrap_err();
+    assert!(matches!(err, VoxError::MalformedChunk { chunk, .. } if chunk == "XYZI"));
+
+    let err = read_vox(&file(&[size(2, 2, 2), xyzi(&[[2, 0, 0, 1]])])).unwrap_err();
+    assert!(matches!(err, VoxError::MalformedChunk { chunk, .. } if chunk == "XYZI"));
+
+    let err = read_vox(&file(&[size(0, 2, 2)])).unwrap_err();
+    assert!(matches!(err, VoxError::MalformedChunk { chunk, .. } if chunk == "SIZE"));
+
+    // XYZI claims two voxels but carries one.
+    let mut truncated = ints(&[2]);
+    truncated.extend([0, 0, 0, 1]);
+    let err = read_vox(&file(&[size(1, 1, 1), chunk(b"XYZI", &truncated, &[])])).unwrap_err();
+    assert_eq!(
+        err,
+        VoxError::UnexpectedEof {
+            chunk: "XYZI".to_owned()
+        }
+    );
+
+    let err = read_vox(&file(&[
+        size(1, 1, 1),
+        xyzi(&[[0, 0, 0, 1]]),
+        transform(0, "root", 1, &[]),
+        shape(1, 3),
+    ]))
+    .unwrap_err();
+    assert!(matches!(
+        err,
+        VoxError::InvalidSceneGraph { node_id: 1, .. }
+    ));
+}
+
+#[test]
+fn rejects_shared_and_cyclic_nodes() {
+    // Groups sharing both of their children: walked once per path, 2^24 shapes would come out.
+    // The deepest link is the first node reached twice.
+    let mut nodes = vec![transform(0, "root", 1, &[])];
+    for level in 0..24 {
+        let id = 1 + 2 * level;
+        nodes.push(group(id, &[id + 1, id + 1]));
+        nodes.push(transform(id + 1, "link", id + 2, &[]));
+    }
+    nodes.push(shape(49, 0));
+    let mut children = vec![size(1, 1, 1), xyzi(&[[0, 0, 0, 1]])];
+    children.extend(nodes);
+    let err = read_vox(&file(&children)).unwrap_err();
+    assert_eq!(
+        err,
+        VoxError::InvalidSceneGraph {
+            node_id: 48,
+            reason: "node is reached more than once".to_owned()
+        }
+    );
+
+    let err = read_vox(&file(&[
+        size(1, 1, 1),
+        xyzi(&[[0, 0, 0, 1]]),
+        transform(0, "root", 1, &[]),
+        group(1, &[2]),
+        transform(2, "loop", 1, &[]),
+    ]))
+    .unwrap_err();
+    assert!(matches!(
+        err,
+        VoxError::InvalidSceneGraph { node_id: 1, .. }
+    ));
+}
+
+/// World-space center of every solid voxel, paired with its palette index.
+fn solid_voxel_centers(scene: &Scene) -> Vec<([i32; 3], u8)> {
+    let mut centers = Vec::new();
+    for obj in &scene.objects {
+        let [nx, ny, nz] = obj.dims;
+        let dims = Vec3::new(nx as f32, ny as f32, nz as f32);
+        for z in 0..nz {
+            for y in 0..ny {
+                for x in 0..nx {
+                    let index = obj.voxels[(x + nx * (y + ny * z)) as usize];
+                    if index == 0 {
+                        continue;
+                    }
+                    let local = (Vec3::new(x as f32, y as f32, z as f32) + 0.5) / dims - 0.5;
+                    let world = world_position(&obj.model_matrix(), local) * 2.0;
+                    centers.push((world.round().to_array().map(|v| v as i32), index));
+                }
+            }
+        }
+    }
+    centers.sort();
+    centers
+}
+
+fn object(id: &str, dims: [u32; 3], transform: Transform) -> VoxelObject {
+    let count = dims.iter().product::<u32>() as usize;
+    VoxelObject {
+        id: id.to_owned(),
+        transform,
+        dims,
+        voxels: (0..count).map(|i| (i % 3) as u8).collect(),
+    }
+}
+
+#[test]
+fn export_round_trips_through_import() {
+    let rotated = Transform::Trs {
+        // Odd extents need half-voxel offsets to sit on MagicaVoxel's grid.
+        translation: [4.5, -2.0, 7.5],
+        rotation: Quat::from_rotation_y(std::f32::consts::FRAC_PI_2).to_array(),
+        scale: [3.0, 4.0, 5.0],
+    };
+    let scene = Scene {
+        palette: vec![RGBA(0, 0, 0, 0), RGBA(255, 0, 0, 255), RGBA(0, 0, 255, 128)],
+        objects: vec![
+            object(
+                "block",
+                [2, 3, 4],
+                Transform::from_translation_scale([1.0, 1.5, -3.0], [2.0, 3.0, 4.0]),
+            ),
+            object("rotated", [3, 4, 5], rotated),
+        ],
+    };
+
+    let imported = read_vox(&write_vox(&scene).unwrap()).unwrap();
+    assert_eq!(imported.palette[..3], scene.palette[..]);
+    assert_eq!(imported.palette[3], RGBA(0, 0, 0, 0));
+    let ids: Vec<_> = imported.objects.iter().map(|o| o.id.as_str()).collect();
+    assert_eq!(ids, ["block", "rotated"]);
+    assert_eq!(solid_voxel_centers(&imported), solid_voxel_centers(&scene));
+}
+
+#[test]
+fn export_splits_objects_larger_than_256() {
+    let scene = Scene {
+        palette: vec![RGBA(0, 0, 0, 0), RGBA(1, 2, 3, 255), RGBA(4, 5, 6, 255)],
+        objects: vec![object(
+            "beam",
+            [300, 2, 1],
+            Transform::from_translation_scale([0.0, 0.0, 0.5], [300.0, 2.0, 1.0]),
+        )],
+    };
+
+    let imported = read_vox(&write_vox(&scene).unwrap()).unwrap();
+    let dims: Vec<_> = imported.objects.iter().map(|o| o.dims).collect();
+    assert_eq!(dims, [[256, 2, 1], [44, 2, 1]]);
+    assert_eq!(imported.objects[0].id, "beam_0");
+    assert_eq!(solid_voxel_centers(&imported), solid_voxel_centers(&scene));
+}
+
+#[test]
+fn export_rejects_unrepresentable_transforms() {
+    let scene = Scene {
+        palette: vec![RGBA(0, 0, 0, 0), RGBA(1, 2, 3, 255), RGBA(4, 5, 6, 255)],
+        objects: vec![object(
+            "stretched",
+            [2, 2, 2],
+            Transform::from_translation_scale([0.0; 3], [4.0, 2.0, 2.0]),
+        )],
+    };
+    assert_eq!(
+        write_vox(&scene).err(),
+        Some(VoxError::UnsupportedTransform {
+            id: "stretched".to_owned()
+        })
+    );
+}
@@ -12,6 +12,34 @@ type GPUData = {
   backend: string;
 };
 
+type Lighting = {
+  sun: {
+    direction: { x: number; y: number; z: number };
+    color: { r: number; g: number; b: number };
+    intensity: number;
+  };
+  ambient: { r: number; g: number; b: number };
+  shadows: {
+    mode: 'off' | 'hard' | 'soft';
+    bias: number;
+    samples: number;
+    light_radius: number;
+    sun_angle: number;
+  };
+};
+
+type RenderStats = {
+  drawn: number;
+  culled: number;
+};
+
+type PickHit = {
+  object_id: string;
+  voxel: [number, number, number];
+  normal: [number, number, number];
+  palette_index: number;
+};
+
 export function initializeRendererTools(pane: Pane, app: AppData, profilerData: ProfilerData): void {
   const settingsFolder = pane.addFolder({ title: 'Renderer Settings' });
   settingsFolder.addBinding(app, 'presentTarget', {
@@ -21,8 +49,100 @@ export function initializeRendererTools(pane: Pane, app: AppData, profilerData:
       { text: 'Normal', value: 1 },
       { text: 'Linear-Z', value: 2 },
       { text: 'Depth', value: 3 },
+      { text: 'Lit', value: 4 },
+      { text: 'Ambient Occlusion', value: 5 },
+      { text: 'Steps', value: 6 },
     ],
   });
+  const ao = { mode: 'off' };
+  settingsFolder
+    .addBinding(ao, 'mode', {
+      label: 'Ambient Occlusion',
+      options: [
+        { text: 'Off', value: 'off' },
+        { text: 'Shader', value: 'shader' },
+        { text: 'Precomputed', value: 'precomputed' },
+      ],
+    })
+    .on('change', ({ value }) => app.renderer.set_ao_mode(value));
+  const depth = { mode: 'exact' };
+  settingsFolder
+    .addBinding(depth, 'mode', {
+      label: 'Depth',
+      options: [
+        { text: 'Exact', value: 'exact' },
+        { text: 'Conservative', value: 'conservative' },
+      ],
+    })
+    .on('change', ({ value }) => {
+      try {
+        app.renderer.set_depth_mode(value);
+      } catch (error) {
+        console.warn(error);
+        depth.mode = 'exact';
+        settingsFolder.refresh();
+      }
+    });
+
+  const lighting: Lighting = {
+    sun: { direction: { x: -0.4, y: -1, z: -0.3 }, color: { r: 1, g: 1, b: 1 }, intensity: 1 },
+    ambient: { r: 0.2, g: 0.2, b: 0.2 },
+    shadows: { mode: 'off', bias: 0.01, samples: 8, light_radius: 0.1, sun_angle: 0.05 },
+  };
+  const lightingFolder = pane.addFolder({ title: 'Lighting' });
+  lightingFolder.addBinding(lighting.sun, 'direction', { label: 'Sun Direction' });
+  lightingFolder.addBinding(lighting.sun, 'color', { label: 'Sun Color', color: { type: 'float' } });
+  lightingFolder.addBinding(lighting.sun, 'intensity', { label: 'Sun Intensity', min: 0, max: 10 });
+  lightingFolder.addBinding(lighting, 'ambient', { label: 'Ambient', color: { type: 'float' } });
+  lightingFolder.addBinding(lighting.shadows, 'mode', {
+    label: 'Shadows',
+    options: [
+      { text: 'Off', value: 'off' },
+      { text: 'Hard', value: 'hard' },
+      { text: 'Soft', value: 'soft' },
+    ],
+  });
+  lightingFolder.addBinding(lighting.shadows, 'bias', { label: 'Shadow Bias', min: 0, max: 0.1 });
+  lightingFolder.addBinding(lighting.shadows, 'samples', { label: 'Shadow Samples', min: 1, max: 32, step: 1 });
+  lightingFolder.addBinding(lighting.shadows, 'light_radius', { label: 'Light Radius', min: 0, max: 1 });
+  lightingFolder.addBinding(lighting.shadows, 'sun_angle', { label: 'Sun Angle', min: 0, max: 0.2 });
+  lightingFolder.on('change', () => {
+    const { direction, color, intensity } = lighting.sun;
+    const { ambient } = lighting;
+    app.renderer.set_lighting({
+      sun: {
+        direction: [direction.x, direction.y, direction.z],
+        color: [color.r, color.g, color.b],
+        intensity,
+      },
+      ambient: [ambient.r, ambient.g, ambient.b],
+      shadows: lighting.shadows,
+    });
+  });
+
+  // What lies under the cursor, or under the crosshair while the camera holds the pointer.
+  const picked = { object: '-', voxel: '-', normal: '-', palette_index: 0 };
+  const pickingFolder = pane.addFolder({ title: 'Picking' });
+  pickingFolder.addBinding(picked, 'object', { label: 'Object', readonly: true });
+  pickingFolder.addBinding(picked, 'voxel', { label: 'Voxel', readonly: true });
+  pickingFolder.addBinding(picked, 'normal', { label: 'Normal', readonly: true });
+  pickingFolder.addBinding(picked, 'palette_index', {
+    label: 'Palette Index',
+    readonly: true,
+    format: (v) => Math.floor(v).toString(),
+  });
+  app.canvas.addEventListener('mousedown', async (event) => {
+    const { canvas } = app;
+    const locked = document.pointerLockElement === canvas;
+    const x = locked ? canvas.width / 2 : (event.offsetX * canvas.width) / canvas.clientWidth;
+    const y = locked ? canvas.height / 2 : (event.offsetY * canvas.height) / canvas.clientHeight;
+    const hit = (await app.renderer.pick(Math.floor(x), Math.floor(y))) as PickHit | null;
+    picked.object = hit?.object_id ?? '-';
+    picked.voxel = hit ? hit.voxel.join(', ') : '-';
+    picked.normal = hit ? hit.normal.join(', ') : '-';
+    picked.palette_index = hit?.palette_index ?? 0;
+    pickingFolder.refresh();
+  });
 
   const gpuData = app.renderer.get_gpu_info() as GPUData;
 
@@ -44,4 +164,15 @@ export function initializeRendererTools(pane: Pane, app: AppData, profilerData:
     readonly: true,
     format: (v) => v.toFixed(2),
   });
+  const renderStats = {
+    get drawn() {
+      return (app.renderer.get_render_stats() as RenderStats).drawn;
+    },
+    get culled() {
+      return (app.renderer.get_render_stats() as RenderStats).culled;
+    },
+  };
+  const countFormat = (v: number) => Math.floor(v).toString();
+  performanceFolder.addBinding(renderStats, 'drawn', { label: 'Drawn Objects', readonly: true, format: countFormat });
+  performanceFolder.addBinding(renderStats, 'culled', { label: 'Culled Objects', readonly: true, format: countFormat });
 }
@@ -1,12 +1,16 @@
-import { mat4, vec3 } from 'gl-matrix';
+import { mat4, quat, vec3 } from 'gl-matrix';
 
 /** RGBA color as [r, g, b, a] with values in 0..255 */
 export type RGBA = [number, number, number, number];
 
+/** Object placement; the inverse matrix is derived by the renderer */
+export type Transform =
+  | { trs: { translation: vec3; rotation: quat; scale: vec3 } }
+  | { matrix: mat4 };
+
 export interface VoxelObject {
   id: string;
-  model_matrix: mat4;
-  inv_model_matrix: mat4;
+  transform: Transform;
   dims: vec3;
   voxels: Uint8Array;
 }
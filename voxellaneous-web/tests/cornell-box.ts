@@ -1,4 +1,4 @@
-import { mat4, vec3 } from 'gl-matrix';
+import { quat, vec3 } from 'gl-matrix';
 import { Scene } from '../src/scene';
 
 function createUniformVoxelData([nx, ny, nz]: [number, number, number], paletteIndex: number): Uint8Array {
@@ -38,16 +38,10 @@ function createSphereVoxelData([nx, ny, nz]: [number, number, number], paletteIn
 }
 
 function addObjectToScene(scene: Scene, id: string, dims: vec3, translate: vec3, voxels: Uint8Array): void {
-  const modelMatrix = mat4.create();
-  mat4.translate(modelMatrix, modelMatrix, translate);
-  mat4.scale(modelMatrix, modelMatrix, dims);
-  const inverseModelMatrix = mat4.invert(mat4.create(), modelMatrix);
-
   scene.objects.push({
     id,
     dims,
-    model_matrix: modelMatrix,
-    inv_model_matrix: inverseModelMatrix,
+    transform: { trs: { translation: translate, rotation: quat.create(), scale: dims } },
     voxels,
   });
 }